@@ -0,0 +1,407 @@
+use crate::AAError;
+use crate::Verbosity;
+use crate::constants;
+use crate::input::Binding;
+use crate::input::InputMap;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use ash::vk;
+use clap::Parser;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+pub const DEFAULT_CONFIG:&str = "config.toml";
+pub const DEFAULT_MODEL:&str = "res/gltf/Fox.glb";
+
+static ASSET_ROOT:OnceLock<PathBuf> = OnceLock::new();
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowMode {
+    Windowed,
+    Fullscreen,
+    Borderless,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PresentMode {
+    Fifo,
+    FifoRelaxed,
+    Mailbox,
+    Immediate,
+}
+
+// gpu to prefer over the best rated one, by enumeration index or by a piece of its name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpuSelector {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub mode: WindowMode,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordingConfig {
+    // command the raw frames are piped to, {width} {height} and {fps} are replaced in its arguments
+    pub encoder: Option<String>,
+    // where the frame sequences go when there is no encoder
+    pub directory: PathBuf,
+    // record a turntable of the first model as soon as it is loaded and exit
+    pub autostart: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub models: Vec<PathBuf>,
+    pub window: WindowConfig,
+    pub validation: bool,
+    // validation message ids that are neither logged nor counted as errors
+    pub validation_suppress: Vec<String>,
+    // weld and reorder the meshes before uploading them
+    pub optimize_meshes: bool,
+    // read models from their baked caches, baking the ones that have none
+    pub asset_cache: bool,
+    // bake the models and exit without opening a window
+    pub bake: bool,
+    pub gpu: Option<GpuSelector>,
+    pub present_mode: PresentMode,
+    pub asset_root: PathBuf,
+    pub recording: RecordingConfig,
+    pub input: InputMap,
+    pub verbosity: Verbosity,
+}
+
+
+// the command line wins over the config file, which wins over the defaults
+#[derive(Debug, Parser)]
+#[command(version, about = "AdAstra renderer")]
+struct Cli {
+    /// toml file with the startup settings, config.toml is used when present
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// model to load at startup, can be repeated
+    #[arg(short, long = "model")]
+    models: Vec<PathBuf>,
+    #[arg(long)]
+    width: Option<u32>,
+    #[arg(long)]
+    height: Option<u32>,
+    #[arg(long, value_enum)]
+    window_mode: Option<WindowMode>,
+    /// enable the validation layers
+    #[arg(long, overrides_with = "no_validation")]
+    validation: bool,
+    /// disable the validation layers
+    #[arg(long)]
+    no_validation: bool,
+    /// validation message id name or number to ignore, can be repeated
+    #[arg(long = "suppress-validation")]
+    validation_suppress: Vec<String>,
+    /// weld and reorder the meshes for the vertex cache before uploading them
+    #[arg(long, overrides_with = "no_optimize_meshes")]
+    optimize_meshes: bool,
+    /// upload the meshes as they are authored
+    #[arg(long)]
+    no_optimize_meshes: bool,
+    /// read the models from the .bake files next to them, writing the missing or stale ones
+    #[arg(long, overrides_with = "no_asset_cache")]
+    asset_cache: bool,
+    /// always parse the models, without reading or writing .bake files
+    #[arg(long)]
+    no_asset_cache: bool,
+    /// bake the models into their .bake files and exit
+    #[arg(long)]
+    bake: bool,
+    /// gpu index or part of its name
+    #[arg(long)]
+    gpu: Option<String>,
+    #[arg(long, value_enum)]
+    present_mode: Option<PresentMode>,
+    /// directory the shaders and models are loaded from
+    #[arg(long)]
+    asset_root: Option<PathBuf>,
+    /// record a turntable of the first model and exit once it is written
+    #[arg(long)]
+    record: bool,
+    /// command the recorded frames are piped to as raw rgba, {width} {height} and {fps} get replaced
+    #[arg(long)]
+    encoder: Option<String>,
+    /// directory the recorded frame sequences are written to
+    #[arg(long)]
+    recording_directory: Option<PathBuf>,
+    /// more logging, can be repeated
+    #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// no logging
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    models: Option<Vec<PathBuf>>,
+    window: FileWindow,
+    validation: Option<bool>,
+    validation_suppress: Option<Vec<String>>,
+    optimize_meshes: Option<bool>,
+    asset_cache: Option<bool>,
+    gpu: Option<String>,
+    present_mode: Option<PresentMode>,
+    asset_root: Option<PathBuf>,
+    recording: FileRecording,
+    input: FileInput,
+    verbosity: Option<Verbosity>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileWindow {
+    width: Option<u32>,
+    height: Option<u32>,
+    mode: Option<WindowMode>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileRecording {
+    encoder: Option<String>,
+    directory: Option<PathBuf>,
+}
+
+// action or axis name to its bindings, a listed name replaces every default binding it had
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileInput {
+    actions: BTreeMap<String, Vec<Binding>>,
+    axes: BTreeMap<String, Vec<Binding>>,
+}
+
+
+impl Default for Config {
+    fn default() -> Self {
+        Self{
+            models: vec![PathBuf::from(DEFAULT_MODEL)],
+            window: WindowConfig{
+                width: constants::WIDTH,
+                height: constants::HEIGTH,
+                mode: WindowMode::Windowed,
+            },
+            validation: constants::VALIDATION,
+            validation_suppress: Vec::new(),
+            optimize_meshes: true,
+            asset_cache: true,
+            bake: false,
+            gpu: None,
+            present_mode: PresentMode::Mailbox,
+            asset_root: PathBuf::from("."),
+            recording: RecordingConfig{
+                encoder: None,
+                directory: PathBuf::from("recordings"),
+                autostart: false,
+            },
+            input: InputMap::default(),
+            verbosity: Verbosity::default(),
+        }
+    }
+}
+
+impl Config {
+    // parses the command line, exiting on bad arguments like any cli would
+    pub fn load() -> Result<Self, AAError> {
+        let cli = Cli::parse();
+        
+        let file = match &cli.config {
+            Some(path) => Some(FileConfig::read(path)?),
+            None => {
+                let default_path = Path::new(DEFAULT_CONFIG);
+                if default_path.is_file() {
+                    Some(FileConfig::read(default_path)?)
+                } else {
+                    None
+                }
+            }
+        };
+        
+        let mut holder = Self::default();
+        if let Some(file) = file {
+            holder.apply_file(file);
+        }
+        holder.apply_cli(cli);
+        Ok(holder)
+    }
+    
+//----
+    fn apply_file(&mut self, file:FileConfig) {
+        if let Some(models) = file.models {
+            self.models = models;
+        }
+        if let Some(width) = file.window.width {
+            self.window.width = width;
+        }
+        if let Some(height) = file.window.height {
+            self.window.height = height;
+        }
+        if let Some(mode) = file.window.mode {
+            self.window.mode = mode;
+        }
+        if let Some(validation) = file.validation {
+            self.validation = validation;
+        }
+        if let Some(validation_suppress) = file.validation_suppress {
+            self.validation_suppress = validation_suppress;
+        }
+        if let Some(optimize_meshes) = file.optimize_meshes {
+            self.optimize_meshes = optimize_meshes;
+        }
+        if let Some(asset_cache) = file.asset_cache {
+            self.asset_cache = asset_cache;
+        }
+        if let Some(gpu) = file.gpu {
+            self.gpu = Some(GpuSelector::from(gpu.as_str()));
+        }
+        if let Some(present_mode) = file.present_mode {
+            self.present_mode = present_mode;
+        }
+        if let Some(asset_root) = file.asset_root {
+            self.asset_root = asset_root;
+        }
+        if let Some(encoder) = file.recording.encoder {
+            self.recording.encoder = Some(encoder);
+        }
+        if let Some(directory) = file.recording.directory {
+            self.recording.directory = directory;
+        }
+        self.input.merge(file.input.actions, file.input.axes);
+        if let Some(verbosity) = file.verbosity {
+            self.verbosity = verbosity;
+        }
+    }
+    
+//----
+    fn apply_cli(&mut self, cli:Cli) {
+        if !cli.models.is_empty() {
+            self.models = cli.models;
+        }
+        if let Some(width) = cli.width {
+            self.window.width = width;
+        }
+        if let Some(height) = cli.height {
+            self.window.height = height;
+        }
+        if let Some(mode) = cli.window_mode {
+            self.window.mode = mode;
+        }
+        if cli.validation {
+            self.validation = true;
+        }
+        if cli.no_validation {
+            self.validation = false;
+        }
+        if !cli.validation_suppress.is_empty() {
+            self.validation_suppress = cli.validation_suppress;
+        }
+        if cli.optimize_meshes {
+            self.optimize_meshes = true;
+        }
+        if cli.no_optimize_meshes {
+            self.optimize_meshes = false;
+        }
+        if cli.asset_cache {
+            self.asset_cache = true;
+        }
+        if cli.no_asset_cache {
+            self.asset_cache = false;
+        }
+        self.bake = cli.bake;
+        if let Some(gpu) = cli.gpu {
+            self.gpu = Some(GpuSelector::from(gpu.as_str()));
+        }
+        if let Some(present_mode) = cli.present_mode {
+            self.present_mode = present_mode;
+        }
+        if let Some(asset_root) = cli.asset_root {
+            self.asset_root = asset_root;
+        }
+        self.recording.autostart = cli.record;
+        if let Some(encoder) = cli.encoder {
+            self.recording.encoder = Some(encoder);
+        }
+        if let Some(directory) = cli.recording_directory {
+            self.recording.directory = directory;
+        }
+        if cli.quiet {
+            self.verbosity = Verbosity::Silent;
+        }
+        match cli.verbose {
+            0 => {}
+            1 => {self.verbosity = Verbosity::Expresive;}
+            _ => {self.verbosity = Verbosity::Dump;}
+        }
+    }
+}
+
+
+impl FileConfig {
+    fn read(path:&Path) -> Result<Self, AAError> {
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|err|err.into())
+    }
+}
+
+
+impl From<&str> for GpuSelector {
+    fn from(value:&str) -> Self {
+        match value.parse::<usize>() {
+            Ok(index) => GpuSelector::Index(index),
+            Err(_) => GpuSelector::Name(value.to_lowercase()),
+        }
+    }
+}
+
+impl GpuSelector {
+    pub fn matches(&self, index:usize, name:&str) -> bool {
+        match self {
+            GpuSelector::Index(selected) => *selected == index,
+            GpuSelector::Name(selected) => name.to_lowercase().contains(selected.as_str()),
+        }
+    }
+}
+
+
+impl PresentMode {
+    pub fn underlying(self) -> vk::PresentModeKHR {
+        match self {
+            PresentMode::Fifo => vk::PresentModeKHR::FIFO,
+            PresentMode::FifoRelaxed => vk::PresentModeKHR::FIFO_RELAXED,
+            PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+            PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+        }
+    }
+}
+
+
+// can only be set once, before anything is loaded
+pub fn set_asset_root(root:&Path) {
+    ASSET_ROOT.set(root.to_path_buf()).expect("asset root should only be set at startup");
+}
+
+// relative paths are taken from the asset root, absolute ones are kept as they are
+pub fn asset_path<P: AsRef<Path>>(path:P) -> PathBuf {
+    match ASSET_ROOT.get() {
+        Some(root) => root.join(path),
+        None => path.as_ref().to_path_buf(),
+    }
+}
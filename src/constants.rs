@@ -32,13 +32,14 @@ pub mod sc_max_images {
 
 pub const SHADER_START:&CStr = cstr::cstr!(b"main");
 
+// defaults for the settings the config file and the command line can override
 pub const WIDTH:u32 = 1200/16*16;
 pub const HEIGTH:u32 = 800/16*16;
 pub const VALIDATION:bool = true;
+
 pub const LOGGING:bool = true;
 
 pub const LAYERS:[&'static str; 1] = ["VK_LAYER_KHRONOS_validation"];
-pub const EXTENSIONS:[&'static str; EXTENSIONS_LEN_PLUS_VAL] = extension_logic();
 
 pub const DEVICE_EXTENSIONS:[&'static str; 5] = const_array!(
     Swapchain::name(), 
@@ -50,18 +51,9 @@ pub const DEVICE_EXTENSIONS:[&'static str; 5] = const_array!(
 
 
 
-const BASE_EXTENSIONS:[&'static str; 0] = [];
-const DEBUG_EXTENSIONS:[&'static str; 1] = const_array!(DebugUtils::name());
-
-const EXTENSIONS_LEN_PLUS_VAL:usize = extencion_len_logic();
-
-const fn extencion_len_logic() -> usize {
-    if VALIDATION {
-        BASE_EXTENSIONS.len()+DEBUG_EXTENSIONS.len()
-    } else {
-        BASE_EXTENSIONS.len() 
-    }
-}
+// the debug ones are only enabled together with validation
+pub const BASE_EXTENSIONS:[&str; 0] = [];
+pub const DEBUG_EXTENSIONS:[&str; 1] = const_array!(DebugUtils::name());
 
 const fn cstr_to_str(value:&'static CStr) -> &'static str {
     let extension:&str = match value.to_str() {
@@ -71,31 +63,13 @@ const fn cstr_to_str(value:&'static CStr) -> &'static str {
     extension
 }
 
-const fn extension_logic() -> [&'static str; EXTENSIONS_LEN_PLUS_VAL] {
-    
-    let mut holder = [""; EXTENSIONS_LEN_PLUS_VAL];
-    let mut elem = 0;
-    let base_extension_len = BASE_EXTENSIONS.len();
-    while elem<base_extension_len {
-        holder[elem] = BASE_EXTENSIONS[elem];
-        elem+=1;
-    }
-    if VALIDATION {
-        elem=0;
-        while elem<DEBUG_EXTENSIONS.len() {
-            holder[base_extension_len+elem] = DEBUG_EXTENSIONS[elem];
-            elem+=1;
-        }
-    }
-    holder
-}
-
 
 pub mod comp {
     #[allow(dead_code)]
     pub const COMP_SHADER:&str = "res/shaders/sh.comp.spv";
     pub const GRADIENT_SHADER:&str = "res/shaders/gradient_color.comp.spv";
     pub const SKY_SHADER:&str = "res/shaders/sky.comp.spv";
+    pub const CULL_SHADER:&str = "res/shaders/cull.comp.spv";
 }
 
 
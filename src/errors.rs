@@ -4,6 +4,7 @@ use std::io;
 use ash::vk;
 use sdl2::video;
 use gpu_allocator as gpu;
+use imgui_rs_vulkan_renderer as imgui_rend;
 
 
 #[derive(Error, Debug)]
@@ -38,6 +39,32 @@ pub enum Error {
     #[error("cant create mesh from empty arrays")]
     EmptyMesh,
     
+    #[error("upload of {0} bytes does not fit in the staging ring")]
+    UploadTooLarge(u64),
+    
+    #[error("no space left in the geometry buffers")]
+    GeometryBufferFull,
+    
+    #[error("bindless {0} table is full")]
+    BindlessTableFull(&'static str),
+    
+    #[error("mesh {mesh} primitive {primitive} has no positions")]
+    GltfMissingPositions{mesh:String, primitive:usize},
+    #[error("mesh {mesh} primitive {primitive} index {index} is out of its {vertices} vertices")]
+    GltfIndexOutOfRange{mesh:String, primitive:usize, index:u32, vertices:usize},
+    #[error("mesh {mesh} primitive {primitive} uses {mode:?}, only triangles are supported")]
+    GltfUnsupportedMode{mesh:String, primitive:usize, mode:gltf::mesh::Mode},
+    #[error("gltf image {0} is missing or its uri is malformed")]
+    GltfImage(usize),
+    
+    #[error("baked cache not usable: {0}")]
+    BakeRejected(&'static str),
+    
+    #[error("recording encoder command is empty")]
+    EmptyEncoder,
+    #[error("input binding {0} not understood, expected something like key:W, -stick:leftx or button:a")]
+    InvalidBinding(String),
+    
     
     #[error("invalid load transform")]
     InvalidLoadTransform,
@@ -45,8 +72,12 @@ pub enum Error {
     LobjError(#[from] tobj::LoadError),
     #[error("VK error")]
     VkError(#[from] vk::Result),
+    #[error("vulkan loader error")]
+    LoaderError(#[from] ash::LoadingError),
     #[error("gpu_allocator error")]
     GPUAlocError(#[from] gpu::AllocationError),
+    #[error("imgui renderer error")]
+    ImguiRendererError(#[from] imgui_rend::RendererError),
     #[error("IO error")]
     IoError(#[from] io::Error),
     #[error("string error")]
@@ -56,9 +87,25 @@ pub enum Error {
     
     #[error("gltf error")]
     GLTFError(#[from] gltf::Error),
+    #[error("image decoding error")]
+    ImageError(#[from] image::ImageError),
+    #[error("base64 error")]
+    Base64Error(#[from] base64::DecodeError),
+    #[error("config error: {0}")]
+    ConfigError(#[from] toml::de::Error),
     
 }
 
+impl Error {
+    // the only error the renderer can't go on from without being built again, the rest only cost a frame
+    pub fn is_device_lost(&self) -> bool {
+        matches!(
+            self,
+            Error::VkError(vk::Result::ERROR_DEVICE_LOST) | Error::ImguiRendererError(imgui_rend::RendererError::Vulkan(vk::Result::ERROR_DEVICE_LOST))
+        )
+    }
+}
+
 /*
 use crate::errors::messages:: ;
 */
@@ -68,13 +115,8 @@ pub mod messages {
     pub const SIMPLE_VK_FN:&'static str = "simple vk functions should not fail";
     pub const SIMPLE_SDL_FN:&'static str = "simple sdl2 functions should not fail";
     pub const BAD_DESTRUCTOR:&'static str = "destruct did not receive the right information";
-    pub const GPU_ALLOCATION:&'static str = "gpu allocation should not fail";
-    pub const GPU_FREE:&'static str = "gpu free should not fail";
     pub const STANDARD_CONV:&'static str = "conversion is granted by de standard";
     pub const GRANTED:&'static str = "things the programer knows but the compiled does not";
-    pub const RESOURCE_FILE:&'static str = "the resource file should be found in a particular path";
-    
-    pub const VK_UNRECOVERABLE:&'static str = "things the programer knows but the compiled does not";
     
     pub const COMPILETIME_ASSERT:&'static str = "things the programer knows but the compiled does not";
     pub const MODEL_DENSITY:&'static str = "model vertex indices should fit in a u32";
@@ -87,7 +129,6 @@ pub mod messages {
     pub const REDUNDANT_DESTRUCTOR:&'static str = "object was allready set to be defered destructed";
     pub const LEAKING_OBJECTS:&'static str = "destruction stack is being dropped before dispatching";
     
-    pub const SU_COMMAND_FAIL:&'static str = "single use instant command should not fail";
     pub const CPU_ACCESIBLE:&'static str = "memory should be granted to be cpu accesible";
     
     pub const RESOURCE_REFERENCED:&'static str = "resource is still reference somewhere";
@@ -110,3 +151,19 @@ pub enum Error {
 }
 
 */
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn only_device_loss_rebuilds() {
+        assert!(Error::VkError(vk::Result::ERROR_DEVICE_LOST).is_device_lost());
+        assert!(Error::ImguiRendererError(imgui_rend::RendererError::Vulkan(vk::Result::ERROR_DEVICE_LOST)).is_device_lost());
+        assert!(!Error::VkError(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY).is_device_lost());
+        assert!(!Error::VkError(vk::Result::ERROR_OUT_OF_POOL_MEMORY).is_device_lost());
+        assert!(!Error::GPUAlocError(gpu::AllocationError::OutOfMemory).is_device_lost());
+        assert!(!Error::UploadTooLarge(1).is_device_lost());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::logger;
+
+use std::time::Instant;
+
+
+// simulated seconds per tick, gameplay only ever sees this delta
+pub const TICK:f32 = 1.0/60.0;
+// a long frame runs at most this many ticks, the rest of the backlog is dropped instead of snowballing
+const MAX_TICKS_PER_FRAME:u32 = 8;
+
+// set from the gui, kept between frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationControls {
+    pub paused: bool,
+    pub time_scale: f32,
+}
+
+// real time goes into the accumulator scaled, whole ticks come out of it
+pub struct Clock {
+    last: Instant,
+    accumulator: f32,
+    controls: SimulationControls,
+    step_requested: bool,
+}
+
+
+impl Default for SimulationControls {
+    fn default() -> Self {
+        Self{
+            paused: false,
+            time_scale: 1.0,
+        }
+    }
+}
+
+
+impl Clock {
+    pub fn new() -> Self {
+        Self{
+            last: Instant::now(),
+            accumulator: 0.0,
+            controls: SimulationControls::default(),
+            step_requested: false,
+        }
+    }
+    
+//----
+    pub fn set_controls(&mut self, controls:SimulationControls) {
+        self.controls = SimulationControls{
+            time_scale: controls.time_scale.max(0.0),
+            ..controls
+        };
+    }
+    
+//----
+    // runs one tick on the next advance while paused
+    pub fn request_step(&mut self) {
+        self.step_requested = true;
+    }
+    
+//----
+    // ticks due since the last call, called once per rendered frame
+    pub fn advance(&mut self) -> u32 {
+        let elapsed = self.last.elapsed().as_secs_f32();
+        self.advance_by(elapsed)
+    }
+    
+//----
+    // same as advance for a frame of a given length, recordings step by their timestep however long rendering took
+    pub fn advance_by(&mut self, elapsed:f32) -> u32 {
+        self.last = Instant::now();
+        
+        if self.controls.paused {
+            self.accumulator = 0.0;
+            return u32::from(std::mem::take(&mut self.step_requested));
+        }
+        self.step_requested = false;
+        
+        self.accumulator += elapsed * self.controls.time_scale;
+        let due = (self.accumulator / TICK) as u32;
+        let ticks = due.min(MAX_TICKS_PER_FRAME);
+        if due > ticks {
+            logger::various_log!("clock",
+                (logger::Debug, "{} ticks behind, dropped", due - ticks)
+            );
+            self.accumulator = 0.0;
+        } else {
+            self.accumulator -= ticks as f32 * TICK;
+        }
+        ticks
+    }
+    
+//----
+    pub fn is_paused(&self) -> bool {
+        self.controls.paused
+    }
+    
+//----
+    // how far the frame is between the previous tick and the current one, a paused frame shows the current one
+    pub fn alpha(&self) -> f32 {
+        if self.controls.paused {
+            return 1.0;
+        }
+        (self.accumulator / TICK).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,161 @@
+use crate::vulkan::MeshHandle;
+use crate::vulkan::MaterialHandle;
+
+use super::World;
+use super::Entity;
+
+use nalgebra as na;
+use na::Matrix4;
+use na::Point3;
+use na::UnitQuaternion;
+use na::Vector3;
+use na::Vector4;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshRenderer {
+    pub mesh: MeshHandle,
+    // replaces the material of every surface, None keeps the ones of the file
+    pub material: Option<MaterialHandle>,
+    pub tint: Vector4<f32>,
+}
+
+// the transform before the last tick, rendering blends it with the current one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviousTransform(pub Transform);
+
+// the view comes from the transform of the entity, the projection from the gui unless overridden
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub active: bool,
+    // degrees
+    pub vertical_fov: Option<f32>,
+}
+
+// directional, the renderer only has a sun so the first light found is used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    // points towards the light
+    pub direction: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    pub ambient: Vector3<f32>,
+}
+
+// moved by the fly system from the input axes, the held ones are set every frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlyCamera {
+    // units per second
+    pub speed: f32,
+    // radians per second
+    pub turn_speed: f32,
+    // right, up and forward, each in -1..1
+    pub movement: Vector3<f32>,
+    // right and up
+    pub look: na::Vector2<f32>,
+    // mouse motion added up over frames, the next tick uses it once and clears it
+    pub movement_motion: Vector3<f32>,
+    pub look_motion: na::Vector2<f32>,
+}
+
+// radians per second around axis, applied by the spin system
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spin {
+    pub axis: na::Unit<Vector3<f32>>,
+    pub speed: f32,
+}
+
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self{
+            translation: Vector3::zeros(),
+            rotation: UnitQuaternion::identity(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn looking_at(eye:Point3<f32>, target:Point3<f32>, up:&Vector3<f32>) -> Self {
+        // face_towards keeps +z on the direction, the camera looks down -z
+        let rotation = UnitQuaternion::face_towards(&(eye - target), up);
+        Self{
+            translation: eye.coords,
+            rotation,
+            ..Self::default()
+        }
+    }
+    
+    pub fn interpolate(&self, next:&Transform, alpha:f32) -> Self {
+        Self{
+            translation: self.translation.lerp(&next.translation, alpha),
+            rotation: self.rotation.slerp(&next.rotation, alpha),
+            scale: self.scale.lerp(&next.scale, alpha),
+        }
+    }
+    
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation) * self.rotation.to_homogeneous() * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+}
+
+// where the entity is drawn, alpha of 0 is the previous tick and 1 the current one
+pub fn render_transform(world:&World, entity:Entity, alpha:f32) -> Option<Transform> {
+    let current = world.get::<Transform>(entity)?;
+    match world.get::<PreviousTransform>(entity) {
+        Some(previous) => Some(previous.0.interpolate(current, alpha)),
+        None => Some(*current),
+    }
+}
+
+impl MeshRenderer {
+    pub fn new(mesh:MeshHandle) -> Self {
+        Self{
+            mesh,
+            material: None,
+            tint: Vector4::new(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self{
+            speed: 2.0,
+            turn_speed: 1.5,
+            movement: Vector3::zeros(),
+            look: na::Vector2::zeros(),
+            movement_motion: Vector3::zeros(),
+            look_motion: na::Vector2::zeros(),
+        }
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self{
+            active: true,
+            vertical_fov: None,
+        }
+    }
+}
+
+// the values the scene data had before lights were components
+impl Default for Light {
+    fn default() -> Self {
+        Self{
+            direction: Vector3::new(1.0, 1.0, 1.0),
+            color: Vector3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            ambient: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
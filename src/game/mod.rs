@@ -1,15 +1,31 @@
-use nalgebra as na;
-use crate::window::Window;
+mod world;
+pub use world::*;
+
+mod components;
+pub use components::*;
+
+mod schedule;
+pub use schedule::*;
+
+mod clock;
+pub use clock::*;
+
 use crate::logger;
+use crate::gui::InputData;
+use crate::input;
+use crate::input::InputState;
+use crate::vulkan::MeshHandle;
 
-#[derive(Default)]
-pub struct Game{
-    player: Player,
-}
+use nalgebra as na;
 
-#[derive(Default)]
-pub struct Player{
-    postion: na::Vector3<f32>,
+
+pub struct Game{
+    world: World,
+    schedule: Schedule,
+    // shows the mesh picked in the gui
+    display: Entity,
+    camera: Entity,
+    clock: Clock,
 }
 
 
@@ -24,23 +40,105 @@ impl Drop for Game {
 
 impl Game {
     pub fn init() -> Self {
-        Game::default()
+        let mut world = World::new();
+        
+        let display = world.spawn();
+        world.insert(display, Transform::default());
+        world.insert(display, Spin{axis:na::Vector3::y_axis(), speed:0.0});
+        
+        // where the renderer used to look from
+        let camera = world.spawn();
+        world.insert(camera, Transform::looking_at(na::Point3::new(1.5, -1.5, 5.0), na::Point3::new(1.5, -1.5, 0.0), &na::Vector3::y()));
+        world.insert(camera, Camera::default());
+        world.insert(camera, FlyCamera::default());
+        
+        let sun = world.spawn();
+        world.insert(sun, Light::default());
+        
+        let mut schedule = Schedule::new();
+        schedule.add("spin", spin);
+        schedule.add("fly", fly);
+        
+        Game{
+            world,
+            schedule,
+            display,
+            camera,
+            clock: Clock::new(),
+        }
     }
-    pub fn step(&mut self, window: &mut Window) {
-        println!("advancing logic");
-        logger::various_log!("Game",
-            (logger::Trace, "advancing game logic")
-        );
+    
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+    
+    // None hides the display entity, the handle can also go stale once its scene is unloaded
+    pub fn show_mesh(&mut self, mesh:Option<MeshHandle>) {
+        let current = self.world.get::<MeshRenderer>(self.display).map(|renderer|renderer.mesh);
+        if current == mesh {
+            return;
+        }
+        match mesh {
+            Some(mesh) => {
+                self.world.insert(self.display, MeshRenderer::new(mesh));
+            }
+            None => {
+                self.world.remove::<MeshRenderer>(self.display);
+            }
+        }
+    }
+    
+    // passed to the renderer to blend the last two ticks
+    pub fn alpha(&self) -> f32 {
+        self.clock.alpha()
+    }
+    
+    pub fn gui_tick(&mut self, data:&InputData) {
+        if let Some(spin) = self.world.get_mut::<Spin>(self.display) {
+            spin.speed = data.spin_speed;
+        }
+        self.clock.set_controls(data.simulation);
+        if data.simulation_stepped {
+            self.clock.request_step();
+        }
+    }
+    
+    // held axes are sampled once per frame, every tick until the next frame sees the same value,
+    // mouse motion piles up until a tick uses it, a paused clock only keeps the motion of this frame
+    pub fn input_tick(&mut self, input:&InputState) {
+        let paused = self.clock.is_paused();
+        if let Some(fly) = self.world.get_mut::<FlyCamera>(self.camera) {
+            fly.movement = na::Vector3::new(input.axis(input::MOVE_RIGHT), input.axis(input::MOVE_UP), input.axis(input::MOVE_FORWARD));
+            fly.look = na::Vector2::new(input.axis(input::LOOK_RIGHT), input.axis(input::LOOK_UP));
+            if paused {
+                fly.movement_motion = na::Vector3::zeros();
+                fly.look_motion = na::Vector2::zeros();
+            }
+            fly.movement_motion += na::Vector3::new(input.motion(input::MOVE_RIGHT), input.motion(input::MOVE_UP), input.motion(input::MOVE_FORWARD));
+            fly.look_motion += na::Vector2::new(input.motion(input::LOOK_RIGHT), input.motion(input::LOOK_UP));
+        }
+    }
+    
+    // once per rendered frame, the systems only run on whole ticks so the frame rate can't change the outcome,
+    // a frame_time replaces the real time the frame took
+    pub fn step(&mut self, frame_time:Option<f32>) {
+        let ticks = match frame_time {
+            Some(elapsed) => self.clock.advance_by(elapsed),
+            None => self.clock.advance(),
+        };
+        for _ in 0..ticks {
+            self.store_previous();
+            logger::various_log!("Game",
+                (logger::Trace, "advancing game logic")
+            );
+            self.schedule.run(&mut self.world, TICK);
+        }
+    }
+    
+    fn store_previous(&mut self) {
+        let transforms:Vec<_> = self.world.query::<Transform>().map(|(entity, transform)|(entity, *transform)).collect();
+        for (entity, transform) in transforms {
+            self.world.insert(entity, PreviousTransform(transform));
+        }
     }
 }
-
-
-
-
-
-
-
-
-
-
-
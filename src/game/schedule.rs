@@ -0,0 +1,78 @@
+use crate::logger;
+
+use super::World;
+use super::Transform;
+use super::Spin;
+use super::FlyCamera;
+
+use nalgebra as na;
+use na::UnitQuaternion;
+use na::Vector2;
+use na::Vector3;
+
+
+pub type System = Box<dyn FnMut(&mut World, f32)>;
+
+// systems run in the order they were added, each one sees the changes of the previous ones
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<(&'static str, System)>,
+}
+
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+//----
+    pub fn add<F:FnMut(&mut World, f32) + 'static>(&mut self, name:&'static str, system:F) -> &mut Self {
+        self.systems.push((name, Box::new(system)));
+        self
+    }
+    
+//----
+    // delta is in seconds
+    pub fn run(&mut self, world:&mut World, delta:f32) {
+        for (name, system) in self.systems.iter_mut() {
+            logger::various_log!("schedule",
+                (logger::Trace, "running {}", name)
+            );
+            system(world, delta);
+        }
+    }
+}
+
+
+pub fn spin(world:&mut World, delta:f32) {
+    let spinning:Vec<_> = world.query::<Spin>().map(|(entity, spin)|(entity, *spin)).collect();
+    for (entity, spin) in spinning {
+        if let Some(transform) = world.get_mut::<Transform>(entity) {
+            transform.rotation = UnitQuaternion::from_axis_angle(&spin.axis, spin.speed * delta) * transform.rotation;
+        }
+    }
+}
+
+// turns around the world up and its own right, moves along where it looks, the camera looks down -z
+pub fn fly(world:&mut World, delta:f32) {
+    let flying:Vec<_> = world.query::<FlyCamera>().map(|(entity, fly)|(entity, *fly)).collect();
+    for (entity, fly) in flying {
+        // the mouse motion counts once, as if its axis was held for this tick
+        let look = fly.look + fly.look_motion;
+        let movement = fly.movement + fly.movement_motion;
+        if let Some(stored) = world.get_mut::<FlyCamera>(entity) {
+            stored.look_motion = Vector2::zeros();
+            stored.movement_motion = Vector3::zeros();
+        }
+        
+        if let Some(transform) = world.get_mut::<Transform>(entity) {
+            let yaw = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), -look.x * fly.turn_speed * delta);
+            let pitch = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), look.y * fly.turn_speed * delta);
+            transform.rotation = yaw * transform.rotation * pitch;
+            
+            let local = Vector3::new(movement.x, 0.0, -movement.z);
+            let movement = transform.rotation * local + Vector3::new(0.0, movement.y, 0.0);
+            transform.translation += movement * fly.speed * delta;
+        }
+    }
+}
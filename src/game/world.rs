@@ -0,0 +1,178 @@
+use crate::logger;
+use crate::errors::messages::GRANTED;
+
+use std::any::Any;
+use std::any::TypeId;
+use std::collections::HashMap;
+
+
+// the generation makes an entity that was despawned and whose slot got reused miss instead of aliasing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+// components of one type, indexed by the entity slot
+struct Storage<T> {
+    values: Vec<Option<T>>,
+}
+
+// lets despawn clear an entity from storages it does not know the type of
+trait AnyStorage {
+    fn remove_index(&mut self, index:usize);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// any 'static type can be a component, the built in ones live in components.rs
+#[derive(Default)]
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+}
+
+
+impl<T:'static> AnyStorage for Storage<T> {
+    fn remove_index(&mut self, index:usize) {
+        if let Some(value) = self.values.get_mut(index) {
+            *value = None;
+        }
+    }
+    
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Entity {
+    fn slot(self) -> usize {
+        self.index as usize
+    }
+}
+
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+//----
+    pub fn spawn(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = index as usize;
+                self.generations[slot] += 1;
+                self.alive[slot] = true;
+                Entity{index, generation:self.generations[slot]}
+            }
+            None => {
+                let index = u32::try_from(self.generations.len()).expect(GRANTED);
+                self.generations.push(0);
+                self.alive.push(true);
+                Entity{index, generation:0}
+            }
+        }
+    }
+    
+//----
+    // drops every component of the entity, false when it was already gone
+    #[allow(dead_code)]
+    pub fn despawn(&mut self, entity:Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        for storage in self.storages.values_mut() {
+            storage.remove_index(entity.slot());
+        }
+        self.alive[entity.slot()] = false;
+        self.free.push(entity.index);
+        true
+    }
+    
+    pub fn is_alive(&self, entity:Entity) -> bool {
+        self.alive.get(entity.slot()).copied().unwrap_or(false) && self.generations[entity.slot()] == entity.generation
+    }
+    
+//----
+    // replaces the component the entity had, which is returned
+    pub fn insert<T:'static>(&mut self, entity:Entity, component:T) -> Option<T> {
+        if !self.is_alive(entity) {
+            logger::various_log!("world",
+                (logger::Warn, "component {} added to the despawned {:?}", std::any::type_name::<T>(), entity)
+            );
+            return None;
+        }
+        let size = self.generations.len();
+        let storage = self.storage_mut::<T>();
+        if storage.values.len() < size {
+            storage.values.resize_with(size, ||None);
+        }
+        storage.values[entity.slot()].replace(component)
+    }
+    
+    pub fn remove<T:'static>(&mut self, entity:Entity) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.storage_mut::<T>().values.get_mut(entity.slot())?.take()
+    }
+    
+    pub fn get<T:'static>(&self, entity:Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.storage::<T>()?.values.get(entity.slot())?.as_ref()
+    }
+    
+    pub fn get_mut<T:'static>(&mut self, entity:Entity) -> Option<&mut T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.storage_mut::<T>().values.get_mut(entity.slot())?.as_mut()
+    }
+    
+//----
+    // entities with a T in spawn slot order, despawning clears the storages so every value belongs to a live entity
+    pub fn query<T:'static>(&self) -> impl Iterator<Item=(Entity, &T)> + '_ {
+        let generations = &self.generations;
+        self.storage::<T>().into_iter().flat_map(move |storage|{
+            storage.values.iter().enumerate().filter_map(move |(index, value)|{
+                let entity = Entity{index:index as u32, generation:generations[index]};
+                value.as_ref().map(|value|(entity, value))
+            })
+        })
+    }
+    
+//----
+    #[allow(dead_code)]
+    pub fn query_mut<T:'static>(&mut self) -> impl Iterator<Item=(Entity, &mut T)> + '_ {
+        let World{generations, storages, ..} = self;
+        let storage = storages.get_mut(&TypeId::of::<T>()).and_then(|storage|storage.as_any_mut().downcast_mut::<Storage<T>>());
+        let generations = &*generations;
+        storage.into_iter().flat_map(move |storage|{
+            storage.values.iter_mut().zip(generations.iter()).enumerate().filter_map(|(index, (value, generation))|{
+                let entity = Entity{index:index as u32, generation:*generation};
+                value.as_mut().map(|value|(entity, value))
+            })
+        })
+    }
+    
+    fn storage<T:'static>(&self) -> Option<&Storage<T>> {
+        self.storages.get(&TypeId::of::<T>())?.as_any().downcast_ref::<Storage<T>>()
+    }
+    
+    fn storage_mut<T:'static>(&mut self) -> &mut Storage<T> {
+        self.storages.entry(TypeId::of::<T>())
+            .or_insert_with(||Box::new(Storage::<T>{values:Vec::new()}))
+            .as_any_mut()
+            .downcast_mut::<Storage<T>>()
+            .expect(GRANTED)
+    }
+}
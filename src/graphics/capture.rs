@@ -0,0 +1,183 @@
+use crate::AAError;
+use crate::logger;
+use crate::errors::messages::GRANTED;
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use half::f16;
+use image::ImageFormat;
+use image::RgbaImage;
+use image::Rgba32FImage;
+
+const SCREENSHOT_DIRECTORY:&str = "screenshots";
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureResolution {
+    // the region of the canvas the scene is drawn to, before it is scaled to the window
+    #[default]
+    Internal,
+    Swapchain,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    // tonemapped to 8 bits
+    #[default]
+    Png,
+    // the raw hdr values
+    Exr,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureRequest {
+    pub resolution: CaptureResolution,
+    pub overlay: bool,
+    pub format: CaptureFormat,
+}
+
+// layout of the texels read back from the gpu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureLayout {
+    Rgba16Float,
+    Rgba8{bgr:bool, srgb:bool},
+}
+
+#[derive(Debug)]
+pub struct CapturedImage {
+    pub width: u32,
+    pub height: u32,
+    pub layout: CaptureLayout,
+    pub format: CaptureFormat,
+    pub data: Vec<u8>,
+}
+
+
+impl CaptureLayout {
+    pub fn texel_size(self) -> u64 {
+        match self {
+            CaptureLayout::Rgba16Float => 8,
+            CaptureLayout::Rgba8{..} => 4,
+        }
+    }
+}
+
+impl CapturedImage {
+    
+//----
+    // encoding a big capture takes long enough to stall the render loop
+    pub fn save_in_background(self) {
+        std::thread::spawn(move ||{
+            match self.save() {
+                Ok(path) => {
+                    logger::various_log!("capture",
+                        (logger::Info, "screenshot saved to {:?}", path)
+                    );
+                }
+                Err(error) => {
+                    logger::various_log!("capture",
+                        (logger::Error, "screenshot could not be saved {}", error)
+                    );
+                }
+            }
+        });
+    }
+    
+//----
+    pub fn save(&self) -> Result<PathBuf, AAError> {
+        std::fs::create_dir_all(SCREENSHOT_DIRECTORY)?;
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        self.save_as(PathBuf::from(SCREENSHOT_DIRECTORY).join(format!("screenshot_{}", stamp)))
+    }
+    
+//----
+    // the extension is set from the format
+    pub fn save_as(&self, mut path:PathBuf) -> Result<PathBuf, AAError> {
+        match self.format {
+            CaptureFormat::Png => {
+                path.set_extension("png");
+                self.to_ldr().save_with_format(&path, ImageFormat::Png)?;
+            }
+            CaptureFormat::Exr => {
+                path.set_extension("exr");
+                self.to_hdr().save_with_format(&path, ImageFormat::OpenExr)?;
+            }
+        }
+        Ok(path)
+    }
+    
+    fn to_hdr(&self) -> Rgba32FImage {
+        let texels:Vec<f32> = self.linear_texels().flatten().collect();
+        Rgba32FImage::from_raw(self.width, self.height, texels).expect(GRANTED)
+    }
+    
+//----
+    pub fn to_ldr(&self) -> RgbaImage {
+        let texels:Vec<u8> = match self.layout {
+            // the overlay capture already holds what was shown on screen
+            CaptureLayout::Rgba8{bgr, ..} => {
+                self.data.chunks_exact(4).flat_map(|texel|{
+                    match bgr {
+                        true => [texel[2], texel[1], texel[0], u8::MAX],
+                        false => [texel[0], texel[1], texel[2], u8::MAX],
+                    }
+                }).collect()
+            }
+            CaptureLayout::Rgba16Float => {
+                self.linear_texels().flat_map(|[red, green, blue, _]|{
+                    [tonemap(red), tonemap(green), tonemap(blue), u8::MAX]
+                }).collect()
+            }
+        };
+        RgbaImage::from_raw(self.width, self.height, texels).expect(GRANTED)
+    }
+    
+    fn linear_texels(&self) -> Box<dyn Iterator<Item=[f32; 4]> + '_> {
+        match self.layout {
+            CaptureLayout::Rgba16Float => {
+                Box::new(self.data.chunks_exact(8).map(|texel|{
+                    let channel = |index:usize|f16::from_le_bytes([texel[index*2], texel[index*2+1]]).to_f32();
+                    [channel(0), channel(1), channel(2), channel(3)]
+                }))
+            }
+            CaptureLayout::Rgba8{bgr, srgb} => {
+                Box::new(self.data.chunks_exact(4).map(move |texel|{
+                    let (red, blue) = match bgr {
+                        true => (texel[2], texel[0]),
+                        false => (texel[0], texel[2]),
+                    };
+                    let decode = |value:u8|{
+                        let value = value as f32 / u8::MAX as f32;
+                        if srgb {srgb_to_linear(value)} else {value}
+                    };
+                    [decode(red), decode(texel[1]), decode(blue), texel[3] as f32 / u8::MAX as f32]
+                }))
+            }
+        }
+    }
+}
+
+
+// narkowicz fit of the aces filmic curve followed by the srgb transfer function
+fn tonemap(value:f32) -> u8 {
+    let value = value.max(0.0);
+    let mapped = (value*(2.51*value + 0.03)) / (value*(2.43*value + 0.59) + 0.14);
+    (linear_to_srgb(mapped.clamp(0.0, 1.0)) * u8::MAX as f32).round() as u8
+}
+
+fn linear_to_srgb(value:f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0/2.4) - 0.055
+    }
+}
+
+fn srgb_to_linear(value:f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
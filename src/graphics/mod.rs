@@ -1,3 +1,15 @@
+mod profiling;
+pub use profiling::*;
+
+mod streaming;
+pub use streaming::*;
+
+mod capture;
+pub use capture::*;
+
+mod recording;
+pub use recording::*;
+
 use crate::errors::messages::COMPILETIME_ASSERT;
 
 use std::ops::Deref;
@@ -7,6 +19,7 @@ use std::mem::size_of;
 use arrayvec::ArrayString;
 use nalgebra as na;
 use na::Matrix4;
+use na::Vector2;
 use na::Vector4;
 use na::Vector3;
 
@@ -45,8 +58,38 @@ pub struct Vertex {
 	pub normal: Vector3<f32>,
 	pub uv_y: f32,
 	pub color: Vector4<f32>,
+	// xyz points along +u, w is the sign of the bitangent
+	pub tangent: Vector4<f32>,
+	pub uv1: Vector2<f32>,
+	pub padding: Vector2<f32>,
+}
+
+// the shaders read the vertices with the std430 stride of their struct, always a multiple of 16
+const _:() = if !size_of::<Vertex>().is_multiple_of(16) {
+    panic!("{}", COMPILETIME_ASSERT);
+};
+
+// axis aligned box in object space, used for culling
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Bounds {
+    pub center: Vector3<f32>,
+    pub extents: Vector3<f32>,
 }
 
+impl Bounds {
+    pub fn from_vertices(vertices:&[Vertex]) -> Self {
+        let Some(first) = vertices.first() else {
+            return Self::default();
+        };
+        let (min, max) = vertices.iter().fold((first.position, first.position), |(min, max), vertex|{
+            (min.inf(&vertex.position), max.sup(&vertex.position))
+        });
+        Self{
+            center: (min + max) * 0.5,
+            extents: (max - min) * 0.5,
+        }
+    }
+}
 
 
 const _:u32 = ComputePushConstants::size_u32();
@@ -99,6 +142,17 @@ impl GPUSceneData {
         }
         size_of::<Self>() as u64
     }
+    
+    pub fn view_projection(&self) -> &Matrix4<f32> {
+        &self.view_projection
+    }
+    
+    // direction points towards the light, both w carry the power like the shader struct says
+    pub fn set_light(&mut self, direction:&Vector3<f32>, color:&Vector3<f32>, intensity:f32, ambient:&Vector3<f32>) {
+        self.sunlight_direction = direction.push(intensity);
+        self.sunlight_color = color.push(intensity);
+        self.ambient_color = ambient.push(1.0);
+    }
 }
 
 impl Default for GPUSceneData {
@@ -0,0 +1,144 @@
+use crate::errors::messages::GRANTED;
+
+use arrayvec::ArrayString;
+
+pub const TIMING_HISTORY:usize = 128;
+// samples behind every point of the plotted average
+pub const AVERAGE_WINDOW:usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PassTiming {
+    pub name: ArrayString<32>,
+    pub milliseconds: f32,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PipelineStatistics {
+    pub vertex_invocations: u64,
+    pub fragment_invocations: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PassHistory {
+    pub name: ArrayString<32>,
+    pub samples: [f32; TIMING_HISTORY],
+    // the rolling average at the time of each sample, plotted instead of the noisy samples
+    pub averages: [f32; TIMING_HISTORY],
+    pub offset: usize,
+    pub average: f32,
+    count: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct GpuTimings {
+    passes: Vec<PassHistory>,
+    statistics: Option<PipelineStatistics>,
+}
+
+impl PassHistory {
+    fn new(name:ArrayString<32>) -> Self {
+        Self{
+            name,
+            samples: [0f32; TIMING_HISTORY],
+            averages: [0f32; TIMING_HISTORY],
+            offset: 0,
+            average: 0f32,
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, milliseconds:f32) {
+        self.samples[self.offset] = milliseconds;
+        if self.count < TIMING_HISTORY {
+            self.count += 1;
+        }
+        let window = self.count.min(AVERAGE_WINDOW);
+        let sum:f32 = (0..window).map(|back|self.samples[(self.offset + TIMING_HISTORY - back) % TIMING_HISTORY]).sum();
+        self.average = sum / window as f32;
+        self.averages[self.offset] = self.average;
+        self.offset = (self.offset + 1) % TIMING_HISTORY;
+    }
+
+    pub fn max(&self) -> f32 {
+        self.averages.iter().fold(0f32, |last, current|last.max(*current))
+    }
+}
+
+impl GpuTimings {
+
+    pub fn push_frame(&mut self, timings:&[PassTiming], statistics:Option<PipelineStatistics>) {
+        // passes that stopped running are dropped, a frame without timestamps keeps what is there
+        if !timings.is_empty() {
+            self.passes.retain(|pass|timings.iter().any(|timing|timing.name == pass.name));
+        }
+        for timing in timings {
+            let position = self.passes.iter().position(|pass|pass.name == timing.name);
+            let history = match position {
+                Some(index) => &mut self.passes[index],
+                None => {
+                    self.passes.push(PassHistory::new(timing.name));
+                    self.passes.last_mut().expect(GRANTED)
+                }
+            };
+            history.push(timing.milliseconds);
+        }
+        if statistics.is_some() {
+            self.statistics = statistics;
+        }
+    }
+
+    pub fn passes(&self) -> &[PassHistory] {
+        &self.passes
+    }
+
+    pub fn statistics(&self) -> Option<&PipelineStatistics> {
+        self.statistics.as_ref()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(name:&str, milliseconds:f32) -> PassTiming {
+        PassTiming{
+            name: ArrayString::from(name).unwrap(),
+            milliseconds,
+        }
+    }
+
+    #[test]
+    fn averages_the_latest_window() {
+        let mut timings = GpuTimings::default();
+        for _ in 0..TIMING_HISTORY {
+            timings.push_frame(&[timing("draw", 1.0)], None);
+        }
+        for _ in 0..AVERAGE_WINDOW/2 {
+            timings.push_frame(&[timing("draw", 3.0)], None);
+        }
+        let pass = &timings.passes()[0];
+        assert_eq!(pass.average, 2.0);
+        assert_eq!(pass.averages[(pass.offset + TIMING_HISTORY - 1) % TIMING_HISTORY], 2.0);
+        assert_eq!(pass.max(), 2.0);
+    }
+
+    #[test]
+    fn averages_fewer_samples_than_the_window() {
+        let mut timings = GpuTimings::default();
+        timings.push_frame(&[timing("draw", 1.0)], None);
+        timings.push_frame(&[timing("draw", 2.0)], None);
+        assert_eq!(timings.passes()[0].average, 1.5);
+    }
+
+    #[test]
+    fn drops_passes_that_stopped() {
+        let mut timings = GpuTimings::default();
+        timings.push_frame(&[timing("cull", 1.0), timing("draw", 1.0)], None);
+        timings.push_frame(&[], None);
+        assert_eq!(timings.passes().len(), 2);
+        timings.push_frame(&[timing("draw", 1.0)], None);
+        let names:Vec<_> = timings.passes().iter().map(|pass|pass.name.as_str()).collect();
+        assert_eq!(names, ["draw"]);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::AAError;
+use crate::logger;
+use crate::errors::messages::GRANTED;
+
+use super::CaptureRequest;
+use super::CapturedImage;
+
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::sync::mpsc::Receiver;
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordingSettings {
+    pub frames: u32,
+    // rendered frames per captured one
+    pub every_nth: u32,
+    // simulated seconds between rendered frames, however long they take to render
+    pub timestep: f32,
+    pub turntable: bool,
+    // simulated seconds per turntable revolution
+    pub revolution: f32,
+    pub capture: CaptureRequest,
+}
+
+pub struct Recording {
+    settings: RecordingSettings,
+    rendered: u32,
+    captured: u32,
+    sender: Sender<CapturedImage>,
+    writer: JoinHandle<()>,
+}
+
+enum Output {
+    Sequence(PathBuf),
+    // started with the first frame, once its size is known
+    Encoder(String),
+    Running(Encoder),
+    Failed,
+}
+
+struct Encoder {
+    process: Child,
+    width: u32,
+    height: u32,
+}
+
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self{
+            frames: 120,
+            every_nth: 1,
+            timestep: 1.0/30.0,
+            turntable: true,
+            revolution: 4.0,
+            capture: CaptureRequest::default(),
+        }
+    }
+}
+
+impl RecordingSettings {
+    pub fn frame_rate(&self) -> f32 {
+        1.0 / (self.timestep * self.every_nth as f32)
+    }
+    
+    fn sanitized(mut self) -> Self {
+        self.frames = self.frames.max(1);
+        self.every_nth = self.every_nth.max(1);
+        self.timestep = self.timestep.max(0.0001);
+        self.revolution = self.revolution.max(self.timestep);
+        self
+    }
+}
+
+
+impl Recording {
+    
+//----
+    // frames are piped to the encoder when there is one, otherwise they become a numbered sequence in directory
+    pub fn start(settings:RecordingSettings, encoder:Option<&str>, directory:&Path) -> Result<Self, AAError> {
+        let settings = settings.sanitized();
+        let output = match encoder {
+            Some(command) => Output::Encoder(command.to_owned()),
+            None => {
+                let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+                let sequence = directory.join(format!("recording_{}", stamp));
+                std::fs::create_dir_all(&sequence)?;
+                Output::Sequence(sequence)
+            }
+        };
+        logger::various_log!("recording",
+            (logger::Info, "recording {} frames at {} fps", settings.frames, settings.frame_rate())
+        );
+        
+        let (sender, receiver) = mpsc::channel();
+        let frame_rate = settings.frame_rate();
+        let writer = std::thread::spawn(move ||Self::write_frames(receiver, output, frame_rate));
+        
+        Ok(Self{
+            settings,
+            rendered: 0,
+            captured: 0,
+            sender,
+            writer,
+        })
+    }
+    
+//----
+    // simulated seconds at the frame about to be rendered
+    pub fn time(&self) -> f32 {
+        self.rendered as f32 * self.settings.timestep
+    }
+    
+//----
+    // fraction of a revolution the turntable camera is at, None when the camera is left alone
+    pub fn turntable_turn(&self) -> Option<f32> {
+        self.settings.turntable.then(||(self.time() / self.settings.revolution).fract())
+    }
+    
+//----
+    // called once per recorded frame, the frame has to be captured into the returned sink
+    pub fn advance(&mut self) -> Option<(CaptureRequest, Sender<CapturedImage>)> {
+        let due = !self.is_finished() && self.rendered.is_multiple_of(self.settings.every_nth);
+        self.rendered += 1;
+        if !due {
+            return None;
+        }
+        self.captured += 1;
+        Some((self.settings.capture, self.sender.clone()))
+    }
+    
+    pub fn is_finished(&self) -> bool {
+        self.captured >= self.settings.frames
+    }
+    
+    pub fn timestep(&self) -> f32 {
+        self.settings.timestep
+    }
+    
+    pub fn progress(&self) -> (u32, u32) {
+        (self.captured, self.settings.frames)
+    }
+    
+//----
+    // blocks until every frame is written, the captures still in flight have to be completed before
+    pub fn finish(self) {
+        let Recording{sender, writer, captured, ..} = self;
+        std::mem::drop(sender);
+        match writer.join() {
+            Ok(_) => {
+                logger::various_log!("recording",
+                    (logger::Info, "recording finished with {} frames", captured)
+                );
+            }
+            Err(_) => {
+                logger::various_log!("recording",
+                    (logger::Error, "recording writer panicked")
+                );
+            }
+        }
+    }
+    
+    fn write_frames(receiver:Receiver<CapturedImage>, mut output:Output, frame_rate:f32) {
+        for (index, image) in receiver.iter().enumerate() {
+            if let Err(error) = output.write(index, &image, frame_rate) {
+                logger::various_log!("recording",
+                    (logger::Error, "frame {} could not be written {}", index, error)
+                );
+            }
+        }
+        if let Output::Running(encoder) = output {
+            encoder.finish();
+        }
+    }
+}
+
+
+impl Output {
+    fn write(&mut self, index:usize, image:&CapturedImage, frame_rate:f32) -> Result<(), AAError> {
+        match self {
+            Output::Sequence(directory) => {
+                image.save_as(directory.join(format!("frame_{:05}", index)))?;
+            }
+            Output::Encoder(command) => {
+                match Encoder::spawn(command, image.width, image.height, frame_rate) {
+                    Ok(encoder) => {
+                        *self = Output::Running(encoder);
+                        return self.write(index, image, frame_rate);
+                    }
+                    Err(error) => {
+                        *self = Output::Failed;
+                        return Err(error);
+                    }
+                }
+            }
+            Output::Running(encoder) => {
+                encoder.write(image)?;
+            }
+            Output::Failed => {}
+        }
+        Ok(())
+    }
+}
+
+
+impl Encoder {
+    // the command is split on whitespace without a shell, {width} {height} and {fps} are replaced in every argument
+    fn spawn(command:&str, width:u32, height:u32, frame_rate:f32) -> Result<Self, AAError> {
+        let mut arguments = command.split_whitespace().map(|argument|{
+            argument
+                .replace("{width}", &width.to_string())
+                .replace("{height}", &height.to_string())
+                .replace("{fps}", &frame_rate.to_string())
+        });
+        let program = arguments.next().ok_or(AAError::EmptyEncoder)?;
+        let process = Command::new(program)
+            .args(arguments)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        logger::various_log!("recording",
+            (logger::Debug, "encoder started for {}x{} rgba frames", width, height)
+        );
+        Ok(Self{
+            process,
+            width,
+            height,
+        })
+    }
+    
+    // raw rgba8 frames, tonemapped like the png ones
+    fn write(&mut self, image:&CapturedImage) -> Result<(), AAError> {
+        if (image.width, image.height) != (self.width, self.height) {
+            logger::various_log!("recording",
+                (logger::Warn, "{}x{} frame dropped, the encoder was started at {}x{}", image.width, image.height, self.width, self.height)
+            );
+            return Ok(());
+        }
+        let stdin = self.process.stdin.as_mut().expect(GRANTED);
+        stdin.write_all(image.to_ldr().as_raw())?;
+        Ok(())
+    }
+    
+    fn finish(mut self) {
+        // closing stdin is the end of the stream for the encoder
+        std::mem::drop(self.process.stdin.take());
+        match self.process.wait() {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                logger::various_log!("recording",
+                    (logger::Warn, "encoder exited with {}", status)
+                );
+            }
+            Err(error) => {
+                logger::various_log!("recording",
+                    (logger::Warn, "encoder could not be waited on {}", error)
+                );
+            }
+        }
+    }
+}
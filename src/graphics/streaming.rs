@@ -0,0 +1,49 @@
+use arrayvec::ArrayString;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    Parsing,
+    Loading,
+    Done,
+    Failed,
+    Unloaded,
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamProgress {
+    pub path: ArrayString<128>,
+    pub state: StreamState,
+    pub meshes_loaded: usize,
+    pub meshes_total: usize,
+    pub textures_loaded: usize,
+    pub textures_total: usize,
+    pub error: Option<String>,
+}
+
+impl StreamProgress {
+    pub fn new(path:&str) -> Self {
+        let mut name = ArrayString::new();
+        // long paths keep their tail, it is the part that tells files apart
+        let start = path.len().saturating_sub(name.capacity());
+        let start = (start..path.len()).find(|index|path.is_char_boundary(*index)).unwrap_or(path.len());
+        name.push_str(&path[start..]);
+        Self{
+            path: name,
+            state: StreamState::Parsing,
+            meshes_loaded: 0,
+            meshes_total: 0,
+            textures_loaded: 0,
+            textures_total: 0,
+            error: None,
+        }
+    }
+    
+    pub fn fraction(&self) -> f32 {
+        let total = self.meshes_total + self.textures_total;
+        match self.state {
+            StreamState::Done => 1.0,
+            _ if total == 0 => 0.0,
+            _ => (self.meshes_loaded + self.textures_loaded) as f32 / total as f32,
+        }
+    }
+}
@@ -5,17 +5,49 @@ pub use vk_gui::Gui;
 
 use crate::window::Window;
 use crate::graphics::ComputePushConstants;
+use crate::graphics::GpuTimings;
+use crate::graphics::StreamProgress;
+use crate::graphics::StreamState;
+use crate::graphics::CaptureRequest;
+use crate::graphics::CaptureResolution;
+use crate::graphics::CaptureFormat;
+use crate::graphics::RecordingSettings;
+use crate::game::SimulationControls;
+use crate::game::TICK;
+use crate::input::Binding;
+use crate::input::RebindTarget;
 
 use nalgebra as na;
 use na::Vector3;
 
 
+// names and timings the windows show, the recording progress is in frames written out of the total
+pub type UiArgs<'a, C, D> = (&'a [C], &'a [D], &'a GpuTimings, &'a [StreamProgress], Option<(u32, u32)>);
+// renderer state the windows edit in place
+pub type UiParameters<'a> = (&'a mut usize, &'a mut ComputePushConstants, &'a mut usize, &'a mut Vector3<f32>, &'a mut f32, &'a mut bool);
 
 #[derive(Default, Debug)]
 pub struct InputData {
     pub background_index: usize,
     pub push_constants: ComputePushConstants,
     
+    pub model_path: String,
+    // only set on the frame the button was pressed
+    pub load_requested: bool,
+    pub unload_requested: Option<usize>,
+    
+    // kept between frames, the hotkey uses the same settings
+    pub capture: CaptureRequest,
+    pub capture_requested: bool,
+    pub recording: RecordingSettings,
+    // starts a recording, or stops the running one
+    pub recording_toggled: bool,
+    // radians per second the shown model turns at
+    pub spin_speed: f32,
+    pub simulation: SimulationControls,
+    // runs a single tick while paused
+    pub simulation_stepped: bool,
+    
     /*
     pub mesh_index: usize,
     pub perspectives: na::Vector3<f32>,
@@ -49,17 +81,23 @@ impl Gui{
     pub fn draw_ui<C, CC:Fn(&C)->&str, D, DD:Fn(&D)->&str>(
         &mut self,
         window: &mut Window,
-        args: (&[C], &[D]),
+        args: UiArgs<C, D>,
         transform: (CC, DD),
-        parameters: (&mut usize, &mut ComputePushConstants, &mut usize, &mut Vector3<f32>, &mut f32),
+        parameters: UiParameters,
     ) {
         
-        let (compute_effects_name, mesh_assets_metadata) = args;
+        let (compute_effects_name, mesh_assets_metadata, gpu_timings, streams, recording) = args;
         let (c_transform, d_transform) = transform;
-        let (compute_effect_index, compute_push_constant, mesh_index, near_far, downscale_coheficient) = parameters;
+        let (compute_effect_index, compute_push_constant, mesh_index, near_far, downscale_coheficient, gpu_driven) = parameters;
         
+        self.frame_open = true;
         let (context, platform, ui_data) = self.get_common_mut();
         let ui = context.new_frame();
+        ui_data.load_requested = false;
+        ui_data.unload_requested = None;
+        ui_data.capture_requested = false;
+        ui_data.recording_toggled = false;
+        ui_data.simulation_stepped = false;
         
         let _background = Self::get_next_window(&ui, "Background", [0,0]).build(||{
             
@@ -87,6 +125,9 @@ impl Gui{
             ui.text("Render scale");
             ui.slider("Scale", 0.1, 1.0, downscale_coheficient);
             
+            ui.text("Draw submission");
+            ui.checkbox("GPU driven", gpu_driven);
+            
         });
         
         let _model = Self::get_next_window(&ui, "Model", [0,2]).build(||{
@@ -95,6 +136,18 @@ impl Gui{
             for (index, mesh) in mesh_assets_metadata.into_iter().enumerate() {
                 ui.radio_button(d_transform(mesh), mesh_index, index);
             }
+            
+            ui.separator();
+            ui.text("Open .glb/.gltf/.obj, or drop it on the window");
+            let entered = ui.input_text("Path", &mut ui_data.model_path)
+                .enter_returns_true(true)
+                .build();
+            if ui.button("Load") || entered {
+                ui_data.load_requested = true;
+            }
+            
+            ui.separator();
+            ui.slider("Spin", -3.0, 3.0, &mut ui_data.spin_speed);
         });
         
         let mut _window = Self::get_next_window(&ui, "Field of View(FOV)", [0,3]).build(||{
@@ -107,9 +160,155 @@ impl Gui{
             
         });
         
+        let _profiler = Self::get_next_window(ui, "GPU Profiler", [0,4]).build(||{
+            if gpu_timings.passes().is_empty() {
+                ui.text("No timestamps available");
+            }
+            for pass in gpu_timings.passes() {
+                let overlay = format!("{:.3} ms", pass.average);
+                ui.plot_lines(pass.name.as_str(), &pass.averages)
+                    .values_offset(pass.offset)
+                    .overlay_text(&overlay)
+                    .scale_min(0.0)
+                    .scale_max(pass.max().max(0.001))
+                    .graph_size([256.0, 32.0])
+                    .build();
+            }
+            match gpu_timings.statistics() {
+                Some(statistics) => {
+                    ui.text(format!("vertex invocations: {}", statistics.vertex_invocations));
+                    ui.text(format!("fragment invocations: {}", statistics.fragment_invocations));
+                }
+                None => {
+                    ui.text("Pipeline statistics not available");
+                }
+            }
+        });
+        
+        let _streaming = Self::get_next_window(ui, "Streaming", [0,5]).build(||{
+            if streams.is_empty() {
+                ui.text("No assets requested");
+            }
+            for (job, stream) in streams.iter().enumerate() {
+                ui.text(stream.path.as_str());
+                let overlay = match stream.state {
+                    StreamState::Parsing => String::from("parsing"),
+                    StreamState::Loading => format!("{}/{} meshes {}/{} textures", stream.meshes_loaded, stream.meshes_total, stream.textures_loaded, stream.textures_total),
+                    StreamState::Done => String::from("done"),
+                    StreamState::Failed => String::from("failed"),
+                    StreamState::Unloaded => String::from("unloaded"),
+                };
+                imgui::ProgressBar::new(stream.fraction())
+                    .overlay_text(&overlay)
+                    .build(ui);
+                if let Some(error) = &stream.error {
+                    ui.text_wrapped(error);
+                }
+                if matches!(stream.state, StreamState::Done | StreamState::Failed) && ui.button(format!("Unload##{}", job)) {
+                    ui_data.unload_requested = Some(job);
+                }
+            }
+        });
+        
+        let _screenshot = Self::get_next_window(ui, "Screenshot", [0,6]).build(||{
+            ui.text("Resolution");
+            ui.radio_button("Internal", &mut ui_data.capture.resolution, CaptureResolution::Internal);
+            ui.radio_button("Window", &mut ui_data.capture.resolution, CaptureResolution::Swapchain);
+            ui.checkbox("Include interface", &mut ui_data.capture.overlay);
+            
+            ui.text("Format");
+            ui.radio_button("PNG, tonemapped", &mut ui_data.capture.format, CaptureFormat::Png);
+            ui.radio_button("EXR, raw HDR", &mut ui_data.capture.format, CaptureFormat::Exr);
+            if ui.button("Capture (F12)") {
+                ui_data.capture_requested = true;
+            }
+        });
+        
+        let _recording = Self::get_next_window(ui, "Recording", [0,7]).build(||{
+            let disabled_token = ui.begin_disabled(recording.is_some());
+            ui.input_scalar("Frames", &mut ui_data.recording.frames).build();
+            ui.slider("Every nth frame", 1, 16, &mut ui_data.recording.every_nth);
+            ui.input_float("Timestep (s)", &mut ui_data.recording.timestep).build();
+            ui.checkbox("Turntable", &mut ui_data.recording.turntable);
+            ui.input_float("Revolution (s)", &mut ui_data.recording.revolution).build();
+            ui.text("Resolution and format from the screenshot settings");
+            disabled_token.end();
+            
+            match recording {
+                Some((captured, frames)) => {
+                    imgui::ProgressBar::new(captured as f32 / frames.max(1) as f32)
+                        .overlay_text(format!("{}/{} frames", captured, frames))
+                        .build(ui);
+                    if ui.button("Stop") {
+                        ui_data.recording_toggled = true;
+                    }
+                }
+                None => {
+                    if ui.button("Record") {
+                        ui_data.recording.capture = ui_data.capture;
+                        ui_data.recording_toggled = true;
+                    }
+                }
+            }
+        });
+        
+        let _simulation = Self::get_next_window(ui, "Simulation", [0,8]).build(||{
+            ui.text(format!("Fixed tick of {:.2} ms", TICK*1000.0));
+            ui.checkbox("Paused", &mut ui_data.simulation.paused);
+            let disabled_token = ui.begin_disabled(!ui_data.simulation.paused);
+            if ui.button("Step") {
+                ui_data.simulation_stepped = true;
+            }
+            disabled_token.end();
+            ui.slider("Time scale", 0.0, 4.0, &mut ui_data.simulation.time_scale);
+        });
+        
+        let _controls = Self::get_next_window(ui, "Controls", [0,9]).build(||{
+            let input = window.input_mut();
+            let rebinding = input.rebinding();
+            match rebinding {
+                Some(_) => ui.text("Press the new input, Escape cancels"),
+                None => ui.text("Rebinds last until the renderer exits"),
+            }
+            let disabled_token = ui.begin_disabled(rebinding.is_some());
+            let mut target = None;
+            ui.separator();
+            for (index, (name, bindings)) in input.map().actions.iter().enumerate() {
+                if ui.button(format!("Set##action{}", index)) {
+                    target = Some(RebindTarget::Action(index));
+                }
+                ui.same_line();
+                ui.text(format!("{}: {}", name, Self::join_bindings(bindings)));
+            }
+            ui.separator();
+            for (index, (name, bindings)) in input.map().axes.iter().enumerate() {
+                if ui.button(format!("+##axis{}", index)) {
+                    target = Some(RebindTarget::Axis(index, false));
+                }
+                ui.same_line();
+                if ui.button(format!("-##axis{}", index)) {
+                    target = Some(RebindTarget::Axis(index, true));
+                }
+                ui.same_line();
+                ui.text(format!("{}: {}", name, Self::join_bindings(bindings)));
+            }
+            ui.separator();
+            if ui.button("Reset to config") {
+                input.reset_map();
+            }
+            disabled_token.end();
+            if let Some(target) = target {
+                input.begin_rebind(target);
+            }
+        });
+        
         platform.prepare_render(&ui, window.underlying());
     }
     
+    fn join_bindings(bindings:&[Binding]) -> String {
+        bindings.iter().map(Binding::to_string).collect::<Vec<_>>().join(", ")
+    }
+    
 
     fn get_next_window<'a>(ui:&'a imgui::Ui, name:&'a str, position:[u8; 2]) -> imgui::Window<'a, 'a, &'a str> {
         let position = [32.0+position[0] as f32*256.0, 32.0+position[1] as f32*32.0];
@@ -1,3 +1,4 @@
+use crate::AAError;
 use crate::logger;
 
 use crate::window::Window;
@@ -5,14 +6,12 @@ use crate::vulkan::VInit;
 use crate::vulkan::Device;
 use crate::vulkan::Swapchain;
 use crate::vulkan::memory::Allocator;
-use crate::vulkan::vk_create_interpreter;
 use crate::vulkan::pipeline;
 
 use super::InputData;
 
 use std::sync::Arc;
 use std::sync::Mutex;
-use std::mem::ManuallyDrop;
 
 use ash::vk;
 use imgui::Context;
@@ -26,15 +25,18 @@ use gpu_allocator::vulkan as gpu_vk;
 
 #[allow(dead_code)]
 pub struct Gui{
-    allocator: Arc<Mutex<gpu_vk::Allocator>>,
+    // both are gone while the renderer is rebuilt, the allocator has to outlive the renderer using it
+    allocator: Option<Arc<Mutex<gpu_vk::Allocator>>>,
     pub platform: ImguiSdl2,
     pub context: Context,
-    pub renderer: ManuallyDrop<Renderer>,
+    pub renderer: Option<Renderer>,
     pub ui_data: InputData,
+    // started by draw_ui and ended by render or end_frame
+    pub(super) frame_open: bool,
 }
 
 impl Gui {
-    pub fn init(window:&mut Window, v_init:&mut VInit) -> Self {
+    pub fn init(window:&mut Window, v_init:&mut VInit) -> Result<Self, AAError> {
         let VInit{
             instance,
             p_device,
@@ -44,7 +46,7 @@ impl Gui {
             ..
         } = v_init;
         
-        let imgui_allocator = vk_create_interpreter(Allocator::create(instance, &p_device, device), "allocator").into_inner();
+        let imgui_allocator = Allocator::create(instance, p_device, device)?.into_inner();
         Self::create(window, device, swapchain, &command_control.pool, imgui_allocator)
     }
     
@@ -55,7 +57,7 @@ impl Gui {
         command_pool: &vk::CommandPool,
         allocator: gpu_vk::Allocator,
         
-    ) -> Self {
+    ) -> Result<Self, AAError> {
         logger::create!("imgui");
         
         /*
@@ -65,11 +67,30 @@ impl Gui {
         let command_pool = unsafe{device.create_command_pool(&create_info, None)}.unwrap();
         */
         
+        let mut context = imgui::Context::create();
+        let platform = imgui_sdl2::ImguiSdl2::new(&mut context, window.underlying());
+        let allocator = Arc::new(Mutex::new(allocator));
+        let renderer = Self::create_renderer(device, swapchain, command_pool, allocator.clone(), &mut context)?;
         
+        Ok(Self{
+            allocator: Some(allocator),
+            context,
+            platform,
+            renderer: Some(renderer),
+            ui_data: InputData::default(),
+            frame_open: false,
+        })
+    }
+    
+    fn create_renderer(
+        device: &Device,
+        swapchain: &Swapchain,
+        command_pool: &vk::CommandPool,
+        allocator: Arc<Mutex<gpu_vk::Allocator>>,
+        context: &mut Context,
+    ) -> Result<Renderer, AAError> {
         let graphics_queue: vk::Queue = device.queue_handles.graphics;
         
-        let mut context = imgui::Context::create();
-        
         let options_arg = Some(Options{
             in_flight_frames:2,
             enable_depth_test:false,
@@ -81,17 +102,33 @@ impl Gui {
             depth_attachment_format: None,
         };
         
-        let platform = imgui_sdl2::ImguiSdl2::new(&mut context, window.underlying());
-        let allocator = Arc::new(Mutex::new(allocator));
-        let renderer = Renderer::with_gpu_allocator(allocator.clone(), device.underlying(), graphics_queue, command_pool.clone(), dynamic_info, &mut context, options_arg).unwrap();
+        Ok(Renderer::with_gpu_allocator(allocator, device.underlying(), graphics_queue, *command_pool, dynamic_info, context, options_arg)?)
+    }
+    
+//----
+    // has to run before the device the renderer was built on is destroyed
+    pub fn release_renderer(&mut self) {
+        std::mem::drop(self.renderer.take());
+        std::mem::drop(self.allocator.take());
+    }
+    
+//----
+    // the context and the ui state are kept, only the gpu side is built again on the new device
+    pub fn restore_renderer(&mut self, v_init:&mut VInit) -> Result<(), AAError> {
+        let VInit{
+            instance,
+            p_device,
+            device,
+            swapchain,
+            command_control,
+            ..
+        } = v_init;
         
-        Self{
-            allocator: allocator,
-            context,
-            platform,
-            renderer: ManuallyDrop::new(renderer),
-            ui_data: InputData::default(),
-        }
+        let allocator = Arc::new(Mutex::new(Allocator::create(instance, p_device, device)?.into_inner()));
+        let renderer = Self::create_renderer(device, swapchain, &command_control.pool, allocator.clone(), &mut self.context)?;
+        self.allocator = Some(allocator);
+        self.renderer = Some(renderer);
+        Ok(())
     }
     
     
@@ -103,6 +140,14 @@ impl Gui {
         &self.ui_data
     }
     
+    // a frame that was skipped still has to be ended, imgui refuses to start the next one otherwise
+    pub fn end_frame(&mut self) {
+        if self.frame_open {
+            self.frame_open = false;
+            self.context.render();
+        }
+    }
+    
     pub fn render(
         &mut self,
         device: &Device,
@@ -110,16 +155,19 @@ impl Gui {
         extent: vk::Extent2D,
         view: vk::ImageView,
         
-    ) {
+    ) -> Result<(), AAError> {
         
+        self.frame_open = false;
         let draw_data = self.context.render();
+        let Some(renderer) = self.renderer.as_mut() else {return Ok(())};
         
         let color_attachment_info = pipeline::rendering_attachment_info(view, None, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
         let rendering_info = pipeline::rendering_info(extent, &color_attachment_info, None);
         
         unsafe{device.cmd_begin_rendering(cmd, &rendering_info)};
-        self.renderer.cmd_draw(cmd, draw_data).unwrap();
+        let holder = renderer.cmd_draw(cmd, draw_data);
         unsafe{device.cmd_end_rendering(cmd)};
+        Ok(holder?)
     }
     
 }
@@ -127,7 +175,7 @@ impl Gui {
 impl Drop for Gui {
     fn drop(&mut self) {
         logger::destruct!("imgui");
-        unsafe{ManuallyDrop::drop(&mut self.renderer)};
+        self.release_renderer();
     }
 }
 
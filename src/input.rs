@@ -0,0 +1,450 @@
+use crate::AAError;
+use crate::logger;
+
+use std::fmt;
+use std::str::FromStr;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::controller::Axis;
+use sdl2::controller::Button;
+use serde::Deserialize;
+
+
+pub const QUIT:&str = "quit";
+pub const SCREENSHOT:&str = "screenshot";
+
+pub const MOVE_RIGHT:&str = "move_right";
+pub const MOVE_UP:&str = "move_up";
+pub const MOVE_FORWARD:&str = "move_forward";
+pub const LOOK_RIGHT:&str = "look_right";
+pub const LOOK_UP:&str = "look_up";
+
+// stick values under it read as 0
+const DEADZONE:f32 = 0.15;
+// a stick past it counts as pressed, also what a rebind needs to pick a stick
+const STICK_PRESS:f32 = 0.5;
+// axis units per pixel the mouse moved in a frame
+const MOUSE_MOTION_SCALE:f32 = 0.01;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionAxis {
+    X,
+    Y,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Key(Keycode),
+    Mouse(MouseButton),
+    MouseMotion(MotionAxis),
+    // sdl game controller, every connected one drives the same bindings
+    Button(Button),
+    Stick(Axis),
+}
+
+// written as "key:W", "mouse:left", "motion:x", "button:a" or "stick:leftx",
+// a leading - makes it push an axis the negative way, or a stick press on its negative side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Binding {
+    pub input: Input,
+    pub inverted: bool,
+}
+
+// actions are pressed or not, axes add up the value of every binding
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputMap {
+    pub actions: Vec<(String, Vec<Binding>)>,
+    pub axes: Vec<(String, Vec<Binding>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebindTarget {
+    Action(usize),
+    // the bool picks the negative direction
+    Axis(usize, bool),
+}
+
+// what the game sees of the devices, only fed the events the gui did not take
+pub struct InputState {
+    map: InputMap,
+    configured: InputMap,
+    // inverted only tells the sides of a stick apart
+    held: Vec<Binding>,
+    // since the last begin_frame
+    pressed: Vec<Binding>,
+    sticks: Vec<(Axis, f32)>,
+    motion: [f32; 2],
+    rebinding: Option<RebindTarget>,
+}
+
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let bind = |bindings:&[&str]|bindings.iter().map(|binding|binding.parse::<Binding>().expect("default bindings should parse")).collect();
+        Self{
+            actions: vec![
+                (QUIT.to_owned(), bind(&["key:Escape", "key:Q", "button:back"])),
+                (SCREENSHOT.to_owned(), bind(&["key:F12"])),
+            ],
+            axes: vec![
+                (MOVE_RIGHT.to_owned(), bind(&["key:D", "-key:A", "stick:leftx"])),
+                (MOVE_UP.to_owned(), bind(&["key:Space", "-key:Left Ctrl", "stick:righttrigger", "-stick:lefttrigger"])),
+                (MOVE_FORWARD.to_owned(), bind(&["key:W", "-key:S", "-stick:lefty"])),
+                (LOOK_RIGHT.to_owned(), bind(&["key:Right", "-key:Left", "stick:rightx"])),
+                (LOOK_UP.to_owned(), bind(&["key:Up", "-key:Down", "-stick:righty"])),
+            ],
+        }
+    }
+}
+
+impl InputMap {
+    // named entries replace the bindings they had, new names are added
+    pub fn merge(&mut self, actions:impl IntoIterator<Item=(String, Vec<Binding>)>, axes:impl IntoIterator<Item=(String, Vec<Binding>)>) {
+        fn merge_into(into:&mut Vec<(String, Vec<Binding>)>, from:impl IntoIterator<Item=(String, Vec<Binding>)>) {
+            for (name, bindings) in from {
+                match into.iter_mut().find(|(existing, _)|*existing == name) {
+                    Some((_, existing)) => {
+                        *existing = bindings;
+                    }
+                    None => {
+                        into.push((name, bindings));
+                    }
+                }
+            }
+        }
+        merge_into(&mut self.actions, actions);
+        merge_into(&mut self.axes, axes);
+    }
+}
+
+
+impl InputState {
+    pub fn new(map:InputMap) -> Self {
+        Self{
+            configured: map.clone(),
+            map,
+            held: Vec::new(),
+            pressed: Vec::new(),
+            sticks: Vec::new(),
+            motion: [0.0, 0.0],
+            rebinding: None,
+        }
+    }
+    
+//----
+    // called before the events of a frame are handled
+    pub fn begin_frame(&mut self) {
+        self.pressed.clear();
+        self.motion = [0.0, 0.0];
+    }
+    
+//----
+    // takes the input events routed to the game, the rest are ignored
+    pub fn handle_event(&mut self, event:&Event) {
+        match *event {
+            Event::KeyDown{keycode: Some(keycode), repeat: false, ..} => {
+                self.press(Input::Key(keycode));
+            }
+            Event::KeyUp{keycode: Some(keycode), ..} => {
+                self.release(Input::Key(keycode));
+            }
+            Event::MouseButtonDown{mouse_btn, ..} => {
+                self.press(Input::Mouse(mouse_btn));
+            }
+            Event::MouseButtonUp{mouse_btn, ..} => {
+                self.release(Input::Mouse(mouse_btn));
+            }
+            Event::MouseMotion{xrel, yrel, ..} => {
+                self.motion[0] += xrel as f32;
+                self.motion[1] += yrel as f32;
+            }
+            Event::ControllerButtonDown{button, ..} => {
+                self.press(Input::Button(button));
+            }
+            Event::ControllerButtonUp{button, ..} => {
+                self.release(Input::Button(button));
+            }
+            Event::ControllerAxisMotion{axis, value, ..} => {
+                self.move_stick(axis, f32::from(value) / f32::from(i16::MAX));
+            }
+            _ => {}
+        }
+    }
+    
+//----
+    // releases everything a disconnected controller could be holding
+    pub fn release_controllers(&mut self) {
+        self.held.retain(|held|!matches!(held.input, Input::Button(_) | Input::Stick(_)));
+        self.sticks.clear();
+    }
+    
+//----
+    pub fn is_pressed(&self, action:&str) -> bool {
+        self.action_bindings(action).iter().any(|binding|self.pressed.contains(&binding.press_key()))
+    }
+    
+//----
+    // buttons give 1 and sticks their position, clamped together to -1..1
+    pub fn axis(&self, name:&str) -> f32 {
+        self.axis_parts(name).0
+    }
+    
+//----
+    // mouse motion since begin_frame, unclamped, it is a distance the game adds up rather than a position
+    pub fn motion(&self, name:&str) -> f32 {
+        self.axis_parts(name).1
+    }
+    
+//----
+    pub fn map(&self) -> &InputMap {
+        &self.map
+    }
+    
+    pub fn reset_map(&mut self) {
+        self.map = self.configured.clone();
+        self.rebinding = None;
+    }
+    
+//----
+    // the next input the game receives becomes the binding, escape cancels
+    pub fn begin_rebind(&mut self, target:RebindTarget) {
+        self.rebinding = Some(target);
+    }
+    
+    pub fn rebinding(&self) -> Option<RebindTarget> {
+        self.rebinding
+    }
+    
+    fn action_bindings(&self, action:&str) -> &[Binding] {
+        self.map.actions.iter().find(|(name, _)|name == action).map(|(_, bindings)|bindings.as_slice()).unwrap_or(&[])
+    }
+    
+    // the clamped value of the held bindings and the scaled mouse motion, apart
+    fn axis_parts(&self, name:&str) -> (f32, f32) {
+        let Some((_, bindings)) = self.map.axes.iter().find(|(axis, _)|axis == name) else {
+            return (0.0, 0.0);
+        };
+        let mut value = 0.0;
+        let mut motion = 0.0;
+        for binding in bindings {
+            let sign = if binding.inverted {-1.0} else {1.0};
+            match binding.input {
+                Input::MouseMotion(MotionAxis::X) => {
+                    motion += sign * self.motion[0] * MOUSE_MOTION_SCALE;
+                }
+                Input::MouseMotion(MotionAxis::Y) => {
+                    motion += sign * self.motion[1] * MOUSE_MOTION_SCALE;
+                }
+                Input::Stick(axis) => {
+                    let position = self.stick(axis);
+                    if position.abs() > DEADZONE {
+                        value += sign * position;
+                    }
+                }
+                input => {
+                    if self.held.contains(&Binding{input, inverted:false}) {
+                        value += sign;
+                    }
+                }
+            }
+        }
+        (value.clamp(-1.0, 1.0), motion)
+    }
+    
+    fn stick(&self, axis:Axis) -> f32 {
+        self.sticks.iter().find(|(stick, _)|*stick == axis).map(|(_, position)|*position).unwrap_or(0.0)
+    }
+    
+    fn press(&mut self, input:Input) {
+        if let Some(target) = self.rebinding.take() {
+            if input == Input::Key(Keycode::Escape) {
+                return;
+            }
+            self.rebind(target, Binding{input, inverted:false});
+            return;
+        }
+        let key = Binding{input, inverted:false};
+        self.pressed.push(key);
+        if !self.held.contains(&key) {
+            self.held.push(key);
+        }
+    }
+    
+    fn release(&mut self, input:Input) {
+        self.held.retain(|held|held.input != input);
+    }
+    
+    // a stick is pressed on the side its binding points to, the inverted binding stands for the negative side
+    fn move_stick(&mut self, axis:Axis, position:f32) {
+        let before = self.stick(axis);
+        match self.sticks.iter_mut().find(|(stick, _)|*stick == axis) {
+            Some((_, stored)) => *stored = position,
+            None => self.sticks.push((axis, position)),
+        }
+        
+        if let Some(target) = self.rebinding {
+            if position.abs() > STICK_PRESS {
+                self.rebinding = None;
+                self.rebind(target, Binding{input:Input::Stick(axis), inverted:position < 0.0});
+            }
+            return;
+        }
+        for (inverted, side, was) in [(false, position, before), (true, -position, -before)] {
+            let key = Binding{input:Input::Stick(axis), inverted};
+            let is = side > STICK_PRESS;
+            let was = was > STICK_PRESS;
+            if is && !was {
+                self.pressed.push(key);
+                self.held.push(key);
+            }
+            if !is && was {
+                self.held.retain(|held|*held != key);
+            }
+        }
+    }
+    
+    fn rebind(&mut self, target:RebindTarget, binding:Binding) {
+        match target {
+            RebindTarget::Action(index) => {
+                if let Some((name, bindings)) = self.map.actions.get_mut(index) {
+                    logger::various_log!("input",
+                        (logger::Info, "{} bound to {}", name, binding)
+                    );
+                    *bindings = vec![binding];
+                }
+            }
+            RebindTarget::Axis(index, negative) => {
+                if let Some((name, bindings)) = self.map.axes.get_mut(index) {
+                    // a stick moved the negative way while binding the positive direction pushes it when moved that way
+                    let binding = Binding{inverted: binding.inverted != negative, ..binding};
+                    logger::various_log!("input",
+                        (logger::Info, "{} bound to {}", name, binding)
+                    );
+                    bindings.retain(|existing|existing.inverted != negative);
+                    bindings.push(binding);
+                }
+            }
+        }
+    }
+}
+
+
+impl Binding {
+    // sticks are pressed on one side, for everything else inverted means nothing once pressed
+    fn press_key(&self) -> Binding {
+        Binding{
+            input: self.input,
+            inverted: self.inverted && matches!(self.input, Input::Stick(_)),
+        }
+    }
+}
+
+
+impl FromStr for Binding {
+    type Err = AAError;
+    fn from_str(text:&str) -> Result<Self, Self::Err> {
+        let invalid = ||AAError::InvalidBinding(text.to_owned());
+        let (inverted, rest) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (kind, name) = rest.split_once(':').ok_or_else(invalid)?;
+        let input = match kind {
+            "key" => Input::Key(Keycode::from_name(name).ok_or_else(invalid)?),
+            "mouse" => Input::Mouse(match name.to_lowercase().as_str() {
+                "left" => MouseButton::Left,
+                "middle" => MouseButton::Middle,
+                "right" => MouseButton::Right,
+                "x1" => MouseButton::X1,
+                "x2" => MouseButton::X2,
+                _ => return Err(invalid()),
+            }),
+            "motion" => Input::MouseMotion(match name.to_lowercase().as_str() {
+                "x" => MotionAxis::X,
+                "y" => MotionAxis::Y,
+                _ => return Err(invalid()),
+            }),
+            "button" => Input::Button(Button::from_string(name).ok_or_else(invalid)?),
+            "stick" => Input::Stick(Axis::from_string(name).ok_or_else(invalid)?),
+            _ => return Err(invalid()),
+        };
+        Ok(Binding{input, inverted})
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = AAError;
+    fn try_from(text:String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        if self.inverted {
+            write!(f, "-")?;
+        }
+        match self.input {
+            Input::Key(keycode) => write!(f, "key:{}", keycode.name()),
+            Input::Mouse(button) => write!(f, "mouse:{:?}", button),
+            Input::MouseMotion(axis) => write!(f, "motion:{:?}", axis),
+            Input::Button(button) => write!(f, "button:{}", button.string()),
+            Input::Stick(axis) => write!(f, "stick:{}", axis.string()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn parses_every_kind() {
+        let parsed = |text:&str|text.parse::<Binding>().unwrap();
+        assert_eq!(parsed("key:W"), Binding{input:Input::Key(Keycode::W), inverted:false});
+        assert_eq!(parsed("-key:Left Ctrl"), Binding{input:Input::Key(Keycode::LCtrl), inverted:true});
+        assert_eq!(parsed("mouse:right"), Binding{input:Input::Mouse(MouseButton::Right), inverted:false});
+        assert_eq!(parsed("-motion:y"), Binding{input:Input::MouseMotion(MotionAxis::Y), inverted:true});
+        assert_eq!(parsed("button:a"), Binding{input:Input::Button(Button::A), inverted:false});
+        assert_eq!(parsed("-stick:lefty"), Binding{input:Input::Stick(Axis::LeftY), inverted:true});
+    }
+    
+    #[test]
+    fn display_parses_back() {
+        let bindings = [
+            Binding{input:Input::Key(Keycode::Space), inverted:false},
+            Binding{input:Input::Key(Keycode::LCtrl), inverted:true},
+            Binding{input:Input::Key(Keycode::F12), inverted:false},
+            Binding{input:Input::Mouse(MouseButton::Left), inverted:false},
+            Binding{input:Input::Mouse(MouseButton::X2), inverted:true},
+            Binding{input:Input::MouseMotion(MotionAxis::X), inverted:false},
+            Binding{input:Input::Button(Button::Back), inverted:false},
+            Binding{input:Input::Stick(Axis::TriggerRight), inverted:true},
+        ];
+        for binding in bindings {
+            assert_eq!(binding.to_string().parse::<Binding>().unwrap(), binding, "{}", binding);
+        }
+    }
+    
+    #[test]
+    fn default_map_round_trips() {
+        let map = InputMap::default();
+        for (_, bindings) in map.actions.iter().chain(map.axes.iter()) {
+            for binding in bindings {
+                assert_eq!(binding.to_string().parse::<Binding>().unwrap(), *binding);
+            }
+        }
+    }
+    
+    #[test]
+    fn rejects_malformed() {
+        for text in ["", "W", "key:", "key:NotAKey", "mouse:up", "motion:z", "button:nope", "stick:lefty:", "pad:a", "--key:W"] {
+            assert!(matches!(text.parse::<Binding>(), Err(AAError::InvalidBinding(_))), "{} was accepted", text);
+        }
+    }
+}
@@ -7,23 +7,28 @@ mod constants;
 mod utility;
 mod graphics; 
 mod player;
+mod input;
 mod macros;
 mod game;
+mod config;
 pub use errors::Error as AAError;
 
 use std::time::SystemTime;
 use std::mem::ManuallyDrop;
 
+use serde::Deserialize;
+
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct State {
     verbosity: Verbosity,
-    time: SystemTime
+    time: SystemTime,
+    config: config::Config,
 }
 
-#[allow(dead_code)]
-#[derive(Default, Debug, Clone, Copy)]
-enum Verbosity {
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Verbosity {
     Silent,
     #[default]
     Normal,
@@ -38,18 +43,36 @@ struct HolderStruct {
     game: ManuallyDrop<game::Game>,
 }
 
+// held while a value is moved out of a ManuallyDrop, ends the process before an unwind can drop it again
+struct AbortOnUnwind;
+
+// device losses in a row, without a frame drawn in between, before the gpu is given up on
+const MAX_REBUILDS:u32 = 3;
+
 
 fn main() {
     
-    let _state = State::init();
+    let state = State::init();
+    
+    // offline baking needs neither a window nor a device
+    if state.config.bake {
+        let models:Vec<_> = state.config.models.iter().map(config::asset_path).collect();
+        let failed = vulkan::bake_models(&models, state.config.optimize_meshes);
+        std::process::exit(i32::from(failed != 0));
+    }
     
     //run("res/gltf/basicmesh.glb").expect("runtime error");
     
-    let mut window = window::Window::init();
-    let mut v_init = vulkan::VInit::init(&mut window);
-    let gui = gui::Gui::init(&mut window, &mut v_init);
+    let mut window = window::Window::init(&state.config.window, &state.config.input);
+    let mut v_init = vulkan::VInit::init(&mut window, &state.config).unwrap_or_else(|error|exit_on(error));
+    let gui = gui::Gui::init(&mut window, &mut v_init).unwrap_or_else(|error|exit_on(error));
     let game = game::Game::init();
     
+    // previews of the model are recorded once it is loaded and the renderer exits after writing them
+    if state.config.recording.autostart {
+        v_init.start_recording(graphics::RecordingSettings::default());
+    }
+    
     let mut holder_struct = HolderStruct::new(window, v_init, gui, game);
     let HolderStruct{
         window,
@@ -58,25 +81,113 @@ fn main() {
         game,
     } = &mut holder_struct;
     
+    let mut rebuilds = 0;
     println!("=====================================================================================================================================================================\n=====================================================================================================================================================================");
     while !window.should_close() {
         window.poll_events(gui);
         gui.handle_events(window);
         
-        v_init.handle_events(window);
+        if let Err(error) = v_init.handle_events(window) {
+            on_error(window, v_init, gui, (&state.config, &mut rebuilds), error);
+            continue;
+        }
         
         let (static_metadata, transformations, modifiable_metadata) = v_init.get_gui_data();
         gui.draw_ui(window, static_metadata, transformations, modifiable_metadata);
         
-        v_init.gui_tick(gui.get_ui_data());
+        if let Err(error) = v_init.gui_tick(gui.get_ui_data()) {
+            on_error(window, v_init, gui, (&state.config, &mut rebuilds), error);
+            gui.end_frame();
+            continue;
+        }
+        if window.take_screenshot_request() {
+            v_init.request_capture(gui.get_ui_data().capture);
+        }
         
-        game.step(window);
+        game.show_mesh(v_init.selected_mesh());
+        game.gui_tick(gui.get_ui_data());
+        game.input_tick(window.input());
+        game.step(v_init.recording_timestep());
         
-        v_init.draw_frame(gui);
+        match v_init.draw_frame(gui, game.world(), game.alpha()) {
+            Ok(()) => {
+                rebuilds = 0;
+            }
+            Err(error) => {
+                on_error(window, v_init, gui, (&state.config, &mut rebuilds), error);
+            }
+        }
+        // a frame that returned before the gui was drawn
+        gui.end_frame();
         
+        if state.config.recording.autostart && !v_init.is_recording() {
+            break;
+        }
     }
     println!("=====================================================================================================================================================================\n=====================================================================================================================================================================");
-    v_init.wait_idle();
+    if let Err(error) = v_init.stop_recording() {
+        logger::various_log!("main",
+            (logger::Warn, "recording not finished at exit {:?}", error)
+        );
+    }
+    if let Err(error) = v_init.wait_idle() {
+        logger::various_log!("main",
+            (logger::Warn, "device not idle at exit {:?}", error)
+        );
+    }
+    // teardown can still raise validation errors
+    std::mem::drop(holder_struct);
+    
+    let validation_errors = vulkan::DMessenger::validation_errors();
+    if validation_errors != 0 && vulkan::DMessenger::fail_on_validation() {
+        eprintln!("{} validation errors during the run", validation_errors);
+        std::process::exit(3);
+    }
+}
+
+// only a lost device rebuilds the renderer, anything else costs the rest of the frame,
+// a device lost again right after each rebuild ends the process
+fn on_error(window:&mut window::Window, v_init:&mut ManuallyDrop<vulkan::VInit>, gui:&mut gui::Gui, (config, rebuilds):(&config::Config, &mut u32), error:AAError) {
+    if !error.is_device_lost() {
+        logger::various_log!("main",
+            (logger::Error, "frame skipped {:?}", error)
+        );
+        return;
+    }
+    *rebuilds += 1;
+    if *rebuilds > MAX_REBUILDS {
+        exit_on(error);
+    }
+    recover(window, v_init, gui, config, error);
+}
+
+// the whole renderer is built again on a new device, the window and the gui state survive
+fn recover(window:&mut window::Window, v_init:&mut ManuallyDrop<vulkan::VInit>, gui:&mut gui::Gui, config:&config::Config, error:AAError) {
+    logger::various_log!("main",
+        (logger::Error, "device lost {:?}", error)
+    );
+    gui.release_renderer();
+    // a panic while v_init is taken would unwind into HolderStruct and drop it a second time
+    let _guard = AbortOnUnwind;
+    // SAFETY: v_init is written back before it is used again, when it can't be the process ends without dropping it
+    let old = unsafe{ManuallyDrop::take(v_init)};
+    let rebuilt = old.rebuild(window, config).and_then(|mut holder|{
+        gui.restore_renderer(&mut holder)?;
+        Ok(holder)
+    });
+    match rebuilt {
+        Ok(holder) => {
+            *v_init = ManuallyDrop::new(holder);
+        }
+        Err(error) => {
+            exit_on(error);
+        }
+    }
+}
+
+fn exit_on(error:AAError) -> ! {
+    eprintln!("vulkan can't be used {}", error);
+    std::process::exit(1);
 }
 
 
@@ -100,14 +211,35 @@ impl Drop for HolderStruct {
     }
 }
 
+impl Drop for AbortOnUnwind {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!("panicked while the renderer was being rebuilt");
+            std::process::abort();
+        }
+    }
+}
+
 
 
 #[allow(dead_code)]
 impl State {
     
     fn init() -> Self {
-        env_logger::init();
-        State{time:SystemTime::now(), verbosity:Verbosity::default()}
+        let config = match config::Config::load() {
+            Ok(holder) => holder,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(2);
+            }
+        };
+        
+        // RUST_LOG still takes precedence over the configured verbosity
+        let env = env_logger::Env::default().default_filter_or(config.verbosity.log_filter());
+        env_logger::Builder::from_env(env).init();
+        config::set_asset_root(&config.asset_root);
+        
+        State{time:SystemTime::now(), verbosity:config.verbosity, config}
     }
     
 /*
@@ -139,6 +271,17 @@ impl State {
     
 }
 
+impl Verbosity {
+    fn log_filter(self) -> &'static str {
+        match self {
+            Verbosity::Silent => "off",
+            Verbosity::Normal => "info",
+            Verbosity::Expresive => "debug",
+            Verbosity::Dump => "trace",
+        }
+    }
+}
+
 unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
     ::core::slice::from_raw_parts(
         (p as *const T) as *const u8,
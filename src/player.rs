@@ -9,15 +9,3 @@ pub struct Camera {
     pub direction: Vector3<f32>,
     
 }
-
-#[allow(dead_code)]
-pub enum Movement {
-    Foreward,
-    Backward,
-    Left,
-    Rigth,
-    Up,
-    Down,
-}
-
-
@@ -8,13 +8,22 @@ layout(set = 0, binding = 0) uniform  SceneData{
 	vec4 sunlightColor;
 } sceneData;
 
-layout(set = 1, binding = 0) uniform GLTFMaterialData{   
+struct Material {
 
 	vec4 colorFactors;
 	vec4 metal_rough_factors;
-	
-} materialData;
+	uint colorTex;
+	uint colorSampler;
+	uint metalRoughTex;
+	uint metalRoughSampler;
+};
 
-layout(set = 1, binding = 1) uniform sampler2D colorTex;
-layout(set = 1, binding = 2) uniform sampler2D metalRoughTex;
+//bindless tables, indexed with the material index of the push constant
+layout(set = 1, binding = 0) readonly buffer MaterialTable{
+
+	Material materials[];
+} materialTable;
+
+layout(set = 1, binding = 1) uniform sampler samplers[16];
+layout(set = 1, binding = 2) uniform texture2D textures[];
 
@@ -2,12 +2,14 @@
 
 #extension GL_GOOGLE_include_directive : require
 #extension GL_EXT_buffer_reference : require
+#extension GL_EXT_nonuniform_qualifier : require
 
 #include "input_structures.glsl"
 
 layout (location = 0) out vec3 outNormal;
 layout (location = 1) out vec3 outColor;
 layout (location = 2) out vec2 outUV;
+layout (location = 3) flat out uint outMaterial;
 
 struct Vertex {
 
@@ -16,30 +18,55 @@ struct Vertex {
 	vec3 normal;
 	float uv_y;
 	vec4 color;
+	vec4 tangent;
+	vec2 uv1;
+	vec2 padding;
 }; 
 
 layout(buffer_reference, std430) readonly buffer VertexBuffer{ 
 	Vertex vertices[];
 };
 
+struct Instance {
+
+	mat4 transform;
+	vec4 tint;
+	vec4 center;
+	vec4 extents;
+	uint firstIndex;
+	uint indexCount;
+	int vertexOffset;
+	uint materialIndex;
+};
+
+layout(buffer_reference, std430) readonly buffer InstanceBuffer{
+	Instance instances[];
+};
+
 //push constants block
 layout( push_constant ) uniform constants
 {
 	mat4 render_matrix;
 	VertexBuffer vertexBuffer;
+	InstanceBuffer instanceBuffer;
 } PushConstants;
 
 void main() 
 {
 	Vertex v = PushConstants.vertexBuffer.vertices[gl_VertexIndex];
+	// gl_InstanceIndex already includes the first instance of the draw
+	Instance instance = PushConstants.instanceBuffer.instances[gl_InstanceIndex];
+	mat4 render_matrix = PushConstants.render_matrix * instance.transform;
+	uint materialIndex = instance.materialIndex;
 	
 	vec4 position = vec4(v.position, 1.0f);
 
-	gl_Position =  sceneData.viewproj * PushConstants.render_matrix *position;
+	gl_Position =  sceneData.viewproj * render_matrix *position;
 
-	outNormal = (PushConstants.render_matrix * vec4(v.normal, 0.f)).xyz;
-	outColor = v.color.xyz * materialData.colorFactors.xyz;	
+	outNormal = (render_matrix * vec4(v.normal, 0.f)).xyz;
+	outColor = v.color.xyz * instance.tint.xyz * materialTable.materials[materialIndex].colorFactors.xyz;
 	outUV.x = v.uv_x;
 	outUV.y = v.uv_y;
+	outMaterial = materialIndex;
 }
 
@@ -0,0 +1,351 @@
+use crate::logger;
+use crate::errors::messages::U32_TO_USIZE;
+
+use super::VkMeshAsset;
+use super::MaterialInstance;
+use super::GeometryBuffers;
+use super::Image;
+
+use super::VkDestructor;
+use super::VkDeferedDestructor;
+use super::VkDestructorArguments;
+use super::super::DestructionStack;
+use super::super::materials::BindlessTables;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use arrayvec::ArrayString;
+
+// index into an AssetPool, the generation makes a handle to a freed and reused slot miss instead of aliasing
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    marker: PhantomData<fn()->T>,
+}
+
+pub type MeshHandle = Handle<VkMeshAsset>;
+pub type TextureHandle = Handle<Image>;
+pub type MaterialHandle = Handle<MaterialInstance>;
+pub type SceneHandle = Handle<SceneAsset>;
+
+struct Slot<T> {
+    generation: u32,
+    users: u32,
+    value: Option<T>,
+}
+
+// values with an explicit user count, nothing outside the pool can keep one alive
+pub struct AssetPool<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+}
+
+// a loaded file, its users are the loads of that path
+#[derive(Debug)]
+pub struct SceneAsset {
+    pub path: PathBuf,
+    pub meshes: Vec<MeshHandle>,
+    pub materials: Vec<MaterialHandle>,
+    pub textures: Vec<TextureHandle>,
+}
+
+// where a texture comes from, the same image of the same file is only uploaded once
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextureKey {
+    pub file: PathBuf,
+    pub image: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MeshEntry {
+    pub handle: MeshHandle,
+    pub name: ArrayString<64>,
+}
+
+pub struct AssetManager {
+    pub meshes: AssetPool<VkMeshAsset>,
+    pub textures: AssetPool<Image>,
+    pub materials: AssetPool<MaterialInstance>,
+    pub scenes: AssetPool<SceneAsset>,
+    scene_paths: HashMap<PathBuf, SceneHandle>,
+    texture_keys: HashMap<TextureKey, TextureHandle>,
+    // drawable meshes in load order, what the gui lists
+    mesh_list: Vec<MeshEntry>,
+}
+
+
+impl<T> AssetPool<T> {
+    pub fn new() -> Self {
+        Self{
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+    
+//----
+    // the caller is the first user
+    pub fn insert(&mut self, value:T) -> Handle<T> {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.users = 1;
+                slot.value = Some(value);
+                Handle::new(index, slot.generation)
+            }
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("asset pool should not outgrow u32");
+                self.slots.push(Slot{generation: 0, users: 1, value: Some(value)});
+                Handle::new(index, 0)
+            }
+        }
+    }
+    
+    pub fn get(&self, handle:Handle<T>) -> Option<&T> {
+        self.slot(handle)?.value.as_ref()
+    }
+    
+    pub fn get_mut(&mut self, handle:Handle<T>) -> Option<&mut T> {
+        let slot = self.slots.get_mut(handle.index as usize).filter(|slot|slot.generation == handle.generation)?;
+        slot.value.as_mut()
+    }
+    
+//----
+    // false when the handle is stale
+    pub fn acquire(&mut self, handle:Handle<T>) -> bool {
+        match self.slots.get_mut(handle.index as usize).filter(|slot|slot.generation == handle.generation && slot.value.is_some()) {
+            Some(slot) => {
+                slot.users += 1;
+                true
+            }
+            None => false,
+        }
+    }
+    
+//----
+    // the value comes back once its last user is gone, the handles to it go stale
+    pub fn release(&mut self, handle:Handle<T>) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index as usize).filter(|slot|slot.generation == handle.generation && slot.value.is_some())?;
+        slot.users -= 1;
+        if slot.users != 0 {
+            return None;
+        }
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        slot.value.take()
+    }
+    
+    pub fn users(&self, handle:Handle<T>) -> u32 {
+        self.slot(handle).filter(|slot|slot.value.is_some()).map_or(0, |slot|slot.users)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+    
+    fn slot(&self, handle:Handle<T>) -> Option<&Slot<T>> {
+        self.slots.get(handle.index as usize).filter(|slot|slot.generation == handle.generation)
+    }
+    
+    fn values(&self) -> impl Iterator<Item=(&T, u32)> + '_ {
+        self.slots.iter().filter_map(|slot|slot.value.as_ref().map(|value|(value, slot.users)))
+    }
+    
+    // every value regardless of its users, only for teardown
+    fn drain(&mut self) -> impl Iterator<Item=T> + '_ {
+        self.free.clear();
+        self.slots.drain(..).filter_map(|slot|slot.value)
+    }
+}
+
+
+impl AssetManager {
+    pub fn new() -> Self {
+        Self{
+            meshes: AssetPool::new(),
+            textures: AssetPool::new(),
+            materials: AssetPool::new(),
+            scenes: AssetPool::new(),
+            scene_paths: HashMap::new(),
+            texture_keys: HashMap::new(),
+            mesh_list: Vec::new(),
+        }
+    }
+    
+//----
+    // a scene already loaded from the path gets one more user
+    pub fn find_scene(&mut self, path:&PathBuf) -> Option<SceneHandle> {
+        let handle = *self.scene_paths.get(path)?;
+        self.scenes.acquire(handle).then_some(handle)
+    }
+    
+    pub fn add_scene(&mut self, path:PathBuf) -> SceneHandle {
+        let handle = self.scenes.insert(SceneAsset{
+            path: path.clone(),
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            textures: Vec::new(),
+        });
+        self.scene_paths.insert(path, handle);
+        handle
+    }
+    
+    // the scene stays until its users are gone, but the next load of its path starts over
+    pub fn forget_scene_path(&mut self, handle:SceneHandle) {
+        self.scene_paths.retain(|_, known|*known != handle);
+    }
+    
+//----
+    // a texture another scene already uploaded gets the scene as one more user
+    pub fn share_texture(&mut self, scene:SceneHandle, key:&TextureKey) -> Option<TextureHandle> {
+        let handle = *self.texture_keys.get(key)?;
+        if !self.textures.acquire(handle) {
+            return None;
+        }
+        self.attach(scene, |scene|scene.textures.push(handle));
+        Some(handle)
+    }
+    
+    pub fn add_texture(&mut self, scene:SceneHandle, key:TextureKey, image:Image) -> TextureHandle {
+        let handle = self.textures.insert(image);
+        self.texture_keys.insert(key, handle);
+        self.attach(scene, |scene|scene.textures.push(handle));
+        handle
+    }
+    
+    pub fn add_material(&mut self, scene:SceneHandle, instance:MaterialInstance) -> MaterialHandle {
+        let handle = self.materials.insert(instance);
+        self.attach(scene, |scene|scene.materials.push(handle));
+        handle
+    }
+    
+    pub fn add_mesh(&mut self, scene:SceneHandle, mesh:VkMeshAsset) -> MeshHandle {
+        let name = mesh.name;
+        let handle = self.meshes.insert(mesh);
+        self.mesh_list.push(MeshEntry{handle, name});
+        self.attach(scene, |scene|scene.meshes.push(handle));
+        handle
+    }
+    
+    // the path of every scene once per load, loading them again restores the same users
+    pub fn scene_loads(&self) -> Vec<PathBuf> {
+        self.scenes.values()
+            .flat_map(|(scene, users)|std::iter::repeat_n(scene.path.clone(), usize::try_from(users).expect(U32_TO_USIZE)))
+            .collect()
+    }
+    
+    pub fn mesh_list(&self) -> &[MeshEntry] {
+        &self.mesh_list
+    }
+    
+    // the jobs only add to scenes they were started for, those can't be released before the job is done
+    fn attach<F: FnOnce(&mut SceneAsset)>(&mut self, scene:SceneHandle, attach:F) {
+        match self.scenes.get_mut(scene) {
+            Some(scene) => attach(scene),
+            None => {
+                logger::various_log!("assets",
+                    (logger::Warn, "asset added to the released scene {:?}, it won't be freed before teardown", scene)
+                );
+            }
+        }
+    }
+    
+//----
+    // drops a user of the scene, once it has none the assets nobody else uses are released
+    // through the destruction stack, it has to belong to the last recorded frame
+    pub fn release_scene(
+        &mut self,
+        handle: SceneHandle,
+        geometry: &GeometryBuffers,
+        bindless: &mut BindlessTables,
+        destruction_stack: &mut DestructionStack,
+    ) -> bool {
+        let Some(scene) = self.scenes.release(handle) else {return false};
+        self.forget_scene_path(handle);
+        
+        for mesh in scene.meshes.into_iter() {
+            if let Some(asset) = self.meshes.release(mesh) {
+                self.mesh_list.retain(|entry|entry.handle != mesh);
+                destruction_stack.push(geometry.defered_free(asset.geometry));
+            }
+        }
+        for material in scene.materials.into_iter() {
+            if let Some(instance) = self.materials.release(material) {
+                destruction_stack.push(bindless.defered_release_material(instance.material_index));
+            }
+        }
+        for texture in scene.textures.into_iter() {
+            if let Some(mut image) = self.textures.release(texture) {
+                self.texture_keys.retain(|_, known|*known != texture);
+                if let Some(destructor) = bindless.defered_release_texture(&image) {
+                    destruction_stack.push(destructor);
+                }
+                destruction_stack.push(image.defered_destruct());
+            }
+        }
+        
+        logger::various_log!("assets",
+            (logger::Debug, "scene {:?} released, {} meshes {} materials {} textures left", scene.path, self.meshes.len(), self.materials.len(), self.textures.len())
+        );
+        true
+    }
+}
+
+// the device is idle at teardown, so nothing has to wait for a frame
+impl VkDestructor for AssetManager {
+    fn destruct(mut self, mut args:VkDestructorArguments) {
+        logger::destruct!("asset_manager");
+        let (device, allocator) = args.unwrap_dev_all();
+        for texture in self.textures.drain() {
+            texture.destruct(VkDestructorArguments::DevAll(device, allocator));
+        }
+        // the geometry and the bindless slots go away with their owners,
+        // the materials only have to drop their pipelines before the pipelines are destroyed
+        self.materials.drain().for_each(drop);
+        self.meshes.drain().for_each(drop);
+    }
+}
+
+
+impl<T> Handle<T> {
+    fn new(index:u32, generation:u32) -> Self {
+        Self{
+            index,
+            generation,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other:&Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state:&mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, formatter:&mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "Handle({}v{})", self.index, self.generation)
+    }
+}
@@ -0,0 +1,449 @@
+use crate::AAError;
+use crate::errors::messages::GRANTED;
+use crate::errors::messages::MODEL_DENSITY;
+use crate::errors::messages::COMPILETIME_ASSERT;
+
+use super::Vertex;
+use super::Bounds;
+use super::mesh::CpuMesh;
+use super::mesh::CpuSurface;
+use super::mesh::CpuMaterial;
+use super::mesh::MeshView;
+use super::mesh::truncated_name;
+
+use std::fs;
+use std::iter;
+use std::mem::size_of;
+use std::ops::Range;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrayvec::ArrayString;
+use memmap2::Mmap;
+use nalgebra as na;
+use na::Vector3;
+use na::Vector4;
+use xxhash_rust::xxh3::xxh3_64;
+
+const BAKE_MAGIC:[u8; 8] = *b"AABAKE\0\0";
+// bumped whenever the layout or what the parsers produce changes
+const BAKE_VERSION:u32 = 1;
+const BAKE_EXTENSION:&str = "bake";
+// the index and vertex arrays start aligned, so they can be read in place from the mapping
+const SECTION_ALIGN:usize = 16;
+const OPTIMIZED_FLAG:u32 = 1;
+const NO_INDEX:u32 = u32::MAX;
+
+// the meshes are read in place from the mapped cache, the rest is small enough to copy
+pub struct BakedModel {
+    pub meshes: Vec<BakedMesh>,
+    pub materials: Vec<CpuMaterial>,
+    pub textures: Vec<PathBuf>,
+}
+
+pub struct BakedMesh {
+    map: Arc<Mmap>,
+    name: ArrayString<64>,
+    surfaces: Vec<CpuSurface>,
+    bounds: Bounds,
+    // byte ranges of the mapping, checked when the cache was read
+    indices: Range<usize>,
+    vertices: Range<usize>,
+}
+
+// what a cache is written from
+pub struct BakeSource<'a> {
+    pub meshes: &'a [CpuMesh],
+    pub materials: &'a [CpuMaterial],
+    pub textures: &'a [PathBuf],
+    // files besides the source the meshes and materials were read from
+    pub dependencies: &'a [PathBuf],
+}
+
+// fields are native endian, the cache belongs to the machine that wrote it
+struct BakeWriter {
+    bytes: Vec<u8>,
+}
+
+struct BakeReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+
+// next to the source, model.glb is baked into model.glb.bake
+pub fn baked_path(source:&Path) -> PathBuf {
+    let mut holder = source.as_os_str().to_owned();
+    holder.push(".");
+    holder.push(BAKE_EXTENSION);
+    PathBuf::from(holder)
+}
+
+//----
+// every dependency is hashed, so the cache is only used while the files it came from stay the same,
+// it is written aside and renamed over the old one, a mapping of the old file never sees it change
+pub fn write_baked(source:&Path, optimized:bool, bake:&BakeSource) -> Result<PathBuf, AAError> {
+    let base = source.parent().unwrap_or(Path::new(""));
+    let mut writer = BakeWriter{bytes: Vec::new()};
+    
+    writer.bytes.extend_from_slice(&BAKE_MAGIC);
+    writer.u32(BAKE_VERSION);
+    writer.u32(u32::try_from(size_of::<Vertex>()).expect(COMPILETIME_ASSERT));
+    writer.u32(if optimized {OPTIMIZED_FLAG} else {0});
+    
+    writer.count(bake.dependencies.len() + 1);
+    for dependency in iter::once(source).chain(bake.dependencies.iter().map(PathBuf::as_path)) {
+        writer.u64(xxh3_64(&fs::read(dependency)?));
+        writer.path(base, dependency);
+    }
+    
+    writer.count(bake.materials.len());
+    for material in bake.materials.iter() {
+        material.color_factors.iter().chain(material.metal_rough_factors.iter()).for_each(|&factor|writer.f32(factor));
+        writer.index(material.base_color);
+    }
+    
+    writer.count(bake.textures.len());
+    for texture in bake.textures.iter() {
+        writer.path(base, texture);
+    }
+    
+    writer.count(bake.meshes.len());
+    for mesh in bake.meshes.iter() {
+        let view = mesh.view();
+        writer.string(&view.name);
+        writer.count(view.surfaces.len());
+        for surface in view.surfaces.iter() {
+            writer.u32(surface.start_index);
+            writer.u32(surface.count);
+            writer.index(surface.material);
+        }
+        view.bounds.center.iter().chain(view.bounds.extents.iter()).for_each(|&component|writer.f32(component));
+        writer.count(view.indices.len());
+        writer.count(view.vertices.len());
+        writer.section(as_bytes(view.indices));
+        writer.section(as_bytes(view.vertices));
+    }
+    
+    let path = baked_path(source);
+    let temporary = path.with_extension(format!("{}.tmp", BAKE_EXTENSION));
+    fs::write(&temporary, &writer.bytes)?;
+    fs::rename(&temporary, &path)?;
+    Ok(path)
+}
+
+//----
+// fails when there is no cache, when it was written by another version or settings, or when a source changed
+pub fn read_baked(source:&Path, optimized:bool) -> Result<BakedModel, AAError> {
+    let base = source.parent().unwrap_or(Path::new(""));
+    let file = fs::File::open(baked_path(source))?;
+    // the writer never modifies a cache in place, see write_baked
+    let map = Arc::new(unsafe{Mmap::map(&file)}?);
+    let mut reader = BakeReader{bytes: &map, offset: 0};
+    
+    if reader.take(BAKE_MAGIC.len())? != BAKE_MAGIC {
+        return Err(AAError::BakeRejected("not a baked model"));
+    }
+    if reader.u32()? != BAKE_VERSION {
+        return Err(AAError::BakeRejected("written by another version"));
+    }
+    if usize::try_from(reader.u32()?).expect(GRANTED) != size_of::<Vertex>() {
+        return Err(AAError::BakeRejected("written with another vertex layout"));
+    }
+    if (reader.u32()? & OPTIMIZED_FLAG != 0) != optimized {
+        return Err(AAError::BakeRejected("written with other optimization settings"));
+    }
+    
+    for _ in 0..reader.count()? {
+        let hash = reader.u64()?;
+        let dependency = base.join(reader.string()?);
+        if xxh3_64(&fs::read(dependency)?) != hash {
+            return Err(AAError::BakeRejected("its sources changed"));
+        }
+    }
+    
+    // counts are not trusted for allocations, a damaged one only fails as truncated
+    let material_count = reader.count()?;
+    let mut materials = Vec::new();
+    for _ in 0..material_count {
+        let mut factors = [0.0; 8];
+        for factor in factors.iter_mut() {
+            *factor = reader.f32()?;
+        }
+        materials.push(CpuMaterial{
+            color_factors: Vector4::new(factors[0], factors[1], factors[2], factors[3]),
+            metal_rough_factors: Vector4::new(factors[4], factors[5], factors[6], factors[7]),
+            base_color: reader.index()?,
+        });
+    }
+    
+    let texture_count = reader.count()?;
+    let mut textures = Vec::new();
+    for _ in 0..texture_count {
+        textures.push(base.join(reader.string()?));
+    }
+    
+    let mesh_count = reader.count()?;
+    let mut meshes = Vec::new();
+    for _ in 0..mesh_count {
+        let name = truncated_name(reader.string()?);
+        let surface_count = reader.count()?;
+        let mut surfaces = Vec::new();
+        for _ in 0..surface_count {
+            surfaces.push(CpuSurface{
+                start_index: reader.u32()?,
+                count: reader.u32()?,
+                material: reader.index()?,
+            });
+        }
+        let bounds = Bounds{
+            center: Vector3::new(reader.f32()?, reader.f32()?, reader.f32()?),
+            extents: Vector3::new(reader.f32()?, reader.f32()?, reader.f32()?),
+        };
+        let index_count = reader.count()?;
+        let vertex_count = reader.count()?;
+        let indices = reader.section(index_count.checked_mul(size_of::<u32>()).ok_or(AAError::BakeRejected("truncated"))?)?;
+        let vertices = reader.section(vertex_count.checked_mul(size_of::<Vertex>()).ok_or(AAError::BakeRejected("truncated"))?)?;
+        
+        let mesh = BakedMesh{map: map.clone(), name, surfaces, bounds, indices, vertices};
+        mesh.validate()?;
+        meshes.push(mesh);
+    }
+    
+    Ok(BakedModel{meshes, materials, textures})
+}
+
+// the bytes of plain vertex and index arrays, every bit of them is initialized
+fn as_bytes<T: Copy>(values:&[T]) -> &[u8] {
+    unsafe{std::slice::from_raw_parts(values.as_ptr() as *const u8, std::mem::size_of_val(values))}
+}
+
+// only for sections, the reader checked their bounds and alignment
+fn from_bytes<T: Copy>(bytes:&[u8]) -> &[T] {
+    unsafe{std::slice::from_raw_parts(bytes.as_ptr() as *const T, bytes.len()/size_of::<T>())}
+}
+
+
+impl BakedMesh {
+    pub fn view(&self) -> MeshView<'_> {
+        MeshView{
+            name: self.name,
+            surfaces: &self.surfaces,
+            indices: from_bytes(&self.map[self.indices.clone()]),
+            vertices: from_bytes(&self.map[self.vertices.clone()]),
+            bounds: self.bounds,
+        }
+    }
+    
+    // a damaged cache must not reach the gpu with indices past its vertices
+    fn validate(&self) -> Result<(), AAError> {
+        let view = self.view();
+        let vertex_count = u32::try_from(view.vertices.len()).expect(MODEL_DENSITY);
+        if view.indices.iter().any(|&index|index >= vertex_count) {
+            return Err(AAError::BakeRejected("index out of its vertices"));
+        }
+        let index_count = u64::try_from(view.indices.len()).expect(MODEL_DENSITY);
+        if view.surfaces.iter().any(|surface|u64::from(surface.start_index) + u64::from(surface.count) > index_count) {
+            return Err(AAError::BakeRejected("surface out of its indices"));
+        }
+        Ok(())
+    }
+}
+
+
+impl BakeWriter {
+    fn u32(&mut self, value:u32) {
+        self.bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+    
+    fn u64(&mut self, value:u64) {
+        self.bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+    
+    fn f32(&mut self, value:f32) {
+        self.bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+    
+    fn count(&mut self, count:usize) {
+        self.u32(u32::try_from(count).expect(MODEL_DENSITY));
+    }
+    
+    fn index(&mut self, index:Option<usize>) {
+        self.u32(index.map_or(NO_INDEX, |index|u32::try_from(index).expect(MODEL_DENSITY)));
+    }
+    
+    fn string(&mut self, value:&str) {
+        self.count(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+    
+    // relative to the source when it is below it, so the cache moves along with its model
+    fn path(&mut self, base:&Path, path:&Path) {
+        self.string(&path.strip_prefix(base).unwrap_or(path).to_string_lossy());
+    }
+    
+    fn section(&mut self, bytes:&[u8]) {
+        self.bytes.resize(self.bytes.len().next_multiple_of(SECTION_ALIGN), 0);
+        self.bytes.extend_from_slice(bytes);
+    }
+}
+
+impl<'a> BakeReader<'a> {
+    fn take(&mut self, len:usize) -> Result<&'a [u8], AAError> {
+        let end = self.offset.checked_add(len)
+            .filter(|&end|end <= self.bytes.len())
+            .ok_or(AAError::BakeRejected("truncated"))?;
+        let holder = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(holder)
+    }
+    
+    fn u32(&mut self) -> Result<u32, AAError> {
+        Ok(u32::from_ne_bytes(self.take(4)?.try_into().expect(GRANTED)))
+    }
+    
+    fn u64(&mut self) -> Result<u64, AAError> {
+        Ok(u64::from_ne_bytes(self.take(8)?.try_into().expect(GRANTED)))
+    }
+    
+    fn f32(&mut self) -> Result<f32, AAError> {
+        Ok(f32::from_ne_bytes(self.take(4)?.try_into().expect(GRANTED)))
+    }
+    
+    fn count(&mut self) -> Result<usize, AAError> {
+        Ok(usize::try_from(self.u32()?).expect(GRANTED))
+    }
+    
+    fn index(&mut self) -> Result<Option<usize>, AAError> {
+        Ok(match self.u32()? {
+            NO_INDEX => None,
+            index => Some(usize::try_from(index).expect(GRANTED)),
+        })
+    }
+    
+    fn string(&mut self) -> Result<&'a str, AAError> {
+        let len = self.count()?;
+        std::str::from_utf8(self.take(len)?).map_err(|_|AAError::BakeRejected("malformed string"))
+    }
+    
+    // the mapping starts page aligned, so an aligned offset is an aligned address
+    fn section(&mut self, len:usize) -> Result<Range<usize>, AAError> {
+        self.offset = self.offset.next_multiple_of(SECTION_ALIGN);
+        let start = self.offset;
+        if !(self.bytes.as_ptr() as usize + start).is_multiple_of(SECTION_ALIGN) {
+            return Err(AAError::BakeRejected("misaligned section"));
+        }
+        self.take(len)?;
+        Ok(start..self.offset)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // a model file next to nothing else, every test gets its own directory
+    fn source(test:&str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("c_bind_bake_{}_{}", std::process::id(), test));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("model.obj");
+        fs::write(&path, b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        path
+    }
+    
+    fn triangle(indices:Vec<u32>) -> CpuMesh {
+        let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]].map(|position|Vertex{
+            position: Vector3::from(position),
+            normal: Vector3::z(),
+            ..Vertex::default()
+        });
+        CpuMesh{
+            name: truncated_name("triangle"),
+            surfaces: vec![CpuSurface{start_index:0, count:3, material:Some(0)}],
+            indices,
+            vertices: vertices.to_vec(),
+        }
+    }
+    
+    fn bake(source:&Path, meshes:&[CpuMesh]) -> PathBuf {
+        let materials = [CpuMaterial{
+            color_factors: Vector4::new(1.0, 0.5, 0.25, 1.0),
+            metal_rough_factors: Vector4::new(0.0, 1.0, 0.0, 0.0),
+            base_color: Some(0),
+        }];
+        let textures = [source.with_file_name("albedo.png")];
+        write_baked(source, true, &BakeSource{meshes, materials:&materials, textures:&textures, dependencies:&[]}).unwrap()
+    }
+    
+    fn rejection(result:Result<BakedModel, AAError>) -> &'static str {
+        match result {
+            Err(AAError::BakeRejected(reason)) => reason,
+            Err(error) => panic!("expected a rejection, got {:?}", error),
+            Ok(_) => panic!("expected a rejection, the cache was accepted"),
+        }
+    }
+    
+    #[test]
+    fn round_trip() {
+        let source = source("round_trip");
+        let mesh = triangle(vec![0, 1, 2]);
+        bake(&source, std::slice::from_ref(&mesh));
+        
+        let model = read_baked(&source, true).unwrap();
+        assert_eq!(model.textures, vec![source.with_file_name("albedo.png")]);
+        assert_eq!(model.materials.len(), 1);
+        assert_eq!(model.materials[0].color_factors, Vector4::new(1.0, 0.5, 0.25, 1.0));
+        assert_eq!(model.materials[0].base_color, Some(0));
+        
+        assert_eq!(model.meshes.len(), 1);
+        let view = model.meshes[0].view();
+        assert_eq!(view.name.as_str(), "triangle");
+        assert_eq!(view.indices, mesh.indices.as_slice());
+        assert_eq!(view.surfaces.len(), 1);
+        assert_eq!(view.surfaces[0].material, Some(0));
+        let positions:Vec<_> = view.vertices.iter().map(|vertex|vertex.position).collect();
+        assert_eq!(positions, mesh.vertices.iter().map(|vertex|vertex.position).collect::<Vec<_>>());
+    }
+    
+    #[test]
+    fn rejects_other_settings_and_changed_sources() {
+        let source = source("settings");
+        bake(&source, &[triangle(vec![0, 1, 2])]);
+        assert_eq!(rejection(read_baked(&source, false)), "written with other optimization settings");
+        
+        fs::write(&source, b"v 0 0 1\n").unwrap();
+        assert_eq!(rejection(read_baked(&source, true)), "its sources changed");
+    }
+    
+    #[test]
+    fn rejects_truncated() {
+        let source = source("truncated");
+        let path = bake(&source, &[triangle(vec![0, 1, 2])]);
+        let bytes = fs::read(&path).unwrap();
+        for len in [0, 4, BAKE_MAGIC.len() + 6, bytes.len() - 1] {
+            fs::write(&path, &bytes[..len]).unwrap();
+            assert_eq!(rejection(read_baked(&source, true)), "truncated");
+        }
+    }
+    
+    #[test]
+    fn rejects_indices_past_the_vertices() {
+        let source = source("indices");
+        bake(&source, &[triangle(vec![0, 1, 3])]);
+        assert_eq!(rejection(read_baked(&source, true)), "index out of its vertices");
+    }
+    
+    #[test]
+    fn rejects_misaligned_sections() {
+        // u128 keeps the backing bytes 16 aligned, so one byte in is never aligned
+        let backing = [0u128; 4];
+        let bytes = as_bytes(&backing);
+        let mut reader = BakeReader{bytes: &bytes[1..], offset: 0};
+        assert!(matches!(reader.section(4), Err(AAError::BakeRejected("misaligned section"))));
+        
+        let mut reader = BakeReader{bytes, offset: 1};
+        assert_eq!(reader.section(4).unwrap(), SECTION_ALIGN..SECTION_ALIGN+4);
+    }
+}
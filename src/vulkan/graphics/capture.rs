@@ -0,0 +1,210 @@
+use crate::AAError;
+use crate::logger;
+use crate::errors::messages::CPU_ACCESIBLE;
+use crate::graphics::CaptureRequest;
+use crate::graphics::CaptureResolution;
+use crate::graphics::CaptureLayout;
+use crate::graphics::CapturedImage;
+
+use super::VkDestructor;
+use super::VkDestructorArguments;
+use super::super::Device;
+use super::super::Allocator;
+use super::super::Buffer;
+use super::super::Image;
+use super::super::Swapchain;
+use super::super::image::ImageMetadata;
+use super::super::render_graph::RenderGraph;
+use super::super::render_graph::ImageHandle;
+use super::super::render_graph::ResourceState;
+use super::super::render_graph::Access;
+
+use std::slice::from_ref;
+use std::sync::mpsc::Sender;
+
+use ash::vk;
+use gpu_allocator as gpu_all;
+
+
+// readback of a frame, it lives in the frame slot until the fence of that frame is waited on
+pub struct PendingCapture {
+    buffer: Buffer,
+    request: CaptureRequest,
+    source_extent: vk::Extent2D,
+    source_format: vk::Format,
+    extent: vk::Extent2D,
+    layout: CaptureLayout,
+    // recordings take the image, screenshots are saved on their own
+    sink: Option<Sender<CapturedImage>>,
+}
+
+
+impl PendingCapture {
+    
+//----
+    // render is the format and drawn region of the canvas, the overlay is dropped when the swapchain can't be copied from
+    pub fn create(
+        device: &mut Device,
+        allocator: &mut Allocator,
+        mut request: CaptureRequest,
+        render: (vk::Format, vk::Extent2D),
+        swapchain: &Swapchain,
+        sink: Option<Sender<CapturedImage>>,
+    ) -> Result<Self, AAError> {
+        
+        let swapchain_layout = Self::layout_of(swapchain.surface_format.format);
+        if request.overlay && (!swapchain.capturable || swapchain_layout.is_none()) {
+            logger::various_log!("capture",
+                (logger::Warn, "swapchain {:?} can't be captured, taking the screenshot without the overlay", swapchain.surface_format.format)
+            );
+            request.overlay = false;
+        }
+        
+        let (source_format, source_extent) = match request.overlay {
+            true => (swapchain.surface_format.format, swapchain.extent),
+            false => render,
+        };
+        let extent = match request.resolution {
+            CaptureResolution::Internal => render.1,
+            CaptureResolution::Swapchain => swapchain.extent,
+        };
+        let layout = match request.overlay {
+            true => swapchain_layout,
+            false => Self::layout_of(source_format),
+        }.ok_or(vk::Result::ERROR_FORMAT_NOT_SUPPORTED)?;
+        
+        let size = extent.width as u64 * extent.height as u64 * layout.texel_size();
+        let buffer = Buffer::create(device, allocator, Some("capture_buffer"), size, vk::BufferUsageFlags::TRANSFER_DST, gpu_all::MemoryLocation::GpuToCpu)?;
+        
+        Ok(Self{
+            buffer,
+            request,
+            source_extent,
+            source_format,
+            extent,
+            layout,
+            sink,
+        })
+    }
+    
+//----
+    // has to be called once the passes drawing what is captured are in the graph, the overlay reads the swapchain image
+    pub fn add_passes(&self, graph:&mut RenderGraph, render_handle:ImageHandle, swapchain_handle:ImageHandle) {
+        let source_handle = match self.request.overlay {
+            true => swapchain_handle,
+            false => render_handle,
+        };
+        let source_extent = self.source_extent;
+        let extent = self.extent;
+        
+        let copy_handle = if source_extent != extent {
+            let scaled_handle = graph.create_image("capture_image", vk::Extent3D::from(extent), ImageMetadata::capture(self.source_format));
+            graph.add_pass("capture_scale")
+                .read_image(source_handle, Access::BlitSrc)
+                .write_image(scaled_handle, Access::BlitDst)
+                .execute(move |device, cmd, resources|{
+                    let source = resources.image(source_handle);
+                    let destination = resources.image(scaled_handle);
+                    Image::raw_copy_image_to_image(device, cmd, source.image, vk::Extent3D::from(source_extent), destination.image, destination.extent);
+                });
+            scaled_handle
+        } else {
+            source_handle
+        };
+        
+        let buffer = self.buffer.buffer;
+        let buffer_handle = graph.import_buffer("capture_buffer", buffer, ResourceState::UNDEFINED);
+        graph.add_pass("capture_copy")
+            .read_image(copy_handle, Access::TransferSrc)
+            .write_buffer(buffer_handle, Access::TransferDst)
+            .execute(move |device, cmd, resources|{
+                Self::copy_to_buffer(device, cmd, resources.image(copy_handle).image, extent, buffer);
+            });
+    }
+    
+//----
+    // the caller waited on the fence of the frame that recorded the copy
+    pub fn complete(self, device:&mut Device, allocator:&mut Allocator) {
+        let PendingCapture{
+            buffer,
+            request,
+            extent,
+            layout,
+            sink,
+            ..
+        } = self;
+        let data = buffer.allocation.mapped_slice().expect(CPU_ACCESIBLE).to_vec();
+        buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
+        let image = CapturedImage{
+            width: extent.width,
+            height: extent.height,
+            layout,
+            format: request.format,
+            data,
+        };
+        match sink {
+            Some(sink) => {
+                if sink.send(image).is_err() {
+                    logger::various_log!("capture",
+                        (logger::Warn, "recorded frame dropped, the recording writer is gone")
+                    );
+                }
+            }
+            None => {
+                image.save_in_background();
+            }
+        }
+    }
+    
+    fn copy_to_buffer(device:&mut Device, cmd:vk::CommandBuffer, image:vk::Image, extent:vk::Extent2D, buffer:vk::Buffer) {
+        
+        let subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1);
+        
+        let region = vk::BufferImageCopy2::builder()
+            .buffer_offset(0)
+            .image_subresource(*subresource)
+            .image_extent(vk::Extent3D::from(extent));
+        
+        let copy_info = vk::CopyImageToBufferInfo2::builder()
+            .src_image(image)
+            .src_image_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .dst_buffer(buffer)
+            .regions(from_ref(&region));
+        
+        unsafe{device.cmd_copy_image_to_buffer2(cmd, &copy_info)};
+        
+        // the graph has no host access, the write is made visible to the mapped memory here
+        let memory_barrier = vk::MemoryBarrier2::builder()
+            .src_stage_mask(vk::PipelineStageFlags2::COPY)
+            .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags2::HOST)
+            .dst_access_mask(vk::AccessFlags2::HOST_READ);
+        
+        let dependency_info = vk::DependencyInfo::builder()
+            .memory_barriers(from_ref(&memory_barrier));
+        
+        unsafe{device.cmd_pipeline_barrier2(cmd, &dependency_info)};
+    }
+    
+    fn layout_of(format:vk::Format) -> Option<CaptureLayout> {
+        match format {
+            vk::Format::R16G16B16A16_SFLOAT => Some(CaptureLayout::Rgba16Float),
+            vk::Format::R8G8B8A8_UNORM => Some(CaptureLayout::Rgba8{bgr:false, srgb:false}),
+            vk::Format::R8G8B8A8_SRGB => Some(CaptureLayout::Rgba8{bgr:false, srgb:true}),
+            vk::Format::B8G8R8A8_UNORM => Some(CaptureLayout::Rgba8{bgr:true, srgb:false}),
+            vk::Format::B8G8R8A8_SRGB => Some(CaptureLayout::Rgba8{bgr:true, srgb:true}),
+            _ => None,
+        }
+    }
+}
+
+impl VkDestructor for PendingCapture {
+    fn destruct(self, mut args:VkDestructorArguments) {
+        let (device, allocator) = args.unwrap_dev_all();
+        self.buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
+    }
+}
@@ -13,6 +13,8 @@ use super::super::Device;
 use super::super::GDescriptorAllocator;
 use super::super::DescriptorLayoutBuilder;
 use super::super::DestructionStack;
+use super::super::FrameQueries;
+use super::PendingCapture;
 
 
 use ash::vk;
@@ -33,6 +35,10 @@ pub struct FrameData {
     pub descriptor_allocator: GDescriptorAllocator,
     #[derivative(Debug="ignore")]
     pub destruction_stack: DestructionStack,
+    #[derivative(Debug="ignore")]
+    pub queries: FrameQueries,
+    #[derivative(Debug="ignore")]
+    pub capture: Option<PendingCapture>,
 }
 
 
@@ -43,7 +49,7 @@ pub struct FramesData (
 
 
 impl FrameData {
-    pub fn create(p_device:&PDevice, device:&mut Device) -> Result<Self, AAError> {
+    pub fn create(p_device:&PDevice, device:&mut Device, index:usize) -> Result<Self, AAError> {
         
         let semaphore_create_info = vk::SemaphoreCreateInfo::builder();
         let fence_create_info = vk::FenceCreateInfo::builder()
@@ -67,6 +73,12 @@ impl FrameData {
         let buffer_vec = unsafe{device.allocate_command_buffers(&create_info)}?;
         let cmd_buffer = buffer_vec[0];
         
+        device.set_object_name(image_available_semaphore, &format!("frame {} image available", index));
+        device.set_object_name(render_finished_semaphore, &format!("frame {} render finished", index));
+        device.set_object_name(inflight_fence, &format!("frame {} in flight", index));
+        device.set_object_name(cmd_pool, &format!("frame {} command pool", index));
+        device.set_object_name(cmd_buffer, &format!("frame {} command buffer", index));
+        
         let mut ds_layout_builder = DescriptorLayoutBuilder::create();
         ds_layout_builder.add_binding(0, vk::DescriptorType::STORAGE_IMAGE, 3);
         ds_layout_builder.add_binding(0, vk::DescriptorType::STORAGE_BUFFER, 3);
@@ -74,8 +86,9 @@ impl FrameData {
         ds_layout_builder.add_binding(0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 4);
         let descriptor_counts = ds_layout_builder.assemble();
         
-        let descriptor_allocator:GDescriptorAllocator = GDescriptorAllocator::create(device, descriptor_counts).unwrap();
+        let descriptor_allocator:GDescriptorAllocator = GDescriptorAllocator::create(device, descriptor_counts)?;
         let destruction_stack = DestructionStack::default();
+        let queries = FrameQueries::create(p_device, device)?;
         
         Ok(Self{
             image_available_semaphore,
@@ -84,7 +97,9 @@ impl FrameData {
             cmd_pool,
             cmd_buffer,
             descriptor_allocator,
-            destruction_stack
+            destruction_stack,
+            queries,
+            capture: None,
         })
     }
     
@@ -105,6 +120,14 @@ impl FrameData {
         &mut self.destruction_stack
     }
     
+    pub(in self) fn get_queries(&mut self) -> &mut FrameQueries {
+        &mut self.queries
+    }
+    
+    pub(in self) fn get_capture(&mut self) -> &mut Option<PendingCapture> {
+        &mut self.capture
+    }
+    
     pub(in self) fn get_references(&mut self) -> (&mut GDescriptorAllocator, &mut DestructionStack) {
         let Self{
             destruction_stack,
@@ -119,9 +142,9 @@ impl FrameData {
 impl FramesData {
     pub fn create(p_device:&PDevice, device:&mut Device) -> Result<Self, AAError> {
         let mut holder:ArrayVec<FrameData, {constants::fif::USIZE}> = ArrayVec::new();
-        for _index in 0..constants::fif::USIZE {
+        for index in 0..constants::fif::USIZE {
             logger::create!("frame_data");
-            let frame_data = FrameData::create(p_device, device)?;
+            let frame_data = FrameData::create(p_device, device, index)?;
             holder.push(frame_data);
         }
         let holder = holder.into_inner().expect(GRANTED);
@@ -144,6 +167,15 @@ impl FramesData {
         self.0[frame].get_destruction_stack()
     }
     
+    pub fn get_queries(&mut self, frame:usize) -> &mut FrameQueries {
+        self.0[frame].get_queries()
+    }
+    
+    // the readback recorded the last time this frame slot was used
+    pub fn get_capture(&mut self, frame:usize) -> &mut Option<PendingCapture> {
+        self.0[frame].get_capture()
+    }
+    
     pub fn get_references(&mut self, frame: usize) -> (&mut GDescriptorAllocator, &mut DestructionStack) {
         self.0[frame].get_references()
     }
@@ -163,6 +195,10 @@ impl VkDestructor for FrameData {
         unsafe{device.destroy_semaphore(self.render_finished_semaphore, None)};
         unsafe{device.destroy_fence(self.inflight_fence, None)};
         self.descriptor_allocator.destruct(VkDestructorArguments::Dev(device));
+        self.queries.destruct(VkDestructorArguments::Dev(device));
+        if let Some(capture) = self.capture {
+            capture.destruct(VkDestructorArguments::DevAll(device, allocator));
+        }
         
         self.destruction_stack.dispatch(device, allocator);
     }
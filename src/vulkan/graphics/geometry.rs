@@ -0,0 +1,309 @@
+use crate::AAError;
+use crate::logger;
+use crate::errors::messages::VK_CAST;
+use crate::errors::messages::MODEL_DENSITY;
+
+use super::Vertex;
+
+use super::VkDestructor;
+use super::VkDestructorArguments;
+use super::super::VkDestructorType;
+use super::super::VkDynamicDestructor;
+use super::super::Device;
+use super::super::Allocator;
+use super::super::UploadManager;
+use super::super::Buffer;
+use super::super::memory;
+
+use std::mem::size_of;
+use std::ops::Range;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use ash::vk;
+
+// capacities are in elements, not bytes
+pub const VERTEX_CAPACITY:u32 = 1 << 20;
+pub const INDEX_CAPACITY:u32 = 1 << 22;
+
+// first fit allocator over a range of elements, the free ranges are kept sorted and merged
+#[derive(Debug)]
+pub struct FreeList {
+    capacity: u32,
+    free: Vec<Range<u32>>,
+}
+
+// place of a mesh inside the geometry buffers
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GeometryAllocation {
+    pub first_vertex: u32,
+    pub vertex_count: u32,
+    pub first_index: u32,
+    pub index_count: u32,
+    // first_index is counted in elements of the buffer of this type
+    pub index_type: vk::IndexType,
+}
+
+// every mesh is sub allocated from these, so each index buffer is bound once per frame,
+// meshes with few enough vertices take their indices from the 16 bit one
+#[derive(Debug)]
+pub struct GeometryBuffers {
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    pub short_index_buffer: Buffer,
+    pub vertex_buffer_address: vk::DeviceAddress,
+    vertices: FreeList,
+    indices: FreeList,
+    short_indices: FreeList,
+    // filled by the destruction stack once the frames that read the ranges are done
+    released: Rc<RefCell<Vec<GeometryAllocation>>>,
+}
+
+
+impl FreeList {
+    pub fn new(capacity:u32) -> Self {
+        Self{
+            capacity,
+            free: std::iter::once(0..capacity).collect(),
+        }
+    }
+    
+    pub fn allocate(&mut self, count:u32) -> Option<u32> {
+        let position = self.free.iter().position(|range|range.end - range.start >= count)?;
+        let range = &mut self.free[position];
+        let start = range.start;
+        range.start += count;
+        if range.start == range.end {
+            self.free.remove(position);
+        }
+        Some(start)
+    }
+    
+    pub fn free(&mut self, start:u32, count:u32) {
+        let end = start + count;
+        let position = self.free.partition_point(|range|range.start < start);
+        let merge_previous = position > 0 && self.free[position-1].end == start;
+        let merge_next = position < self.free.len() && self.free[position].start == end;
+        match (merge_previous, merge_next) {
+            (true, true) => {
+                self.free[position-1].end = self.free[position].end;
+                self.free.remove(position);
+            }
+            (true, false) => {
+                self.free[position-1].end = end;
+            }
+            (false, true) => {
+                self.free[position].start = start;
+            }
+            (false, false) => {
+                self.free.insert(position, start..end);
+            }
+        }
+    }
+    
+    pub fn used(&self) -> u32 {
+        self.capacity - self.free.iter().map(|range|range.end - range.start).sum::<u32>()
+    }
+}
+
+
+impl GeometryBuffers {
+    pub fn create(device:&mut Device, allocator:&mut Allocator, vertex_capacity:u32, index_capacity:u32) -> Result<Self, AAError> {
+        logger::create!("geometry_buffers");
+        
+        let vertices_size = u64::from(vertex_capacity) * u64::try_from(size_of::<Vertex>()).expect(VK_CAST);
+        let indices_size = u64::from(index_capacity) * u64::try_from(size_of::<u32>()).expect(VK_CAST);
+        let short_indices_size = u64::from(index_capacity) * u64::try_from(size_of::<u16>()).expect(VK_CAST);
+        
+        use vk::BufferUsageFlags as buf;
+        let vertex_buffer = Buffer::create(device, allocator, Some("geometry vertex buffer"), vertices_size, buf::STORAGE_BUFFER|buf::SHADER_DEVICE_ADDRESS|buf::TRANSFER_DST, memory::GpuOnly)?;
+        let vertex_buffer_address = vertex_buffer.get_device_address(device);
+        let index_buffer = Buffer::create(device, allocator, Some("geometry index buffer"), indices_size, buf::INDEX_BUFFER|buf::TRANSFER_DST, memory::GpuOnly)?;
+        let short_index_buffer = Buffer::create(device, allocator, Some("geometry 16 bit index buffer"), short_indices_size, buf::INDEX_BUFFER|buf::TRANSFER_DST, memory::GpuOnly)?;
+        
+        Ok(Self{
+            vertex_buffer,
+            index_buffer,
+            short_index_buffer,
+            vertex_buffer_address,
+            vertices: FreeList::new(vertex_capacity),
+            indices: FreeList::new(index_capacity),
+            short_indices: FreeList::new(index_capacity),
+            released: Rc::new(RefCell::new(Vec::new())),
+        })
+    }
+    
+//----
+    // the copies are recorded in the uploader, the caller decides when to flush them
+    pub fn allocate(
+        &mut self,
+        device: &Device,
+        uploader: &mut UploadManager,
+        indices: &[u32],
+        vertices: &[Vertex],
+    ) -> Result<GeometryAllocation, AAError> {
+        if indices.is_empty() || vertices.is_empty() {
+            return Err(AAError::EmptyMesh);
+        }
+        let vertex_count = u32::try_from(vertices.len()).expect(MODEL_DENSITY);
+        let index_count = u32::try_from(indices.len()).expect(MODEL_DENSITY);
+        
+        self.reclaim();
+        let first_vertex = self.vertices.allocate(vertex_count).ok_or(AAError::GeometryBufferFull)?;
+        // indices are relative to the mesh, so 16 bits are enough for up to 65536 vertices
+        let short = match vertex_count <= 1 << 16 {
+            true => self.short_indices.allocate(index_count),
+            false => None,
+        };
+        let (first_index, index_type) = match short.map(|first_index|(first_index, vk::IndexType::UINT16)) {
+            Some(holder) => {holder}
+            None => match self.indices.allocate(index_count) {
+                Some(first_index) => {(first_index, vk::IndexType::UINT32)}
+                None => {
+                    self.vertices.free(first_vertex, vertex_count);
+                    return Err(AAError::GeometryBufferFull);
+                }
+            }
+        };
+        let allocation = GeometryAllocation{first_vertex, vertex_count, first_index, index_count, index_type};
+        
+        let vertex_offset = u64::from(first_vertex) * u64::try_from(size_of::<Vertex>()).expect(VK_CAST);
+        let result = uploader.upload_buffer(device, vertices, &self.vertex_buffer, vertex_offset).and_then(|_|{
+            match index_type {
+                vk::IndexType::UINT16 => {
+                    let short_indices:Vec<u16> = indices.iter().map(|&index|u16::try_from(index).expect(MODEL_DENSITY)).collect();
+                    let index_offset = u64::from(first_index) * u64::try_from(size_of::<u16>()).expect(VK_CAST);
+                    uploader.upload_buffer(device, &short_indices, &self.short_index_buffer, index_offset)
+                }
+                _ => {
+                    let index_offset = u64::from(first_index) * u64::try_from(size_of::<u32>()).expect(VK_CAST);
+                    uploader.upload_buffer(device, indices, &self.index_buffer, index_offset)
+                }
+            }
+        });
+        if let Err(error) = result {
+            self.free(allocation);
+            return Err(error);
+        }
+        
+        logger::various_log!("geometry_buffers",
+            (logger::Trace, "allocated {} vertices and {} {:?} indices, used {}/{} {}/{} {}/{}", vertex_count, index_count, index_type, self.vertices.used(), self.vertices.capacity, self.indices.used(), self.indices.capacity, self.short_indices.used(), self.short_indices.capacity)
+        );
+        Ok(allocation)
+    }
+    
+//----
+    // the range can be reused right away, the caller makes sure no frame in flight still reads it
+    pub fn free(&mut self, allocation:GeometryAllocation) {
+        self.vertices.free(allocation.first_vertex, allocation.vertex_count);
+        match allocation.index_type {
+            vk::IndexType::UINT16 => self.short_indices.free(allocation.first_index, allocation.index_count),
+            _ => self.indices.free(allocation.first_index, allocation.index_count),
+        }
+    }
+    
+    pub fn index_buffer(&self, index_type:vk::IndexType) -> vk::Buffer {
+        match index_type {
+            vk::IndexType::UINT16 => self.short_index_buffer.underlying(),
+            _ => self.index_buffer.underlying(),
+        }
+    }
+    
+//----
+    // for ranges frames in flight may still read, the returned destructor goes to the frame destruction stack
+    pub fn defered_free(&self, allocation:GeometryAllocation) -> VkDynamicDestructor {
+        let released = self.released.clone();
+        let callback = Box::new(move |mut args:VkDestructorArguments|{
+            args.unwrap_none();
+            released.borrow_mut().push(allocation);
+        });
+        (callback, VkDestructorType::None)
+    }
+    
+    fn reclaim(&mut self) {
+        let released = std::mem::take(&mut *self.released.borrow_mut());
+        for allocation in released.into_iter() {
+            self.free(allocation);
+        }
+    }
+}
+
+impl VkDestructor for GeometryBuffers {
+    fn destruct(self, mut args:VkDestructorArguments) {
+        logger::destruct!("geometry_buffers");
+        let (device, allocator) = args.unwrap_dev_all();
+        self.vertex_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
+        self.index_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
+        self.short_index_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::FreeList;
+    
+    fn ranges(list:&FreeList) -> Vec<(u32, u32)> {
+        list.free.iter().map(|range|(range.start, range.end)).collect()
+    }
+    
+    #[test]
+    fn allocates_first_fit() {
+        let mut list = FreeList::new(10);
+        assert_eq!(list.allocate(4), Some(0));
+        assert_eq!(list.allocate(4), Some(4));
+        assert_eq!(list.allocate(4), None);
+        assert_eq!(list.allocate(2), Some(8));
+        assert_eq!(list.used(), 10);
+        assert_eq!(list.allocate(1), None);
+    }
+    
+    #[test]
+    fn reuses_freed_ranges() {
+        let mut list = FreeList::new(10);
+        let first = list.allocate(3).unwrap();
+        let _second = list.allocate(3).unwrap();
+        list.free(first, 3);
+        assert_eq!(list.used(), 3);
+        assert_eq!(list.allocate(2), Some(0));
+        assert_eq!(list.allocate(2), Some(6));
+        assert_eq!(list.allocate(1), Some(2));
+    }
+    
+    #[test]
+    fn merges_neighbours() {
+        let mut list = FreeList::new(9);
+        let a = list.allocate(3).unwrap();
+        let b = list.allocate(3).unwrap();
+        let c = list.allocate(3).unwrap();
+        
+        list.free(a, 3);
+        list.free(c, 3);
+        assert_eq!(ranges(&list), vec![(0, 3), (6, 9)]);
+        
+        // joins both sides into a single range
+        list.free(b, 3);
+        assert_eq!(ranges(&list), vec![(0, 9)]);
+        assert_eq!(list.used(), 0);
+        assert_eq!(list.allocate(9), Some(0));
+    }
+    
+    #[test]
+    fn merges_previous_and_next_only() {
+        let mut list = FreeList::new(12);
+        let a = list.allocate(3).unwrap();
+        let b = list.allocate(3).unwrap();
+        let c = list.allocate(3).unwrap();
+        let _d = list.allocate(3).unwrap();
+        
+        list.free(a, 3);
+        list.free(b, 3);
+        assert_eq!(ranges(&list), vec![(0, 6)]);
+        
+        list.allocate(6).unwrap();
+        list.free(c, 3);
+        list.free(b, 3);
+        assert_eq!(ranges(&list), vec![(3, 9)]);
+    }
+}
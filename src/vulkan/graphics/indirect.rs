@@ -0,0 +1,166 @@
+use crate::AAError;
+use crate::constants;
+use crate::logger;
+use crate::errors::messages::VK_CAST;
+use crate::errors::messages::COMPILETIME_ASSERT;
+
+use super::MaterialInstance;
+
+use super::VkDestructor;
+use super::VkDeferedDestructor;
+use super::VkDestructorArguments;
+use super::super::DestructionStack;
+use super::super::Device;
+use super::super::Allocator;
+use super::super::Buffer;
+use super::super::CPipeline;
+use super::super::memory;
+
+use std::mem::size_of;
+
+use ash::vk;
+use nalgebra as na;
+use na::Matrix4;
+
+const CULL_GROUP_SIZE:u32 = 64;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct CullPushConstants {
+    view_projection: Matrix4<f32>,
+    instance_buffer: vk::DeviceAddress,
+    draw_buffer: vk::DeviceAddress,
+    count_buffer: vk::DeviceAddress,
+    instance_count: u32,
+    short_instances: u32,
+}
+
+pub struct IndirectDrawer {
+    cull_pipeline: CPipeline,
+}
+
+// buffers of a single frame, they are handed to the frame destruction stack as soon as they are created,
+// the commands of the 16 bit indexed instances come first and each index type has its own count
+#[derive(Debug, Clone, Copy)]
+pub struct IndirectBatch {
+    pub draw_buffer: vk::Buffer,
+    pub count_buffer: vk::Buffer,
+    pub instance_count: u32,
+    pub short_instances: u32,
+    push_constants: CullPushConstants,
+}
+
+
+// only the objects drawn with the default pipeline go through the indirect path,
+// a draw count call can't switch pipelines
+pub fn is_indirect(material:&MaterialInstance, default_material:&MaterialInstance) -> bool {
+    material.pipeline.underlying() == default_material.pipeline.underlying()
+}
+
+
+impl IndirectDrawer {
+    pub fn create(device:&mut Device) -> Result<Self, AAError> {
+        logger::create!("indirect_drawer");
+        let cull_pipeline = CPipeline::create_raw(device, &[], CullPushConstants::size_u32(), constants::comp::CULL_SHADER)?;
+        Ok(Self{
+            cull_pipeline,
+        })
+    }
+    
+//----
+    // culls the first instance_count instances of the frame instance buffer, each visible one gets its own command,
+    // the first short_instances of them draw with 16 bit indices
+    pub fn prepare(
+        device: &mut Device,
+        allocator: &mut Allocator,
+        destruction_stack: &mut DestructionStack,
+        instance_buffer: vk::DeviceAddress,
+        (instance_count, short_instances): (u32, u32),
+        view_projection: &Matrix4<f32>,
+    ) -> Result<Option<IndirectBatch>, AAError> {
+        if instance_count == 0 {
+            return Ok(None);
+        }
+        
+        use vk::BufferUsageFlags as buf;
+        let draws_size = u64::from(instance_count) * u64::try_from(size_of::<vk::DrawIndexedIndirectCommand>()).expect(VK_CAST);
+        let count_size = u64::try_from(size_of::<[u32; 2]>()).expect(VK_CAST);
+        
+        let mut draw_holder = Buffer::create(device, allocator, Some("indirect draws"), draws_size, buf::STORAGE_BUFFER|buf::INDIRECT_BUFFER|buf::SHADER_DEVICE_ADDRESS, memory::GpuOnly)?;
+        let draw_buffer = draw_holder.underlying();
+        let draw_address = draw_holder.get_device_address(device);
+        destruction_stack.push(draw_holder.defered_destruct());
+        
+        let mut count_holder = Buffer::create(device, allocator, Some("indirect count"), count_size, buf::STORAGE_BUFFER|buf::INDIRECT_BUFFER|buf::SHADER_DEVICE_ADDRESS|buf::TRANSFER_DST, memory::GpuOnly)?;
+        let count_buffer = count_holder.underlying();
+        let count_address = count_holder.get_device_address(device);
+        destruction_stack.push(count_holder.defered_destruct());
+        
+        Ok(Some(IndirectBatch{
+            draw_buffer,
+            count_buffer,
+            instance_count,
+            short_instances,
+            push_constants: CullPushConstants{
+                view_projection: *view_projection,
+                instance_buffer,
+                draw_buffer: draw_address,
+                count_buffer: count_address,
+                instance_count,
+                short_instances,
+            },
+        }))
+    }
+    
+//----
+    // the count buffer has to be cleared before, the cull pass only appends
+    pub fn cull(&self, device:&mut Device, cmd:vk::CommandBuffer, batch:&IndirectBatch) {
+        unsafe{device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, self.cull_pipeline.pipeline)};
+        
+        let push_constants_slice = unsafe{crate::any_as_u8_slice(&batch.push_constants)};
+        unsafe{device.cmd_push_constants(cmd, self.cull_pipeline.layout, vk::ShaderStageFlags::COMPUTE, 0, push_constants_slice)};
+        
+        unsafe{device.cmd_dispatch(cmd, batch.instance_count.div_ceil(CULL_GROUP_SIZE), 1, 1)};
+    }
+}
+
+impl VkDestructor for IndirectDrawer {
+    fn destruct(self, mut args:VkDestructorArguments) {
+        logger::destruct!("indirect_drawer");
+        let device = args.unwrap_dev();
+        self.cull_pipeline.destruct(VkDestructorArguments::Dev(device));
+    }
+}
+
+
+impl IndirectBatch {
+    pub fn clear_count(&self, device:&mut Device, cmd:vk::CommandBuffer) {
+        unsafe{device.cmd_fill_buffer(cmd, self.count_buffer, 0, vk::WHOLE_SIZE, 0)};
+    }
+    
+    // the index buffer of the type and the pipeline are expected to be bound
+    pub fn draw(&self, device:&mut Device, cmd:vk::CommandBuffer, index_type:vk::IndexType) {
+        let stride = u32::try_from(size_of::<vk::DrawIndexedIndirectCommand>()).expect(VK_CAST);
+        let (first_draw, max_draws, count_offset) = match index_type {
+            vk::IndexType::UINT16 => (0, self.short_instances, 0),
+            _ => (self.short_instances, self.instance_count - self.short_instances, size_of::<u32>()),
+        };
+        if max_draws == 0 {
+            return;
+        }
+        let draw_offset = u64::from(first_draw) * u64::from(stride);
+        let count_offset = u64::try_from(count_offset).expect(VK_CAST);
+        unsafe{device.cmd_draw_indexed_indirect_count(cmd, self.draw_buffer, draw_offset, self.count_buffer, count_offset, max_draws, stride)};
+    }
+}
+
+
+const _:u32 = CullPushConstants::size_u32();
+impl CullPushConstants {
+    pub const fn size_u32() -> u32 {
+        if size_of::<Self>() > u32::MAX as usize {
+            panic!("{}", COMPILETIME_ASSERT);
+        }
+        size_of::<Self>() as u32
+    }
+}
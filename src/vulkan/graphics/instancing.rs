@@ -0,0 +1,165 @@
+use crate::AAError;
+use crate::errors::messages::VK_CAST;
+use crate::errors::messages::CPU_ACCESIBLE;
+
+use super::DrawContext;
+use super::RenderObject;
+use super::MaterialInstance;
+use super::AssetPool;
+use super::indirect::is_indirect;
+
+use super::VkDeferedDestructor;
+use super::super::DestructionStack;
+use super::super::Device;
+use super::super::Allocator;
+use super::super::Buffer;
+use super::super::memory;
+
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use ash::vk;
+use nalgebra as na;
+use derivative::Derivative;
+use na::Matrix4;
+use na::Vector4;
+
+// per instance record, read by the vertex shader with gl_InstanceIndex and by the cull shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct GpuInstance {
+    transform: Matrix4<f32>,
+    tint: Vector4<f32>,
+    center: Vector4<f32>,
+    extents: Vector4<f32>,
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: i32,
+    material_index: u32,
+}
+
+// a surface drawn once per instance in [first_instance, first_instance+instance_count)
+#[derive(Derivative, Clone)]
+#[derivative(Debug)]
+pub struct InstancedDraw {
+    pub index_count: u32,
+    pub first_index: u32,
+    pub vertex_offset: i32,
+    pub index_type: vk::IndexType,
+    pub first_instance: u32,
+    pub instance_count: u32,
+    #[derivative(Debug="ignore")]
+    pub material: MaterialInstance,
+}
+
+#[derive(Debug, Default)]
+pub struct InstancedScene {
+    pub instances: Vec<GpuInstance>,
+    pub draws: Vec<InstancedDraw>,
+    // instances of the draws the indirect path can take, they are always the first ones
+    pub indirect_instances: u32,
+    // the first indirect instances use the 16 bit index buffer
+    pub short_indirect_instances: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct DrawKey {
+    pipeline: vk::Pipeline,
+    material_index: u32,
+    first_index: u32,
+    index_count: u32,
+    vertex_offset: i32,
+    index_type: vk::IndexType,
+}
+
+
+impl DrawContext {
+    // objects sharing surface and material become a single instanced draw,
+    // a material that was unloaded in the meantime falls back to the default one
+    pub fn merge(&self, default_material:&MaterialInstance, materials:&AssetPool<MaterialInstance>) -> InstancedScene {
+        let mut groups:Vec<(&MaterialInstance, Vec<&RenderObject>)> = Vec::new();
+        let mut lookup:HashMap<DrawKey, usize> = HashMap::new();
+        
+        for render_object in self.iter() {
+            let material = render_object.material.and_then(|handle|materials.get(handle)).unwrap_or(default_material);
+            let key = DrawKey{
+                pipeline: material.pipeline.underlying(),
+                material_index: material.material_index,
+                first_index: render_object.first_index,
+                index_count: render_object.index_count,
+                vertex_offset: render_object.vertex_offset,
+                index_type: render_object.index_type,
+            };
+            let group = *lookup.entry(key).or_insert_with(||{
+                groups.push((material, Vec::new()));
+                groups.len() - 1
+            });
+            groups[group].1.push(render_object);
+        }
+        
+        // stable, so the submission order inside each path is kept, 16 bit indices go first in both
+        groups.sort_by_key(|(material, objects)|(!is_indirect(material, default_material), objects[0].index_type != vk::IndexType::UINT16));
+        
+        let mut scene = InstancedScene::default();
+        for (material, objects) in groups.into_iter() {
+            let first_instance = u32::try_from(scene.instances.len()).expect(VK_CAST);
+            let instance_count = u32::try_from(objects.len()).expect(VK_CAST);
+            let first = objects[0];
+            
+            scene.instances.extend(objects.iter().map(|render_object|{
+                GpuInstance{
+                    transform: render_object.transform,
+                    tint: render_object.tint,
+                    center: render_object.bounds.center.push(1.0),
+                    extents: render_object.bounds.extents.push(0.0),
+                    first_index: render_object.first_index,
+                    index_count: render_object.index_count,
+                    vertex_offset: render_object.vertex_offset,
+                    material_index: material.material_index,
+                }
+            }));
+            if is_indirect(material, default_material) {
+                scene.indirect_instances += instance_count;
+                if first.index_type == vk::IndexType::UINT16 {
+                    scene.short_indirect_instances += instance_count;
+                }
+            }
+            scene.draws.push(InstancedDraw{
+                index_count: first.index_count,
+                first_index: first.first_index,
+                vertex_offset: first.vertex_offset,
+                index_type: first.index_type,
+                first_instance,
+                instance_count,
+                material: material.clone(),
+            });
+        }
+        scene
+    }
+}
+
+
+impl InstancedScene {
+    // the buffer only lives for the frame, None when there is nothing to draw
+    pub fn upload(
+        &self,
+        device: &mut Device,
+        allocator: &mut Allocator,
+        destruction_stack: &mut DestructionStack,
+    ) -> Result<Option<vk::DeviceAddress>, AAError> {
+        if self.instances.is_empty() {
+            return Ok(None);
+        }
+        let size = u64::try_from(self.instances.len() * size_of::<GpuInstance>()).expect(VK_CAST);
+        
+        use vk::BufferUsageFlags as buf;
+        let mut holder = Buffer::create(device, allocator, Some("instance buffer"), size, buf::STORAGE_BUFFER|buf::SHADER_DEVICE_ADDRESS, memory::CpuToGpu)?;
+        {
+            let mut align = holder.get_align::<GpuInstance>(0, size).expect(CPU_ACCESIBLE);
+            align.copy_from_slice(&self.instances);
+        }
+        let address = holder.get_device_address(device);
+        destruction_stack.push(holder.defered_destruct());
+        Ok(Some(address))
+    }
+}
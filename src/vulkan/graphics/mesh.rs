@@ -1,289 +1,461 @@
 use crate::AAError;
 use crate::logger;
-use crate::errors::messages::VK_CAST;
-use crate::errors::messages::GRANTED;
 use crate::errors::messages::MODEL_DENSITY;
 
 use super::Vertex;
 use super::VkGeoSurface;
+use super::GeometryBuffers;
+use super::GeometryAllocation;
+use super::Bounds;
 
-use super::VkDestructor;
-use super::VkDestructorArguments;
 use super::super::Device;
-use super::super::Allocator;
-use super::super::CommandControl;
-use super::super::Buffer;
-use super::super::memory;
+use super::super::UploadManager;
 
-use std::mem::size_of_val;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::path::Path;
+use std::path::PathBuf;
 use std::fs; 
 use std::io;
-use std::rc::Rc;
 
 use arrayvec::ArrayString;
-use ash::vk;
 use nalgebra as na;
+use na::Vector2;
 use na::Vector3;
 use na::Vector4;
 
 
-#[derive(Debug)]
-pub struct VkMeshBuffers {
-    pub index_buffer: Buffer,
-    pub vertex_buffer: Buffer,
-    pub vertex_buffer_address: vk::DeviceAddress,
-}
-
 #[derive(Debug, Default)]
 pub struct VkMeshAsset {
     pub name: ArrayString<64>,
     pub surfaces: Vec<VkGeoSurface>,
-    pub meshes: Vec<VkMeshBuffers>,
+    pub geometry: GeometryAllocation,
+    pub bounds: Bounds,
 }
 
 
-pub type VkMeshAssets = Vec<Rc<VkMeshAsset>>;
-
-pub fn load_gltf<P: AsRef<Path>>(
-    device: &mut Device,
-    allocator: &mut Allocator,
-    command_control: &mut CommandControl,
-    path: P,
-) -> Result<VkMeshAssets, AAError> {
+// cpu side of a mesh, produced on the streaming threads and uploaded on the render thread
+#[derive(Debug, Default)]
+pub struct CpuMesh {
+    pub name: ArrayString<64>,
+    pub surfaces: Vec<CpuSurface>,
+    pub indices: Vec<u32>,
+    pub vertices: Vec<Vertex>,
+}
     
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CpuSurface {
+    pub start_index: u32,
+    pub count: u32,
+    // index in the materials of the model, None keeps the default material
+    pub material: Option<usize>,
+}
+
+// what an upload reads, borrowed from a parsed mesh or from a mapped cache
+#[derive(Debug, Clone, Copy)]
+pub struct MeshView<'a> {
+    pub name: ArrayString<64>,
+    pub surfaces: &'a [CpuSurface],
+    pub indices: &'a [u32],
+    pub vertices: &'a [Vertex],
+    pub bounds: Bounds,
+}
+
+// material as read from the file, it becomes a MaterialInstance once its texture is decoded
+#[derive(Debug, Clone, Copy)]
+pub struct CpuMaterial {
+    pub color_factors: Vector4<f32>,
+    pub metal_rough_factors: Vector4<f32>,
+    // index in the textures of the model
+    pub base_color: Option<usize>,
+}
+
+// document plus every buffer it references, embedded in the glb, external .bin or data uri
+pub struct GltfFile {
+    pub document: gltf::Document,
+    pub buffers: Vec<gltf::buffer::Data>,
+    // directory the relative uris are taken from
+    pub base: PathBuf,
+}
+
+pub fn open_gltf<P: AsRef<Path>>(path: P) -> Result<GltfFile, AAError> {
+    let path = path.as_ref();
     let file = fs::File::open(path)?;
     let reader = io::BufReader::new(file);
-    let gltf = gltf::Gltf::from_reader(reader)?;
-    let mut holder = VkMeshAssets::default();
+    let gltf::Gltf{document, blob} = gltf::Gltf::from_reader(reader)?;
+    let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let buffers = gltf::import_buffers(&document, Some(&base), blob)?;
+    Ok(GltfFile{document, buffers, base})
+}
+
+impl GltfFile {
+    // files besides the document the meshes are read from, embedded and data uri buffers have none
+    pub fn buffer_files(&self) -> Vec<PathBuf> {
+        self.document.buffers().filter_map(|buffer|{
+            match buffer.source() {
+                gltf::buffer::Source::Uri(uri) if !uri.starts_with("data:") => Some(self.base.join(uri)),
+                _ => None,
+            }
+        }).collect()
+    }
     
-    let mut indices_vec:Vec<u32> = Vec::new();
-    let mut vertices_vec:Vec<Vertex> = Vec::new();
+    // encoded bytes of the image, still to be decoded
+    pub fn image_bytes(&self, index:usize) -> Result<Cow<'_, [u8]>, AAError> {
+        let image = self.document.images().nth(index).ok_or(AAError::GltfImage(index))?;
+        match image.source() {
+            gltf::image::Source::View{view, ..} => {
+                let buffer = self.buffers.get(view.buffer().index()).ok_or(AAError::GltfImage(index))?;
+                let range = view.offset()..view.offset()+view.length();
+                Ok(Cow::Borrowed(buffer.get(range).ok_or(AAError::GltfImage(index))?))
+            }
+            gltf::image::Source::Uri{uri, ..} => {
+                match uri.strip_prefix("data:") {
+                    Some(data) => {
+                        let (_, encoded) = data.split_once(";base64,").ok_or(AAError::GltfImage(index))?;
+                        Ok(Cow::Owned(base64::decode(encoded)?))
+                    }
+                    None => Ok(Cow::Owned(fs::read(self.base.join(uri))?)),
+                }
+            }
+        }
+    }
+}
+
+// the texture indices are gltf image indices
+pub fn read_gltf_materials(gltf:&GltfFile) -> Vec<CpuMaterial> {
+    gltf.document.materials().map(|material|{
+        let pbr = material.pbr_metallic_roughness();
+        CpuMaterial{
+            color_factors: Vector4::from(pbr.base_color_factor()),
+            metal_rough_factors: Vector4::new(pbr.metallic_factor(), pbr.roughness_factor(), 0.0, 0.0),
+            base_color: pbr.base_color_texture().map(|info|info.texture().source().index()),
+        }
+    }).collect()
+}
+
+// every primitive becomes a surface, their indices are offset into the shared vertex list
+pub fn read_gltf_meshes(gltf:&GltfFile) -> Result<Vec<CpuMesh>, AAError> {
+    
+    let mut holder = Vec::new();
     
-    let meshes = gltf.meshes();
+    let meshes = gltf.document.meshes();
     
     logger::various_log!("mesh",
         (logger::Trace, "amount of meshes {}", meshes.len())
     );
     
-    //println!("{}", meshes.len());
     for mesh in meshes {
-        let mut metadata_holder = VkMeshAsset::default();
-        indices_vec.clear();
-        vertices_vec.clear();
-        
-        
-        //println!("{:?}", mesh.name());
-        match mesh.name() {
-            Some(name) => {
-                logger::various_log!("mesh",
-                    (logger::Trace, "mesh name {}", name)
-                );
-                metadata_holder.name.push_str(name);
-            }
-            None => {
-                logger::various_log!("mesh",
-                    (logger::Trace, "no mesh name")
-                );
-                metadata_holder.name.push_str("empty");
-            }
-        }
-        
-        
-        let primitives = mesh.primitives();
+        // errors need something to point at even for unnamed meshes
+        let label = match mesh.name() {
+            Some(name) => String::from(name),
+            None => format!("#{}", mesh.index()),
+        };
+        let mut metadata_holder = CpuMesh{
+            name: truncated_name(mesh.name().unwrap_or("empty")),
+            ..CpuMesh::default()
+        };
         
         logger::various_log!("mesh",
-            (logger::Trace, "primitives_count {}", &primitives.len())
+            (logger::Trace, "mesh {} primitives_count {}", label, mesh.primitives().len())
         );
-        //println!("{}", &primitives.len());
-        for primitive in primitives {
-            
-            let mut surface = VkGeoSurface::default();
-            surface.start_index = u32::try_from(indices_vec.len()).expect(MODEL_DENSITY);
-            let reader = primitive.reader(|_primitive|{Some(&gltf.blob.as_ref().unwrap()[..])});
+        
+        for primitive in mesh.primitives() {
+            let (indices, vertices) = read_gltf_primitive(gltf, &label, &primitive)?;
             
-            let indices = reader.read_indices().unwrap();
-            //println!("indices count");
-            use gltf::mesh::util::ReadIndices;
-            match indices {
-                ReadIndices::U8(indices) => {
-                    logger::various_log!("mesh",
-                        (logger::Trace, "indices count u8 {}", indices.len())
-                    );
-                    for index in indices {
-                        indices_vec.push(u32::from(index));
-                    }
-                }
-                ReadIndices::U16(indices) => {
-                    logger::various_log!("mesh",
-                        (logger::Trace, "indices count u16 {}", indices.len())
-                    );
-                    for index in indices {
-                        indices_vec.push(u32::from(index));
-                    }
-                }
-                ReadIndices::U32(indices) => {
-                    logger::various_log!("mesh",
-                        (logger::Trace, "indices count u32 {}", indices.len())
-                    );
-                    for index in indices {
-                        indices_vec.push(u32::from(index));
-                    }
-                }
+            let base_vertex = u32::try_from(metadata_holder.vertices.len()).expect(MODEL_DENSITY);
+            metadata_holder.surfaces.push(CpuSurface{
+                start_index: u32::try_from(metadata_holder.indices.len()).expect(MODEL_DENSITY),
+                count: u32::try_from(indices.len()).expect(MODEL_DENSITY),
+                material: primitive.material().index(),
+            });
+            metadata_holder.indices.extend(indices.into_iter().map(|index|index+base_vertex));
+            metadata_holder.vertices.extend(vertices);
+        }
+        holder.push(metadata_holder);
+    }
+    
+    Ok(holder)
+    
+}
+
+// reads a primitive as a triangle list, missing indices, normals and uvs are generated
+fn read_gltf_primitive(gltf:&GltfFile, mesh:&str, primitive:&gltf::Primitive) -> Result<(Vec<u32>, Vec<Vertex>), AAError> {
+    let reader = primitive.reader(|buffer|gltf.buffers.get(buffer.index()).map(|data|&data[..]));
+    
+    let positions = reader.read_positions().ok_or_else(||{
+        AAError::GltfMissingPositions{mesh:String::from(mesh), primitive:primitive.index()}
+    })?;
+    let mut vertices:Vec<Vertex> = positions.map(|position|{
+        Vertex{
+            position: Vector3::from(position),
+            ..Vertex::default()
+        }
+    }).collect();
+    
+    let indexed = reader.read_indices().is_some();
+    let indices:Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..u32::try_from(vertices.len()).expect(MODEL_DENSITY)).collect(),
+    };
+    if let Some(&index) = indices.iter().find(|&&index|usize::try_from(index).expect(MODEL_DENSITY) >= vertices.len()) {
+        return Err(AAError::GltfIndexOutOfRange{mesh:String::from(mesh), primitive:primitive.index(), index, vertices:vertices.len()});
+    }
+    let mut indices = triangle_list(primitive.mode(), indices).ok_or_else(||{
+        AAError::GltfUnsupportedMode{mesh:String::from(mesh), primitive:primitive.index(), mode:primitive.mode()}
+    })?;
+    
+    // zero uvs when missing, Vertex::default already has them, normalized integers are scaled to 0..1
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+            vertex.uv_x = uv[0];
+            vertex.uv_y = uv[1];
+        }
+    }
+    if let Some(uvs) = reader.read_tex_coords(1) {
+        for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+            vertex.uv1 = Vector2::from(uv);
+        }
+    }
+    
+    // white leaves the material color as it is
+    match reader.read_colors(0) {
+        Some(colors) => {
+            for (vertex, color) in vertices.iter_mut().zip(colors.into_rgba_f32()) {
+                vertex.color = Vector4::from(color);
             }
-            
-            let positions = reader.read_positions().unwrap();
-            logger::various_log!("mesh",
-                (logger::Trace, "vertex count {}", positions.len())
-            );
-            for (index, pos) in positions.enumerate() {
-                let mut vertex_holder = Vertex::default();
-                vertex_holder.position = Vector3::from(pos);
-                vertices_vec.push(vertex_holder);
-                
+        }
+        None => {
+            for vertex in vertices.iter_mut() {
+                vertex.color = Vector4::new(1.0, 1.0, 1.0, 1.0);
             }
-            
-            
-            let texture_coordenates = reader.read_tex_coords(0u32).unwrap();
-            match texture_coordenates {
-                gltf::mesh::util::ReadTexCoords::F32(hola) => {
-                    for (index, coords) in hola.enumerate() {
-                        vertices_vec[index].uv_x = coords[0];
-                        vertices_vec[index].uv_y = coords[1];
-                    }
-                }
-                _ => {}
+        }
+    }
+    
+    // tangents are only meaningful next to the normals they were authored with
+    match (reader.read_normals(), reader.read_tangents()) {
+        (Some(normals), Some(tangents)) => {
+            for ((vertex, normal), tangent) in vertices.iter_mut().zip(normals).zip(tangents) {
+                vertex.normal = Vector3::from(normal);
+                vertex.tangent = Vector4::from(tangent);
             }
-            
-            
-            
-            let normals = reader.read_normals().unwrap();
-            logger::various_log!("mesh",
-                (logger::Trace, "normals count {}", normals.len())
-            );
-            for (index, norm) in normals.enumerate() {
-                vertices_vec[index].normal = Vector3::from(norm);
-                vertices_vec[index].color = Vector4::new(norm[0], norm[1], norm[2], 1.0);
+        }
+        (Some(normals), None) => {
+            for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                vertex.normal = Vector3::from(normal);
             }
-            
-            
-            
-            
-            surface.count = u32::try_from(indices_vec.len()).expect(MODEL_DENSITY);
-            metadata_holder.surfaces.push(surface);
+            generate_tangents(&mut indices, &mut vertices);
         }
-        metadata_holder.meshes.push(VkMeshBuffers::upload_mesh(device, allocator, command_control, &indices_vec, &vertices_vec[..]).unwrap());
-        holder.push(Rc::new(metadata_holder));
-        //holder.meshes
-        
-        
-        /*
-        println!("{:?}", holder.metadatas);
-        println!("{:?}", indices_vec);
-        for vertex in &vertices_vec {
-            println!("{:?}", vertex);
-            
+        // shared vertices mean the author wanted them smooth, otherwise every triangle is on its own
+        (None, _) if indexed => {
+            smooth_normals(&indices, &mut vertices);
+            generate_tangents(&mut indices, &mut vertices);
+        }
+        (None, _) => {
+            (indices, vertices) = flat_normals(&indices, &vertices);
+            generate_tangents(&mut indices, &mut vertices);
         }
-        */
-        
     }
     
-    /*
-    println!("surface");
-    for metadata in &holder.metadatas {
-        println!("{:?}", metadata);
+    logger::various_log!("mesh",
+        (logger::Trace, "mesh {} primitive {} indices {} vertices {}", mesh, primitive.index(), indices.len(), vertices.len())
+    );
+    Ok((indices, vertices))
+}
+
+// strips and fans are unrolled, points and lines can't be drawn by the mesh pipelines
+fn triangle_list(mode:gltf::mesh::Mode, indices:Vec<u32>) -> Option<Vec<u32>> {
+    use gltf::mesh::Mode;
+    let triangles = indices.len().saturating_sub(2);
+    match mode {
+        Mode::Triangles => {
+            let mut holder = indices;
+            holder.truncate(holder.len() - holder.len()%3);
+            Some(holder)
+        }
+        // every other triangle is flipped to keep the winding
+        Mode::TriangleStrip => {
+            Some((0..triangles).flat_map(|triangle|{
+                match triangle%2 {
+                    0 => [indices[triangle], indices[triangle+1], indices[triangle+2]],
+                    _ => [indices[triangle], indices[triangle+2], indices[triangle+1]],
+                }
+            }).collect())
+        }
+        Mode::TriangleFan => {
+            Some((0..triangles).flat_map(|triangle|{
+                [indices[triangle+1], indices[triangle+2], indices[0]]
+            }).collect())
+        }
+        _ => None,
+    }
+}
+
+fn face_normal(vertices:&[Vertex], triangle:&[u32]) -> Vector3<f32> {
+    let [a, b, c] = [0, 1, 2].map(|corner|vertices[usize::try_from(triangle[corner]).expect(MODEL_DENSITY)].position);
+    (b - a).cross(&(c - a))
+}
+
+// face normals are added unnormalized, so bigger triangles weight more
+fn smooth_normals(indices:&[u32], vertices:&mut [Vertex]) {
+    let mut normals = vec![Vector3::zeros(); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let normal = face_normal(vertices, triangle);
+        for &index in triangle {
+            normals[usize::try_from(index).expect(MODEL_DENSITY)] += normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        vertex.normal = normal.try_normalize(f32::EPSILON).unwrap_or(Vector3::z());
+    }
+}
+
+fn flat_normals(indices:&[u32], vertices:&[Vertex]) -> (Vec<u32>, Vec<Vertex>) {
+    let mut holder = Vec::with_capacity(indices.len());
+    for triangle in indices.chunks_exact(3) {
+        let normal = face_normal(vertices, triangle).try_normalize(f32::EPSILON).unwrap_or(Vector3::z());
+        for &index in triangle {
+            let mut vertex = vertices[usize::try_from(index).expect(MODEL_DENSITY)];
+            vertex.normal = normal;
+            holder.push(vertex);
+        }
+    }
+    let indices = (0..u32::try_from(holder.len()).expect(MODEL_DENSITY)).collect();
+    (indices, holder)
+}
+
+// corners of a triangle list as mikktspace walks them, it hands back one tangent per corner
+struct TangentSpace<'a> {
+    indices: &'a [u32],
+    vertices: &'a [Vertex],
+    tangents: Vec<Vector4<f32>>,
+}
+
+impl TangentSpace<'_> {
+    fn vertex(&self, face:usize, vert:usize) -> &Vertex {
+        &self.vertices[usize::try_from(self.indices[face*3 + vert]).expect(MODEL_DENSITY)]
+    }
+}
+
+impl mikktspace::Geometry for TangentSpace<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len()/3
     }
-    */
     
-    /*
-    println!("{:#?}", holder.meshes[0].index_buffer);
-    unsafe {panic!("{:#?}", holder.meshes[0].index_buffer.unsafe_clone())};
-    */
+    fn num_vertices_of_face(&self, _face:usize) -> usize {
+        3
+    }
     
-    Ok(holder)
+    fn position(&self, face:usize, vert:usize) -> [f32; 3] {
+        self.vertex(face, vert).position.into()
+    }
+    
+    fn normal(&self, face:usize, vert:usize) -> [f32; 3] {
+        self.vertex(face, vert).normal.into()
+    }
+    
+    fn tex_coord(&self, face:usize, vert:usize) -> [f32; 2] {
+        let vertex = self.vertex(face, vert);
+        [vertex.uv_x, vertex.uv_y]
+    }
     
+    fn set_tangent_encoded(&mut self, tangent:[f32; 4], face:usize, vert:usize) {
+        self.tangents[face*3 + vert] = Vector4::from(tangent);
+    }
 }
 
-impl VkMeshBuffers {
-    pub fn upload_mesh(
-        device: &mut Device,
-        allocator: &mut Allocator,
-        command: &mut CommandControl,
-        indices: &[u32],
-        vertices: &[Vertex],
-    ) -> Result<Self, AAError> {
-        logger::create!("mesh");
-        if indices.is_empty() || vertices.is_empty() {
-            return Err(AAError::EmptyMesh);
+// w keeps the sign of the bitangent, a vertex whose corners got different tangents
+// sits on a uv seam and is split so every side keeps its own
+pub fn generate_tangents(indices:&mut [u32], vertices:&mut Vec<Vertex>) {
+    // vertices outside of every triangle and meshes mikktspace refuses still get a valid frame
+    for vertex in vertices.iter_mut() {
+        vertex.tangent = any_orthogonal(&vertex.normal).push(1.0);
+    }
+    
+    let mut space = TangentSpace{
+        indices,
+        vertices,
+        tangents: vec![Vector4::zeros(); indices.len()],
+    };
+    if !mikktspace::generate_tangents(&mut space) {
+        logger::various_log!("mesh",
+            (logger::Debug, "mikktspace could not generate tangents for {} indices", indices.len())
+        );
+        return;
+    }
+    let tangents = space.tangents;
+    
+    let mut assigned = vec![false; vertices.len()];
+    let mut splits:HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+    for (corner, tangent) in tangents.into_iter().enumerate() {
+        let index = usize::try_from(indices[corner]).expect(MODEL_DENSITY);
+        if !assigned[index] {
+            vertices[index].tangent = tangent;
+            assigned[index] = true;
+            continue;
         }
-        let indices_size = indices.len() * size_of_val(&indices[0]);
-        let indices_size_u64 = u64::try_from(indices_size).expect(VK_CAST);
-        let vertices_size = vertices.len() * size_of_val(&vertices[0]);
-        let vertices_size_u64 = u64::try_from(vertices_size).expect(VK_CAST);
-        
-        use vk::BufferUsageFlags as buf;
-        let mut vertex_buffer = Buffer::create(device, allocator, Some("mesh vertex buffer"), vertices_size_u64, buf::VERTEX_BUFFER|buf::STORAGE_BUFFER|buf::SHADER_DEVICE_ADDRESS|buf::TRANSFER_DST, memory::GpuOnly)?;
-        let vertex_buffer_address = vertex_buffer.get_device_address(device);
-        
-        let mut index_buffer = Buffer::create(device, allocator, Some("mesh index buffer"), indices_size_u64, buf::INDEX_BUFFER|buf::TRANSFER_DST, memory::GpuOnly)?;
-        
-        let mut staging_buffer = Buffer::create(device, allocator, Some("mesh staging buffer"), indices_size_u64+vertices_size_u64, buf::TRANSFER_SRC, memory::CpuToGpu)?;
-        
-        
-        let mut vert_align = staging_buffer.get_align::<Vertex>(0, vertices_size_u64).expect(GRANTED);
-        vert_align.copy_from_slice(vertices);
-        
-        let mut index_align = staging_buffer.get_align::<u32>(vertices_size, indices_size_u64).expect(GRANTED);
-        index_align.copy_from_slice(indices);
-        
-        memory::copy_buffer_2_buffer(device, command, &staging_buffer, 0, &mut vertex_buffer, 0, vertices_size_u64);
-        memory::copy_buffer_2_buffer(device, command, &staging_buffer, vertices_size_u64, &mut index_buffer, 0, indices_size_u64);
-        
-        staging_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
-        
-        Ok(Self{
-            vertex_buffer,
-            vertex_buffer_address,
-            index_buffer,
-        })
+        if vertices[index].tangent == tangent {
+            continue;
+        }
+        let key = (indices[corner], [tangent.x, tangent.y, tangent.z, tangent.w].map(f32::to_bits));
+        indices[corner] = *splits.entry(key).or_insert_with(||{
+            let mut split = vertices[index];
+            split.tangent = tangent;
+            vertices.push(split);
+            u32::try_from(vertices.len() - 1).expect(MODEL_DENSITY)
+        });
     }
 }
 
+fn any_orthogonal(normal:&Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 {Vector3::x()} else {Vector3::y()};
+    normal.cross(&axis).try_normalize(f32::EPSILON).unwrap_or(Vector3::x())
+}
 
-impl VkDestructor for VkMeshBuffers {
-    fn destruct(self, mut args:VkDestructorArguments) {
-        logger::destruct!("mesh");
-        let (device, allocator) = args.unwrap_dev_all();
-        self.index_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
-        self.vertex_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
+pub fn truncated_name(name:&str) -> ArrayString<64> {
+    let mut holder = ArrayString::new();
+    for character in name.chars() {
+        if holder.try_push(character).is_err() {
+            break;
+        }
     }
+    holder
 }
 
-impl VkDestructor for VkMeshAsset {
-    fn destruct(self, mut args:VkDestructorArguments) {
-        logger::destruct!("mesh_asset");
-        let (device, allocator) = args.unwrap_dev_all();
-        
-        let VkMeshAsset{
-            meshes,
-            ..
-        } = self;
-        
-        for mesh in meshes.into_iter() {
-            mesh.index_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
-            mesh.vertex_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
+impl CpuMesh {
+    pub fn view(&self) -> MeshView<'_> {
+        MeshView{
+            name: self.name,
+            surfaces: &self.surfaces,
+            indices: &self.indices,
+            vertices: &self.vertices,
+            bounds: Bounds::from_vertices(&self.vertices),
         }
     }
 }
 
-impl VkDestructor for VkMeshAssets {
-    fn destruct(self, mut args:VkDestructorArguments) {
-        //TODO: check loggic of this thing
-        logger::destruct!("mesh_assets");
-        let (device, allocator) = args.unwrap_dev_all();
-        for metadata in self.into_iter() {
-            let metadata = Rc::try_unwrap(metadata).unwrap();
-            metadata.destruct(VkDestructorArguments::DevAll(device, allocator));
-        }
+impl VkMeshAsset {
+    // the surfaces start without material so the default one is used until their textures are streamed in
+    pub fn upload(
+        device: &Device,
+        geometry: &mut GeometryBuffers,
+        uploader: &mut UploadManager,
+        mesh: &MeshView,
+    ) -> Result<Self, AAError> {
+        logger::create!("mesh");
+        let allocation = geometry.allocate(device, uploader, mesh.indices, mesh.vertices)?;
+        let surfaces = mesh.surfaces.iter().map(|surface|{
+            VkGeoSurface{
+                start_index: surface.start_index,
+                count: surface.count,
+                material: None,
+            }
+        }).collect();
+        Ok(Self{
+            name: mesh.name,
+            surfaces,
+            geometry: allocation,
+            bounds: mesh.bounds,
+        })
     }
 }
+
@@ -1,12 +1,34 @@
+mod geometry;
+pub use geometry::*;
+
 mod mesh;
-pub use mesh::load_gltf;
-pub use mesh::VkMeshBuffers;
 pub use mesh::VkMeshAsset;
-pub use mesh::VkMeshAssets;
+
+mod obj;
+mod optimize;
+mod bake;
+
+mod assets;
+pub use assets::*;
+
+mod streaming;
+pub use streaming::AssetStreamer;
+pub use streaming::bake_models;
+pub use streaming::StreamProgress;
+
+mod indirect;
+pub use indirect::IndirectDrawer;
+pub use indirect::IndirectBatch;
+
+mod instancing;
+pub use instancing::InstancedScene;
 
 mod frame;
 pub use frame::FramesData;
 
+mod capture;
+pub use capture::PendingCapture;
+
 mod r_object;
 pub use r_object::RenderObject;
 pub use r_object::IRenderable;
@@ -15,8 +37,16 @@ mod types;
 pub use types::*;
 
 use crate::AAError;
+use crate::logger;
 use crate::gui::Gui;
-use crate::errors::messages::SIMPLE_VK_FN;
+use crate::graphics::Recording;
+use crate::graphics::StreamState;
+use crate::game::World;
+use crate::game::Transform;
+use crate::game::MeshRenderer;
+use crate::game::Camera;
+use crate::game::Light;
+use crate::game::render_transform;
 use crate::errors::messages::COMPILETIME_ASSERT;
 use crate::errors::messages::CPU_ACCESIBLE;
 
@@ -24,6 +54,8 @@ pub use crate::graphics::GeoSurface;
 pub use crate::graphics::ComputePushConstants;
 pub use crate::graphics::Vertex;
 pub use crate::graphics::GPUSceneData;
+pub use crate::graphics::GpuTimings;
+pub use crate::graphics::Bounds;
 
 
 use super::VkDestructor;
@@ -40,6 +72,11 @@ use super::image;
 
 use super::DescriptorWriter;
 
+use super::render_graph::RenderGraph;
+use super::render_graph::GraphImage;
+use super::render_graph::ResourceState;
+use super::render_graph::Access;
+
 use super::materials::MaterialInstance;
 
 use std::slice::from_ref;
@@ -59,12 +96,12 @@ use gpu_allocator as gpu_all;
 pub struct GPUDrawPushConstants {
     world_matrix: Matrix4<f32>,
     vertex_buffer: vk::DeviceAddress,
+    instance_buffer: vk::DeviceAddress,
 }
 
 
 pub struct Canvas {
     render_image: Image,
-    depth_image: Image,
 }
 
 impl Canvas {
@@ -72,24 +109,22 @@ impl Canvas {
         
         let render_image = Image::create(device, allocator, extent, image::RENDER, None)?;
         
-        let depth_image = Image::create(device, allocator, extent, image::DEPTH, None)?;
-        
         Ok(Self{
             render_image,
-            depth_image,
         })
     }
     
-    pub fn get_images(&mut self) -> (&mut Image, &mut Image) {
-        (&mut self.render_image, &mut self.depth_image)
-    }
-    
     pub fn get_color(&self) -> &Image {
         &self.render_image
     }
     
+    pub fn get_color_mut(&mut self) -> &mut Image {
+        &mut self.render_image
+    }
+    
     pub fn get_formats(&self) -> (vk::Format, vk::Format) {
-        (self.render_image.format, self.depth_image.format)
+        // the depth image is a transient of the render graph
+        (self.render_image.format, image::DEPTH.format)
     }
     
 }
@@ -98,7 +133,6 @@ impl VkDestructor for Canvas {
     fn destruct(self, mut args:VkDestructorArguments) {
         let (device, allocator) = args.unwrap_dev_all();
         self.render_image.destruct(VkDestructorArguments::DevAll(device, allocator));
-        self.depth_image.destruct(VkDestructorArguments::DevAll(device, allocator));
     }
 }
 
@@ -108,12 +142,18 @@ impl VInit {
     
     
 //----
+    // an out of date or lost surface only skips the frame and asks for a swapchain rebuild,
+    // any other error leaves the device in an unknown state and the renderer has to be rebuilt
     pub fn draw_frame(
         &mut self,
         imgui: &mut Gui,
+        world: &World,
+        // between the previous simulation tick and the current one
+        alpha: f32,
         
-    ) {
+    ) -> Result<(), AAError> {
         self.frame_update();
+        self.poll_streaming()?;
         let cf = self.get_frame();
         
         let VInit{
@@ -130,7 +170,7 @@ impl VInit {
             device, 
             allocator,
             
-            mesh_assets,
+            assets,
             mesh_index,
             
             field_of_view,
@@ -144,6 +184,16 @@ impl VInit {
             
             gpu_scene_layout,
             scene_data,
+            gpu_timings,
+            transient_images,
+            upload_manager,
+            geometry,
+            bindless,
+            indirect_drawer,
+            gpu_driven,
+            capture_request,
+            recording,
+            asset_streamer,
             ..
         } = self;
         
@@ -152,23 +202,34 @@ impl VInit {
         
         let (image_avaliable_semaphore, render_finished_semaphore, inflight_fence) = frames_data.get_frame_sync(cf);
         
-        let destruction_stack = frames_data.get_destruction_stack(cf);
-        
-        unsafe{device.wait_for_fences(from_ref(&inflight_fence), true, u64::MAX)}.expect(SIMPLE_VK_FN);
+        unsafe{device.wait_for_fences(from_ref(&inflight_fence), true, u64::MAX)}?;
         
+        if let Some(capture) = frames_data.get_capture(cf).take() {
+            capture.complete(device, allocator);
+        }
+        let destruction_stack = frames_data.get_destruction_stack(cf);
         destruction_stack.dispatch(device, allocator);
+        upload_manager.collect(device)?;
         main_draw_context.clear();
+        // the renderer only has a sun, the first light of the world
+        if let Some((_, light)) = world.query::<Light>().next() {
+            scene_data.set_light(&light.direction, &light.color, light.intensity, &light.ambient);
+        }
         
-        let mut gpu_scene_buffer = Buffer::create(device, allocator, Some("per_frame_buffer"), GPUSceneData::size_u64(), vk::BufferUsageFlags::UNIFORM_BUFFER, gpu_all::MemoryLocation::CpuToGpu).unwrap();//TODO:changet this unwrap
+        let mut gpu_scene_buffer = Buffer::create(device, allocator, Some("per_frame_buffer"), GPUSceneData::size_u64(), vk::BufferUsageFlags::UNIFORM_BUFFER, gpu_all::MemoryLocation::CpuToGpu)?;
         {
             let mut align = gpu_scene_buffer.get_align::<GPUSceneData>(0, GPUSceneData::size_u64()).expect(CPU_ACCESIBLE);
             align.copy_from_slice(from_ref(&scene_data));
         }
         destruction_stack.push(gpu_scene_buffer.defered_destruct());
         
+        if let Some((timings, statistics)) = frames_data.get_queries(cf).read_results(device) {
+            gpu_timings.push_frame(&timings, statistics);
+        }
+        
         let descriptor_allocator = frames_data.get_descriptor_allocator(cf);
-        descriptor_allocator.clear_pools(device);
-        let scene_descriptor = descriptor_allocator.allocate(device, gpu_scene_layout).unwrap();
+        descriptor_allocator.clear_pools(device)?;
+        let scene_descriptor = descriptor_allocator.allocate(device, gpu_scene_layout)?;
         
         let mut writer = DescriptorWriter::default();
         writer.write_buffer(0, gpu_scene_buffer.underlying(), GPUSceneData::size_u64(), 0, vk::DescriptorType::UNIFORM_BUFFER);
@@ -177,58 +238,198 @@ impl VInit {
         
         let (p_image_handle, p_image_view, image_index) = match swapchain.get_next_image(image_avaliable_semaphore){
             Ok(holder) => {holder}
-            Err(()) => {
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::ERROR_SURFACE_LOST_KHR) => {
                 *resize_required = true;
-                return;
+                return Ok(());
+            }
+            Err(error) => {
+                return Err(error.into());
             }
         };
         
-        unsafe{device.reset_fences(from_ref(&inflight_fence))}.expect(SIMPLE_VK_FN);
-        unsafe{device.reset_command_buffer(cmd, vk::CommandBufferResetFlags::empty())}.expect(SIMPLE_VK_FN);
-        
-        let begin_info = vk::CommandBufferBeginInfo::builder()
-            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-        
-        unsafe{device.begin_command_buffer(cmd, &begin_info)}.expect(SIMPLE_VK_FN);
-        
-        let (render_image, depth_image) = canvas.get_images();
-        
-        let r_image_handle = render_image.underlying();
-        let d_image_handle = depth_image.underlying();
-        
-        
-        let extent = Self::calculate_extent(render_image.extent_2d, swapchain.extent, *downscale_coheficient);
-        
-        Image::transition_image(device, cmd, r_image_handle, vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL);
-        
-        Self::draw_background(device, cmd, render_image, *background_image_ds, &compute_effects.pipelines[compute_effect_index], &compute_effects.push_constants[compute_effect_index]);
-        
-        Image::transition_image(device, cmd, d_image_handle, vk::ImageLayout::UNDEFINED, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
-        Image::transition_image(device, cmd, r_image_handle, vk::ImageLayout::GENERAL, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
-        
-        let default_material = materials.get_default();
-        mesh_assets[*mesh_index].draw(&na::Matrix4::<f32>::identity(), main_draw_context);
-        //mesh_assets[2].draw(&(na::Matrix4::<f32>::identity().append_translation(&na::Vector3::new(1.3,0.4,0.0))), main_draw_context);
-        /*
-        mesh_assets[*mesh_index].draw(&(na::Matrix4::<f32>::identity().append_translation(&na::Vector3::new(-1.0,-1.0,-1.0))), main_draw_context);
-        mesh_assets[*mesh_index].draw(&(na::Matrix4::<f32>::identity().append_translation(&na::Vector3::new(1.0,-1.0,-1.0))), main_draw_context);
-        mesh_assets[*mesh_index].draw(&(na::Matrix4::<f32>::identity().append_translation(&na::Vector3::new(1.0,1.0,-1.0))), main_draw_context);
-        */
-        //Self::draw_geometry(device, cmd, extent, canvas, mesh_assets, *mesh_index, field_of_view, main_draw_context, default_material, scene_descriptor);
-        Self::draw_geometry(device, cmd, extent, canvas, /*mesh_assets, *mesh_index, */field_of_view, main_draw_context, default_material, scene_descriptor);
-        
-        Image::transition_image(device, cmd, r_image_handle, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
-        Image::transition_image(device, cmd, p_image_handle, vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
-        
-        Image::raw_copy_image_to_image(device, cmd, r_image_handle, vk::Extent3D::from(extent), p_image_handle, vk::Extent3D::from(swapchain.extent));
-        
-        Image::transition_image(device, cmd, p_image_handle, vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
-        
-        imgui.render(device, cmd, swapchain.extent, p_image_view);
+        // the fence is only reset once something is sure to be submitted, a frame that fails to record
+        // leaves it signaled so the next wait on it does not hang
+        let recorded = (||->Result<(), AAError>{
+            unsafe{device.reset_command_buffer(cmd, vk::CommandBufferResetFlags::empty())}?;
+            
+            let begin_info = vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            
+            unsafe{device.begin_command_buffer(cmd, &begin_info)}?;
+            
+            let render_image = canvas.get_color_mut();
+            let render_extent = render_image.extent;
+            let canvas_format = render_image.format;
+            let swapchain_image = GraphImage{
+                image: p_image_handle,
+                view: p_image_view,
+                extent: vk::Extent3D::from(swapchain.extent),
+                format: swapchain.surface_format.format,
+                aspect: vk::ImageAspectFlags::COLOR,
+            };
+            let swapchain_extent = swapchain.extent;
+            let extent = Self::calculate_extent(render_image.extent_2d, swapchain_extent, *downscale_coheficient);
+            
+            let default_material = materials.get_default();
+            for (entity, renderer) in world.query::<MeshRenderer>() {
+                let matrix = render_transform(world, entity, alpha).as_ref().map(Transform::matrix).unwrap_or_else(na::Matrix4::identity);
+                renderer.draw(&matrix, assets, main_draw_context);
+            }
+            
+            // a recording waits for the streaming to end, so it starts with every asset in place
+            let streaming = asset_streamer.progress().iter().any(|stream|matches!(stream.state, StreamState::Parsing | StreamState::Loading));
+            let recording = recording.as_mut().filter(|_|!streaming);
+            let selected_mesh = assets.mesh_list().get(*mesh_index).and_then(|entry|assets.meshes.get(entry.handle));
+            let camera = world.query::<Camera>().find(|(_, camera)|camera.active);
+            let mut field_of_view = *field_of_view;
+            let view = match (recording.as_deref().and_then(Recording::turntable_turn), selected_mesh, camera) {
+                (Some(turn), Some(mesh), _) => Self::turntable_view(&mesh.bounds, turn, &field_of_view),
+                (_, _, Some((entity, camera))) => {
+                    if let Some(vertical_fov) = camera.vertical_fov {
+                        field_of_view[2] = vertical_fov;
+                    }
+                    let transform = render_transform(world, entity, alpha).unwrap_or_default();
+                    transform.matrix().try_inverse().unwrap_or_else(Self::default_view)
+                }
+                _ => Self::default_view(),
+            };
+            //mesh_assets[2].draw(&(na::Matrix4::<f32>::identity().append_translation(&na::Vector3::new(1.3,0.4,0.0))), main_draw_context);
+            /*
+            mesh_assets[*mesh_index].draw(&(na::Matrix4::<f32>::identity().append_translation(&na::Vector3::new(-1.0,-1.0,-1.0))), main_draw_context);
+            mesh_assets[*mesh_index].draw(&(na::Matrix4::<f32>::identity().append_translation(&na::Vector3::new(1.0,-1.0,-1.0))), main_draw_context);
+            mesh_assets[*mesh_index].draw(&(na::Matrix4::<f32>::identity().append_translation(&na::Vector3::new(1.0,1.0,-1.0))), main_draw_context);
+            */
+            
+            let projection = Self::tmp_perspective_matrix(extent, &field_of_view, &view);
+            let instanced_scene = main_draw_context.merge(default_material, &assets.materials);
+            let destruction_stack = frames_data.get_destruction_stack(cf);
+            let instance_buffer = instanced_scene.upload(device, allocator, destruction_stack)?;
+            let indirect_batch = match (*gpu_driven, instance_buffer) {
+                (true, Some(instance_buffer)) => {
+                    // the cull shader sees the same clip space as mesh.vert, scene matrix times the push constant one
+                    let view_projection = scene_data.view_projection() * projection;
+                    IndirectDrawer::prepare(device, allocator, destruction_stack, instance_buffer, (instanced_scene.indirect_instances, instanced_scene.short_indirect_instances), &view_projection)?
+                }
+                _ => None,
+            };
+            let instance_buffer = instance_buffer.unwrap_or_default();
+            
+            // the passes can't fail, the imgui result is picked up once the graph is recorded
+            let mut imgui_result = Ok(());
+            let mut graph = RenderGraph::new();
+            let render_handle = graph.import_tracked_image("render_image", render_image, None);
+            let depth_handle = graph.create_image("depth_image", render_extent, image::DEPTH);
+            let swapchain_handle = graph.import_image("swapchain_image", swapchain_image, ResourceState::ACQUIRED, Some(Access::Present));
+            
+            let background_image_ds = *background_image_ds;
+            let compute_pipeline = &compute_effects.pipelines[compute_effect_index];
+            let compute_push_constants = &compute_effects.push_constants[compute_effect_index];
+            graph.add_pass("background")
+                .write_image(render_handle, Access::ComputeStorageWrite)
+                .execute(move |device, cmd, resources|{
+                    let extent = resources.image(render_handle).extent_2d();
+                    Self::draw_background(device, cmd, extent, background_image_ds, compute_pipeline, compute_push_constants);
+                });
+            
+            let indirect_handles = indirect_batch.as_ref().map(|batch|{
+                let draws_handle = graph.import_buffer("indirect_draws", batch.draw_buffer, ResourceState::UNDEFINED);
+                let count_handle = graph.import_buffer("indirect_count", batch.count_buffer, ResourceState::UNDEFINED);
+                
+                graph.add_pass("cull_reset")
+                    .write_buffer(count_handle, Access::BufferClear)
+                    .execute(move |device, cmd, _resources|{
+                        batch.clear_count(device, cmd);
+                    });
+                
+                let indirect_drawer = &*indirect_drawer;
+                graph.add_pass("cull")
+                    .write_buffer(draws_handle, Access::ComputeBufferWrite)
+                    .write_buffer(count_handle, Access::ComputeBufferWrite)
+                    .execute(move |device, cmd, _resources|{
+                        indirect_drawer.cull(device, cmd, batch);
+                    });
+                (draws_handle, count_handle)
+            });
+            
+            let instanced_scene = &instanced_scene;
+            let geometry = &*geometry;
+            let bindless_set = bindless.set();
+            let indirect_batch = indirect_batch.as_ref();
+            let mut geometry_pass = graph.add_pass("geometry")
+                .write_image(render_handle, Access::ColorAttachmentWrite)
+                .write_image(depth_handle, Access::DepthAttachmentWrite)
+                .statistics();
+            if let Some((draws_handle, count_handle)) = indirect_handles {
+                geometry_pass = geometry_pass
+                    .read_buffer(draws_handle, Access::IndirectRead)
+                    .read_buffer(count_handle, Access::IndirectRead);
+            }
+            geometry_pass.execute(move |device, cmd, resources|{
+                let color = resources.image(render_handle).view;
+                let depth = resources.image(depth_handle).view;
+                Self::draw_geometry(device, cmd, extent, color, depth, &projection, geometry, instance_buffer, instanced_scene, indirect_batch, default_material, scene_descriptor, bindless_set);
+            });
+            
+            graph.add_pass("blit")
+                .read_image(render_handle, Access::BlitSrc)
+                .write_image(swapchain_handle, Access::BlitDst)
+                .execute(move |device, cmd, resources|{
+                    let source = resources.image(render_handle);
+                    let destination = resources.image(swapchain_handle);
+                    Image::raw_copy_image_to_image(device, cmd, source.image, vk::Extent3D::from(extent), destination.image, destination.extent);
+                });
+            
+            let imgui_result_ref = &mut imgui_result;
+            graph.add_pass("imgui")
+                .write_image(swapchain_handle, Access::ColorAttachmentWrite)
+                .execute(move |device, cmd, resources|{
+                    *imgui_result_ref = imgui.render(device, cmd, swapchain_extent, resources.image(swapchain_handle).view);
+                });
+            
+            // the request is only taken once the frame is sure to be recorded, a recorded frame goes first
+            // and a screenshot asked for meanwhile waits for a frame the recording leaves free
+            let capture = match recording.and_then(Recording::advance) {
+                Some((request, sink)) => Some((request, Some(sink))),
+                None => capture_request.take().map(|request|(request, None)),
+            };
+            if let Some((request, sink)) = capture {
+                let capture = PendingCapture::create(device, allocator, request, (canvas_format, extent), swapchain, sink)?;
+                capture.add_passes(&mut graph, render_handle, swapchain_handle);
+                *frames_data.get_capture(cf) = Some(capture);
+            }
+            
+            let queries = frames_data.get_queries(cf);
+            queries.reset(device, cmd);
+            queries.begin_scope(device, cmd, "frame");
+            // each pass gets its own label inside this one
+            device.begin_label(cmd, "frame");
+            graph.execute(device, allocator, transient_images, cmd, Some(queries))?;
+            imgui_result?;
+            device.end_label(cmd);
+            frames_data.get_queries(cf).end_scope(device, cmd);
+            
+            unsafe{device.end_command_buffer(cmd)}?;
+            Ok(())
+        })();
         
-        Image::transition_image(device, cmd, p_image_handle, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, vk::ImageLayout::PRESENT_SRC_KHR);
+        if let Err(error) = recorded {
+            if error.is_device_lost() {
+                return Err(error);
+            }
+            logger::various_log!("vulkan",
+                (logger::Error, "frame skipped {:?}", error)
+            );
+            // nothing recorded was submitted, what it left behind is dropped and the image is still presented
+            if let Some(capture) = frames_data.get_capture(cf).take() {
+                capture.destruct(VkDestructorArguments::DevAll(device, allocator));
+            }
+            frames_data.get_queries(cf).discard();
+            // the background pass writes all of it every frame, so the content is not needed
+            canvas.get_color_mut().assume_state(ResourceState::UNDEFINED);
+            Self::record_skipped_frame(device, cmd, p_image_handle)?;
+        }
         
-        unsafe{device.end_command_buffer(cmd)}.expect(SIMPLE_VK_FN);
+        unsafe{device.reset_fences(from_ref(&inflight_fence))}?;
         
         let wait_semaphore_submit_info = vk::SemaphoreSubmitInfo::builder()
             .stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
@@ -247,7 +448,7 @@ impl VInit {
             .signal_semaphore_infos(from_ref(&signal_semaphore_submit_info));
         
         
-        unsafe{device.queue_submit2(device.queue_handles.graphics, from_ref(&submit_info), inflight_fence)}.expect(SIMPLE_VK_FN);
+        unsafe{device.queue_submit2(device.queue_handles.graphics, from_ref(&submit_info), inflight_fence)}?;
         
         let present_info = vk::PresentInfoKHR::builder()
             .swapchains(from_ref(&swapchain.swapchain))
@@ -256,14 +457,60 @@ impl VInit {
         
         match unsafe{swapchain.queue_present(device.queue_handles.presentation, &present_info)}{
             Ok(_) => {}
-            Err(_error) => {
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::ERROR_SURFACE_LOST_KHR) => {
                 *resize_required = true;
             }
+            Err(error) => {
+                return Err(error.into());
+            }
         }
+        Ok(())
+    }
+    
+//----
+    // consumes the acquired image of a frame that failed to record, it is cleared to black and presented
+    fn record_skipped_frame(device:&Device, cmd:vk::CommandBuffer, image:vk::Image) -> Result<(), AAError> {
+        unsafe{device.reset_command_buffer(cmd, vk::CommandBufferResetFlags::empty())}?;
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe{device.begin_command_buffer(cmd, &begin_info)}?;
+        
+        let range = Image::subresource_range(vk::ImageAspectFlags::COLOR);
+        // the source stage is the one the acquire semaphore is waited at
+        let to_clear = vk::ImageMemoryBarrier2::builder()
+            .image(image)
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(vk::AccessFlags2::NONE)
+            .dst_stage_mask(vk::PipelineStageFlags2::CLEAR)
+            .dst_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+            .subresource_range(range);
+        let dependency = vk::DependencyInfo::builder()
+            .image_memory_barriers(from_ref(&to_clear));
+        unsafe{device.cmd_pipeline_barrier2(cmd, &dependency)};
+        
+        let black = vk::ClearColorValue{float32:[0.0, 0.0, 0.0, 1.0]};
+        unsafe{device.cmd_clear_color_image(cmd, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &black, from_ref(&range))};
+        
+        let to_present = vk::ImageMemoryBarrier2::builder()
+            .image(image)
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+            .src_stage_mask(vk::PipelineStageFlags2::CLEAR)
+            .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags2::NONE)
+            .dst_access_mask(vk::AccessFlags2::NONE)
+            .subresource_range(range);
+        let dependency = vk::DependencyInfo::builder()
+            .image_memory_barriers(from_ref(&to_present));
+        unsafe{device.cmd_pipeline_barrier2(cmd, &dependency)};
+        
+        Ok(unsafe{device.end_command_buffer(cmd)}?)
     }
     
 //----
-    pub fn draw_background(device:&mut Device, cmd:vk::CommandBuffer, image:&Image, background_image_ds:vk::DescriptorSet, cp_pipeline:&CPipeline, push_constants:&ComputePushConstants) {
+    pub fn draw_background(device:&mut Device, cmd:vk::CommandBuffer, extent:vk::Extent2D, background_image_ds:vk::DescriptorSet, cp_pipeline:&CPipeline, push_constants:&ComputePushConstants) {
         
         unsafe{device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, cp_pipeline.pipeline)};
         unsafe{device.cmd_bind_descriptor_sets(cmd, vk::PipelineBindPoint::COMPUTE, cp_pipeline.layout, 0, from_ref(&background_image_ds), &[])};
@@ -271,7 +518,7 @@ impl VInit {
         let push_constants_slice = unsafe{crate::any_as_u8_slice(push_constants)};
         unsafe{device.cmd_push_constants(cmd, cp_pipeline.layout, vk::ShaderStageFlags::COMPUTE, 0, push_constants_slice)};
         
-        unsafe{device.cmd_dispatch(cmd, image.extent.width/16+1, image.extent.height/16+1, 1)};
+        unsafe{device.cmd_dispatch(cmd, extent.width/16+1, extent.height/16+1, 1)};
         
     }
 
@@ -281,11 +528,8 @@ impl VInit {
         device: &mut Device, 
         cmd: vk::CommandBuffer, 
         extent: vk::Extent2D, 
-        canvas: &mut Canvas,
-        /*
-        image: &Image, 
-        depth: &Image, 
-        */
+        color_view: vk::ImageView,
+        depth_view: vk::ImageView,
         
         //mesh_pipeline: &GPipeline, 
         
@@ -294,11 +538,15 @@ impl VInit {
         mesh_selector: usize, 
         */
         
-        field_of_view: &na::Vector3<f32>,
-        draw_context: &mut DrawContext,
+        projection: &na::Matrix4<f32>,
+        geometry: &GeometryBuffers,
+        instance_buffer: vk::DeviceAddress,
+        instanced_scene: &InstancedScene,
+        indirect_batch: Option<&IndirectBatch>,
         
         default_material: &MaterialInstance,
         scene_descriptor: vk::DescriptorSet,
+        bindless_set: vk::DescriptorSet,
         /*
         texture_descriptor_layout: &DescriptorLayout,
         descriptor_allocator: &mut GDescriptorAllocator,
@@ -307,9 +555,8 @@ impl VInit {
         */
     ) {
         
-        let (image, depth) = canvas.get_images();
-        let color_attachment_info = pipeline::rendering_attachment_info(image.view, None, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
-        let depth_attachment_info = pipeline::depth_attachment_info(depth.view, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
+        let color_attachment_info = pipeline::rendering_attachment_info(color_view, None, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let depth_attachment_info = pipeline::depth_attachment_info(depth_view, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
         let rendering_info = pipeline::rendering_info(extent, &color_attachment_info, Some(&depth_attachment_info));
         
         
@@ -326,27 +573,48 @@ impl VInit {
         unsafe{device.cmd_set_viewport(cmd, 0, from_ref(&viewport))};
         unsafe{device.cmd_set_scissor(cmd, 0, from_ref(&scissor))};
         
-        for render_object in draw_context.iter() {
-            
-            let material = match render_object.material.as_ref() {
-                Some(material) => {material}
-                None => {default_material}
-            };
-            
-            unsafe{device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, material.pipeline.underlying())};
-            //TODO add cmd_descriptor_1
-            unsafe{device.cmd_bind_descriptor_sets(cmd, vk::PipelineBindPoint::GRAPHICS, material.pipeline.layout, 0, from_ref(&scene_descriptor), &[])};
-            unsafe{device.cmd_bind_descriptor_sets(cmd, vk::PipelineBindPoint::GRAPHICS, material.pipeline.layout, 1, from_ref(&material.descriptor_set), &[])};
-            unsafe{device.cmd_bind_index_buffer(cmd, render_object.index_buffer, 0, vk::IndexType::UINT32)};
+        // every material pipeline shares the layout, so the sets stay bound across pipeline changes
+        let descriptor_sets = [scene_descriptor, bindless_set];
+        unsafe{device.cmd_bind_descriptor_sets(cmd, vk::PipelineBindPoint::GRAPHICS, default_material.pipeline.layout, 0, &descriptor_sets, &[])};
+        
+        // the push constants are shared by every draw, the per object data lives in the instance buffer
+        let push_constant_tmp = GPUDrawPushConstants{
+            world_matrix: *projection,
+            vertex_buffer: geometry.vertex_buffer_address,
+            instance_buffer,
+        };
+        let push_constants_slice = unsafe{crate::any_as_u8_slice(&push_constant_tmp)};
+        unsafe{device.cmd_push_constants(cmd, default_material.pipeline.layout, vk::ShaderStageFlags::VERTEX, 0, push_constants_slice)};
+        
+        let mut bound_pipeline = vk::Pipeline::null();
+        let mut bound_index_type = None;
+        if let Some(batch) = indirect_batch {
+            bound_pipeline = default_material.pipeline.underlying();
+            unsafe{device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, bound_pipeline)};
+            for index_type in [vk::IndexType::UINT16, vk::IndexType::UINT32] {
+                unsafe{device.cmd_bind_index_buffer(cmd, geometry.index_buffer(index_type), 0, index_type)};
+                bound_index_type = Some(index_type);
+                batch.draw(device, cmd, index_type);
+            }
+        }
+        
+        for draw in instanced_scene.draws.iter() {
             
-            let mut push_constant_tmp = GPUDrawPushConstants::default();
-            push_constant_tmp.vertex_buffer = render_object.vertex_buffer_address;
-            push_constant_tmp.world_matrix = Self::tmp_perspective_matrix(extent, field_of_view)*render_object.transform;
-            let push_constants_slice = unsafe{crate::any_as_u8_slice(&push_constant_tmp)};
+            // already culled and drawn by the indirect batch
+            if indirect_batch.is_some() && indirect::is_indirect(&draw.material, default_material) {
+                continue;
+            }
             
-            unsafe{device.cmd_push_constants(cmd, material.pipeline.layout, vk::ShaderStageFlags::VERTEX, 0, push_constants_slice)};
+            if draw.material.pipeline.underlying() != bound_pipeline {
+                bound_pipeline = draw.material.pipeline.underlying();
+                unsafe{device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, bound_pipeline)};
+            }
+            if bound_index_type != Some(draw.index_type) {
+                bound_index_type = Some(draw.index_type);
+                unsafe{device.cmd_bind_index_buffer(cmd, geometry.index_buffer(draw.index_type), 0, draw.index_type)};
+            }
             
-            unsafe{device.cmd_draw_indexed(cmd, render_object.index_count, 1, render_object.first_index, 0, 0)};
+            unsafe{device.cmd_draw_indexed(cmd, draw.index_count, draw.instance_count, draw.first_index, draw.vertex_offset, draw.first_instance)};
         }
         
         /*
@@ -384,11 +652,11 @@ impl VInit {
         device: &mut Device, 
         cmd: vk::CommandBuffer, 
         extent: vk::Extent2D, 
-        canvas: &mut Canvas,
+        color_view: vk::ImageView,
+        depth_view: vk::ImageView,
     ) -> Result<(), ()> {
-        let (image, depth) = canvas.get_images();
-        let color_attachment_info = pipeline::rendering_attachment_info(image.view, None, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
-        let depth_attachment_info = pipeline::depth_attachment_info(depth.view, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
+        let color_attachment_info = pipeline::rendering_attachment_info(color_view, None, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let depth_attachment_info = pipeline::depth_attachment_info(depth_view, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
         let rendering_info = pipeline::rendering_info(extent, &color_attachment_info, Some(&depth_attachment_info));
         
         
@@ -405,9 +673,27 @@ impl VInit {
     }
     
 //----
-    pub fn tmp_perspective_matrix(extent:vk::Extent2D, field_of_view:&na::Vector3<f32>) -> na::Matrix4<f32> {
+    pub fn default_view() -> Matrix4<f32> {
         let mut view = Matrix4::<f32>::identity();
         view.prepend_translation_mut(&na::Vector3::new(-1.5,1.5,-5.0));
+        view
+    }
+    
+//----
+    // orbits the bounds slightly from above, far enough for their bounding sphere to fit the vertical field of view
+    pub fn turntable_view(bounds:&Bounds, turn:f32, field_of_view:&na::Vector3<f32>) -> Matrix4<f32> {
+        let radius = bounds.extents.norm().max(0.001);
+        let half_angle = field_of_view[2]/360.0*std::f32::consts::PI;
+        let distance = radius / half_angle.sin();
+        let angle = turn * std::f32::consts::TAU;
+        let elevation = 0.35f32;
+        let offset = na::Vector3::new(angle.sin()*elevation.cos(), elevation.sin(), angle.cos()*elevation.cos()) * distance;
+        let center = na::Point3::from(bounds.center);
+        Matrix4::look_at_rh(&(center + offset), &center, &na::Vector3::y())
+    }
+    
+//----
+    pub fn tmp_perspective_matrix(extent:vk::Extent2D, field_of_view:&na::Vector3<f32>, view:&Matrix4<f32>) -> na::Matrix4<f32> {
         
         //let mut projection = Matrix4::new_perspective(extent.width as f32/extent.height as f32, 70.0/180.0*std::f32::consts::PI, 10000.0, 0.1);       
         let mut projection = glm::perspective_zo(extent.width as f32/extent.height as f32, field_of_view[2]/180.0*std::f32::consts::PI, field_of_view[0], field_of_view[1]);
@@ -637,7 +923,7 @@ impl Vertex {
     
     pub const fn attribute_description() -> &'static[vk::VertexInputAttributeDescription] {
         
-        const HOLDER:[vk::VertexInputAttributeDescription; 5] = [
+        const HOLDER:[vk::VertexInputAttributeDescription; 7] = [
             vk::VertexInputAttributeDescription{
                 binding: 0,
                 location: 0,
@@ -668,6 +954,18 @@ impl Vertex {
                 format: vk::Format::R32G32B32A32_SFLOAT,
                 offset: offset_of!(Vertex, color) as u32
             },
+            vk::VertexInputAttributeDescription{
+                binding: 0,
+                location: 5,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Vertex, tangent) as u32
+            },
+            vk::VertexInputAttributeDescription{
+                binding: 0,
+                location: 6,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: offset_of!(Vertex, uv1) as u32
+            },
         ];
         
         &HOLDER
@@ -680,6 +978,7 @@ impl Default for GPUDrawPushConstants {
         Self{
             world_matrix:Matrix4::<f32>::identity(),
             vertex_buffer:vk::DeviceAddress::default(),
+            instance_buffer:vk::DeviceAddress::default(),
         }
     }
 }
@@ -0,0 +1,189 @@
+use crate::AAError;
+use crate::logger;
+use crate::errors::messages::MODEL_DENSITY;
+
+use super::Vertex;
+use super::mesh::CpuMesh;
+use super::mesh::CpuSurface;
+use super::mesh::CpuMaterial;
+use super::mesh::truncated_name;
+use super::mesh::generate_tangents;
+use super::optimize::VertexKey;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::fs;
+
+use nalgebra as na;
+use na::Vector3;
+use na::Vector4;
+
+// meshes, materials and the texture files the materials point to
+pub struct ObjModel {
+    pub meshes: Vec<CpuMesh>,
+    pub materials: Vec<CpuMaterial>,
+    pub textures: Vec<PathBuf>,
+    // mtl files the materials were read from
+    pub libraries: Vec<PathBuf>,
+}
+
+
+// objects sharing a name become the surfaces of a single mesh, tobj splits them by material
+pub fn read_obj<P: AsRef<Path>>(path:P) -> Result<ObjModel, AAError> {
+    let path = path.as_ref();
+    let options = tobj::LoadOptions{
+        triangulate: true,
+        single_index: false,
+        ignore_points: true,
+        ignore_lines: true,
+    };
+    let (models, materials) = tobj::load_obj(path, &options)?;
+    
+    let materials = match materials {
+        Ok(holder) => holder,
+        Err(error) => {
+            logger::various_log!("obj",
+                (logger::Warn, "{:?} materials not loaded {:?}", path, error)
+            );
+            Vec::new()
+        }
+    };
+    
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut textures:Vec<PathBuf> = Vec::new();
+    let materials = materials.iter().map(|material|{
+        let base_color = material.diffuse_texture.as_ref().map(|texture|{
+            let texture = base.join(texture);
+            match textures.iter().position(|known|known == &texture) {
+                Some(index) => index,
+                None => {
+                    textures.push(texture);
+                    textures.len() - 1
+                }
+            }
+        });
+        let diffuse = material.diffuse.unwrap_or([1.0, 1.0, 1.0]);
+        CpuMaterial{
+            color_factors: Vector4::new(diffuse[0], diffuse[1], diffuse[2], material.dissolve.unwrap_or(1.0)),
+            metal_rough_factors: Vector4::new(1.0, 0.5, 0.0, 0.0),
+            base_color,
+        }
+    }).collect();
+    
+    let mut meshes:Vec<CpuMesh> = Vec::new();
+    let mut dedups:Vec<HashMap<VertexKey, u32>> = Vec::new();
+    for model in models.iter() {
+        let name = truncated_name(&model.name);
+        let position = match meshes.iter().position(|mesh|mesh.name == name) {
+            Some(position) => position,
+            None => {
+                meshes.push(CpuMesh{name, ..CpuMesh::default()});
+                dedups.push(HashMap::new());
+                meshes.len() - 1
+            }
+        };
+        let mesh = &mut meshes[position];
+        let dedup = &mut dedups[position];
+        
+        // without vn every position gets the average of the faces around it, like an indexed gltf primitive
+        let generated_normals = if model.mesh.normal_indices.len() == model.mesh.indices.len() {
+            Vec::new()
+        } else {
+            smooth_normals(&model.mesh)
+        };
+        
+        let start_index = u32::try_from(mesh.indices.len()).expect(MODEL_DENSITY);
+        for corner in 0..model.mesh.indices.len() {
+            let vertex = read_vertex(&model.mesh, &generated_normals, corner);
+            let index = *dedup.entry(VertexKey::from(&vertex)).or_insert_with(||{
+                mesh.vertices.push(vertex);
+                u32::try_from(mesh.vertices.len() - 1).expect(MODEL_DENSITY)
+            });
+            mesh.indices.push(index);
+        }
+        mesh.surfaces.push(CpuSurface{
+            start_index,
+            count: u32::try_from(model.mesh.indices.len()).expect(MODEL_DENSITY),
+            material: model.mesh.material_id,
+        });
+    }
+    
+    // obj has no tangents, they are built once every object of the mesh is in
+    for mesh in meshes.iter_mut() {
+        generate_tangents(&mut mesh.indices, &mut mesh.vertices);
+    }
+    
+    logger::various_log!("obj",
+        (logger::Trace, "{:?} read {} meshes from {} objects", path, meshes.len(), models.len())
+    );
+    
+    Ok(ObjModel{
+        meshes,
+        materials,
+        textures,
+        libraries: material_libraries(path, base),
+    })
+}
+
+// tobj does not say which mtl files it opened, the mtllib lines do
+fn material_libraries(path:&Path, base:&Path) -> Vec<PathBuf> {
+    let Ok(text) = fs::read_to_string(path) else {return Vec::new()};
+    text.lines()
+        .filter_map(|line|line.trim_start().strip_prefix("mtllib"))
+        .flat_map(|names|names.split_whitespace())
+        .map(|name|base.join(name))
+        .collect()
+}
+
+fn read_vertex(mesh:&tobj::Mesh, generated_normals:&[Vector3<f32>], corner:usize) -> Vertex {
+    let position_index = usize::try_from(mesh.indices[corner]).expect(MODEL_DENSITY);
+    
+    let mut vertex = Vertex{
+        position: vector3_from_index(&mesh.positions, position_index),
+        color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+        ..Vertex::default()
+    };
+    
+    vertex.normal = match generated_normals.get(position_index) {
+        Some(&normal) => normal,
+        None => {
+            let normal_index = usize::try_from(mesh.normal_indices[corner]).expect(MODEL_DENSITY);
+            vector3_from_index(&mesh.normals, normal_index)
+        }
+    };
+    if let Some(&uv_index) = mesh.texcoord_indices.get(corner) {
+        let uv_index = usize::try_from(uv_index).expect(MODEL_DENSITY);
+        vertex.uv_x = mesh.texcoords[uv_index*2];
+        // obj has the origin of the texture at the bottom
+        vertex.uv_y = 1.0 - mesh.texcoords[uv_index*2+1];
+    }
+    // vertex colors follow the positions
+    if !mesh.vertex_color.is_empty() {
+        vertex.color = vector3_from_index(&mesh.vertex_color, position_index).push(1.0);
+    }
+    vertex
+}
+
+// face normals are added unnormalized, so bigger triangles weight more
+fn smooth_normals(mesh:&tobj::Mesh) -> Vec<Vector3<f32>> {
+    let mut normals = vec![Vector3::zeros(); mesh.positions.len()/3];
+    for triangle in mesh.indices.chunks_exact(3) {
+        let corners = [0, 1, 2].map(|corner|usize::try_from(triangle[corner]).expect(MODEL_DENSITY));
+        let [a, b, c] = corners.map(|index|vector3_from_index(&mesh.positions, index));
+        let normal = (b - a).cross(&(c - a));
+        for index in corners {
+            normals[index] += normal;
+        }
+    }
+    normals.into_iter().map(|normal|normal.try_normalize(f32::EPSILON).unwrap_or(Vector3::z())).collect()
+}
+
+#[inline(always)]
+fn vector3_from_index(vec:&[f32], index:usize) -> Vector3<f32> {
+    Vector3::new(
+        vec[index*3],
+        vec[index*3+1],
+        vec[index*3+2],
+    )
+}
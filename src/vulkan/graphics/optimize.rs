@@ -0,0 +1,382 @@
+use crate::logger;
+use crate::errors::messages::GRANTED;
+use crate::errors::messages::MODEL_DENSITY;
+
+use super::Vertex;
+use super::mesh::CpuMesh;
+
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ops::Range;
+
+use nalgebra as na;
+use na::Vector2;
+use na::Vector3;
+use na::Vector4;
+
+// entries of the post transform cache the triangle order is tuned for
+const CACHE_SIZE:usize = 32;
+// entries of the fifo the statistics are measured with, closer to what the hardware does
+const STATISTICS_CACHE_SIZE:usize = 16;
+// cache lines the vertex fetch statistics keep
+const FETCH_LINES:usize = 64;
+const FETCH_LINE_SIZE:usize = 64;
+// overdraw clusters are never shorter than this, in triangles
+const MIN_CLUSTER:usize = 32;
+// attributes are compared quantized, so float noise does not keep two vertices apart
+const WELD_SCALE:f32 = 10_000_000.0;
+
+// every attribute of the vertex, quantized
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VertexKey {
+    position: Vector3<i64>,
+    normal: Vector3<i64>,
+    uv: Vector2<i64>,
+    uv1: Vector2<i64>,
+    color: Vector4<i64>,
+    tangent: Vector4<i64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MeshStatistics {
+    vertices: usize,
+    // average cache misses per triangle, 0.5 is about the best a regular grid gets
+    acmr: f32,
+    // bytes fetched over the size of the vertices, 1.0 is every vertex read once
+    overfetch: f32,
+}
+
+
+// welds duplicates, then reorders the triangles of every surface for the vertex cache and overdraw,
+// and the vertices for fetch locality, the surfaces still draw the same triangles
+pub fn optimize_mesh(mesh:&mut CpuMesh) {
+    let before = MeshStatistics::measure(mesh);
+    
+    weld_vertices(mesh);
+    let surfaces:Vec<Range<usize>> = mesh.surfaces.iter().map(|surface|{
+        let start = usize::try_from(surface.start_index).expect(MODEL_DENSITY);
+        start..start+usize::try_from(surface.count).expect(MODEL_DENSITY)
+    }).collect();
+    for range in surfaces.into_iter() {
+        let indices = &mut mesh.indices[range];
+        optimize_vertex_cache(indices, mesh.vertices.len());
+        optimize_overdraw(indices, &mesh.vertices);
+    }
+    optimize_vertex_fetch(mesh);
+    
+    let after = MeshStatistics::measure(mesh);
+    logger::various_log!("optimize",
+        (logger::Debug, "mesh {} vertices {} -> {}, acmr {:.3} -> {:.3}, overfetch {:.3} -> {:.3}", mesh.name, before.vertices, after.vertices, before.acmr, after.acmr, before.overfetch, after.overfetch)
+    );
+}
+
+fn weld_vertices(mesh:&mut CpuMesh) {
+    let mut lookup:HashMap<VertexKey, u32> = HashMap::with_capacity(mesh.vertices.len());
+    let mut vertices = Vec::with_capacity(mesh.vertices.len());
+    let remap:Vec<u32> = mesh.vertices.iter().map(|vertex|{
+        *lookup.entry(VertexKey::from(vertex)).or_insert_with(||{
+            vertices.push(*vertex);
+            u32::try_from(vertices.len() - 1).expect(MODEL_DENSITY)
+        })
+    }).collect();
+    for index in mesh.indices.iter_mut() {
+        *index = remap[usize::try_from(*index).expect(MODEL_DENSITY)];
+    }
+    mesh.vertices = vertices;
+}
+
+// vertices get numbered in the order the indices first use them, the unused ones are dropped
+fn optimize_vertex_fetch(mesh:&mut CpuMesh) {
+    let mut remap = vec![u32::MAX; mesh.vertices.len()];
+    let mut vertices = Vec::with_capacity(mesh.vertices.len());
+    for index in mesh.indices.iter_mut() {
+        let old = usize::try_from(*index).expect(MODEL_DENSITY);
+        if remap[old] == u32::MAX {
+            remap[old] = u32::try_from(vertices.len()).expect(MODEL_DENSITY);
+            vertices.push(mesh.vertices[old]);
+        }
+        *index = remap[old];
+    }
+    mesh.vertices = vertices;
+}
+
+// Forsyth's linear speed optimization, recently used vertices and the ones with few triangles left score higher
+fn vertex_score(cache_position:Option<usize>, remaining:u32) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        // the last triangle is better left alone, strips don't map well to the cache
+        Some(position) if position < 3 => 0.75,
+        Some(position) => (1.0 - (position - 3) as f32/(CACHE_SIZE - 3) as f32).powf(1.5),
+        None => 0.0,
+    };
+    cache_score + 2.0*(remaining as f32).powf(-0.5)
+}
+
+fn optimize_vertex_cache(indices:&mut [u32], vertex_count:usize) {
+    let triangle_count = indices.len()/3;
+    if triangle_count < 2 {
+        return;
+    }
+    let corner = |triangle:usize, corner:usize|usize::try_from(indices[triangle*3+corner]).expect(MODEL_DENSITY);
+    
+    // triangles around each vertex, flattened with their offsets
+    let mut remaining = vec![0u32; vertex_count];
+    for &index in indices.iter() {
+        remaining[usize::try_from(index).expect(MODEL_DENSITY)] += 1;
+    }
+    let mut offsets = Vec::with_capacity(vertex_count+1);
+    offsets.push(0usize);
+    for &count in remaining.iter() {
+        offsets.push(offsets.last().expect(GRANTED) + usize::try_from(count).expect(MODEL_DENSITY));
+    }
+    let mut adjacency = vec![0usize; indices.len()];
+    let mut filled = offsets.clone();
+    for triangle in 0..triangle_count {
+        for position in 0..3 {
+            let vertex = corner(triangle, position);
+            adjacency[filled[vertex]] = triangle;
+            filled[vertex] += 1;
+        }
+    }
+    
+    let mut scores:Vec<f32> = remaining.iter().map(|&count|vertex_score(None, count)).collect();
+    let triangle_score = |scores:&[f32], triangle:usize|(0..3).map(|position|scores[corner(triangle, position)]).sum::<f32>();
+    
+    let mut emitted = vec![false; triangle_count];
+    let mut cache:Vec<usize> = Vec::with_capacity(CACHE_SIZE+3);
+    let mut output:Vec<u32> = Vec::with_capacity(indices.len());
+    let mut best:Option<usize> = (0..triangle_count).max_by(|&a, &b|triangle_score(&scores, a).total_cmp(&triangle_score(&scores, b)));
+    let mut cursor = 0;
+    
+    while output.len() < indices.len() {
+        // nothing in the cache has triangles left, the next unused one starts a new region
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                while emitted[cursor] {
+                    cursor += 1;
+                }
+                cursor
+            }
+        };
+        emitted[triangle] = true;
+        let corners = [0, 1, 2].map(|position|corner(triangle, position));
+        for &vertex in corners.iter() {
+            output.push(u32::try_from(vertex).expect(MODEL_DENSITY));
+            remaining[vertex] -= 1;
+        }
+        
+        let mut updated:Vec<usize> = corners.to_vec();
+        updated.extend(cache.iter().copied().filter(|vertex|!corners.contains(vertex)));
+        let evicted = updated.split_off(updated.len().min(CACHE_SIZE));
+        for &vertex in evicted.iter() {
+            scores[vertex] = vertex_score(None, remaining[vertex]);
+        }
+        for (position, &vertex) in updated.iter().enumerate() {
+            scores[vertex] = vertex_score(Some(position), remaining[vertex]);
+        }
+        cache = updated;
+        
+        best = None;
+        let mut best_score = f32::MIN;
+        for &vertex in cache.iter() {
+            for &candidate in adjacency[offsets[vertex]..offsets[vertex+1]].iter() {
+                if emitted[candidate] {
+                    continue;
+                }
+                let score = triangle_score(&scores, candidate);
+                if score > best_score {
+                    best_score = score;
+                    best = Some(candidate);
+                }
+            }
+        }
+    }
+    indices.copy_from_slice(&output);
+}
+
+// the cache ordered triangles are cut in clusters where the cache starts over, the clusters
+// facing away from the center go first so they hide the ones behind them
+fn optimize_overdraw(indices:&mut [u32], vertices:&[Vertex]) {
+    let triangle_count = indices.len()/3;
+    if triangle_count < MIN_CLUSTER*2 {
+        return;
+    }
+    let position = |index:u32|vertices[usize::try_from(index).expect(MODEL_DENSITY)].position;
+    
+    let mut clusters:Vec<Range<usize>> = Vec::new();
+    let mut cache:Vec<u32> = Vec::with_capacity(STATISTICS_CACHE_SIZE);
+    let mut start = 0;
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        let misses = corners.iter().filter(|index|!cache.contains(index)).count();
+        if misses == 3 && triangle - start >= MIN_CLUSTER {
+            clusters.push(start..triangle);
+            start = triangle;
+        }
+        for &index in corners.iter() {
+            simulate_fifo(&mut cache, index, STATISTICS_CACHE_SIZE);
+        }
+    }
+    clusters.push(start..triangle_count);
+    if clusters.len() < 2 {
+        return;
+    }
+    
+    let center = indices.iter().map(|&index|position(index)).sum::<Vector3<f32>>()/indices.len() as f32;
+    let mut keyed:Vec<(f32, Range<usize>)> = clusters.into_iter().map(|cluster|{
+        let corners = &indices[cluster.start*3..cluster.end*3];
+        let centroid = corners.iter().map(|&index|position(index)).sum::<Vector3<f32>>()/corners.len() as f32;
+        let normal = corners.chunks_exact(3).map(|triangle|{
+            (position(triangle[1]) - position(triangle[0])).cross(&(position(triangle[2]) - position(triangle[0])))
+        }).sum::<Vector3<f32>>();
+        let facing = normal.try_normalize(f32::EPSILON).map_or(0.0, |normal|(centroid - center).dot(&normal));
+        (facing, cluster)
+    }).collect();
+    keyed.sort_by(|a, b|b.0.total_cmp(&a.0));
+    
+    let output:Vec<u32> = keyed.into_iter()
+        .flat_map(|(_, cluster)|indices[cluster.start*3..cluster.end*3].to_vec())
+        .collect();
+    indices.copy_from_slice(&output);
+}
+
+// true on a miss
+fn simulate_fifo<T: PartialEq>(cache:&mut Vec<T>, entry:T, size:usize) -> bool {
+    if cache.contains(&entry) {
+        return false;
+    }
+    if cache.len() == size {
+        cache.remove(0);
+    }
+    cache.push(entry);
+    true
+}
+
+
+impl MeshStatistics {
+    fn measure(mesh:&CpuMesh) -> Self {
+        let triangles = (mesh.indices.len()/3).max(1);
+        let vertex_size = size_of::<Vertex>();
+        
+        let mut cache:Vec<u32> = Vec::with_capacity(STATISTICS_CACHE_SIZE);
+        let mut lines:Vec<usize> = Vec::with_capacity(FETCH_LINES);
+        let mut misses = 0usize;
+        let mut fetched = 0usize;
+        for &index in mesh.indices.iter() {
+            if !simulate_fifo(&mut cache, index, STATISTICS_CACHE_SIZE) {
+                continue;
+            }
+            misses += 1;
+            let start = usize::try_from(index).expect(MODEL_DENSITY)*vertex_size;
+            for line in start/FETCH_LINE_SIZE..=(start + vertex_size - 1)/FETCH_LINE_SIZE {
+                if simulate_fifo(&mut lines, line, FETCH_LINES) {
+                    fetched += FETCH_LINE_SIZE;
+                }
+            }
+        }
+        Self{
+            vertices: mesh.vertices.len(),
+            acmr: misses as f32/triangles as f32,
+            overfetch: fetched as f32/(mesh.vertices.len()*vertex_size).max(1) as f32,
+        }
+    }
+}
+
+
+impl From<&Vertex> for VertexKey {
+    fn from(base:&Vertex) -> Self {
+        let quantize = |component:f32|(component*WELD_SCALE) as i64;
+        Self{
+            position: base.position.map(quantize),
+            normal: base.normal.map(quantize),
+            uv: Vector2::new(base.uv_x, base.uv_y).map(quantize),
+            uv1: base.uv1.map(quantize),
+            color: base.color.map(quantize),
+            tangent: base.tangent.map(quantize),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::mesh::CpuSurface;
+    
+    const GRID:u32 = 12;
+    
+    // every triangle of a grid with its own three vertices, split in two surfaces
+    fn unwelded_grid() -> CpuMesh {
+        let mut mesh = CpuMesh::default();
+        for y in 0..GRID {
+            for x in 0..GRID {
+                let corner = |dx:u32, dy:u32|Vertex{
+                    position: Vector3::new((x + dx) as f32, (y + dy) as f32, 0.0),
+                    normal: Vector3::z(),
+                    ..Vertex::default()
+                };
+                for triangle in [[corner(0, 0), corner(1, 0), corner(1, 1)], [corner(0, 0), corner(1, 1), corner(0, 1)]] {
+                    for vertex in triangle {
+                        mesh.indices.push(u32::try_from(mesh.vertices.len()).unwrap());
+                        mesh.vertices.push(vertex);
+                    }
+                }
+            }
+        }
+        let half = u32::try_from(mesh.indices.len()/2).unwrap();
+        mesh.surfaces.push(CpuSurface{start_index:0, count:half, material:None});
+        mesh.surfaces.push(CpuSurface{start_index:half, count:half, material:Some(0)});
+        mesh
+    }
+    
+    // triangles of a surface by their positions, rotated to start at the smallest corner so the winding is kept
+    fn triangles(mesh:&CpuMesh, surface:&CpuSurface) -> Vec<[[i64; 3]; 3]> {
+        let start = usize::try_from(surface.start_index).unwrap();
+        let end = start + usize::try_from(surface.count).unwrap();
+        let mut holder:Vec<[[i64; 3]; 3]> = mesh.indices[start..end].chunks_exact(3).map(|triangle|{
+            let corners = [0, 1, 2].map(|corner|{
+                let position = mesh.vertices[usize::try_from(triangle[corner]).unwrap()].position;
+                [position.x, position.y, position.z].map(|component|(component*1000.0) as i64)
+            });
+            let first = (0..3).min_by_key(|&corner|corners[corner]).unwrap();
+            [0, 1, 2].map(|offset|corners[(first + offset)%3])
+        }).collect();
+        holder.sort();
+        holder
+    }
+    
+    #[test]
+    fn keeps_the_triangles_of_every_surface() {
+        let mut mesh = unwelded_grid();
+        let before:Vec<_> = mesh.surfaces.iter().map(|surface|triangles(&mesh, surface)).collect();
+        
+        optimize_mesh(&mut mesh);
+        
+        let after:Vec<_> = mesh.surfaces.iter().map(|surface|triangles(&mesh, surface)).collect();
+        assert_eq!(before, after);
+        assert_eq!(mesh.indices.len(), usize::try_from(GRID*GRID*6).unwrap());
+    }
+    
+    #[test]
+    fn welds_shared_corners() {
+        let mut mesh = unwelded_grid();
+        optimize_mesh(&mut mesh);
+        assert_eq!(mesh.vertices.len(), usize::try_from((GRID + 1)*(GRID + 1)).unwrap());
+        assert!(mesh.indices.iter().all(|&index|usize::try_from(index).unwrap() < mesh.vertices.len()));
+    }
+    
+    #[test]
+    fn orders_vertices_by_first_use() {
+        let mut mesh = unwelded_grid();
+        optimize_mesh(&mut mesh);
+        let mut next = 0;
+        for &index in mesh.indices.iter() {
+            assert!(index <= next);
+            if index == next {
+                next += 1;
+            }
+        }
+    }
+}
@@ -1,30 +1,38 @@
 use super::DrawContext;
 use super::VkGeoSurface;
-use super::VkMeshBuffers;
+use super::GeometryAllocation;
 use super::VkMeshAsset;
-use super::MaterialInstance;
+use super::MaterialHandle;
+use super::MeshHandle;
+use super::AssetManager;
+use super::Bounds;
+
+use crate::game::MeshRenderer;
+
+use crate::errors::messages::MODEL_DENSITY;
 
 
 use std::rc::Rc;
 
+use ash::vk;
 use nalgebra as na;
 use arrayvec::ArrayString;
-use derivative::Derivative;
-use ash::vk;
 
-#[derive(Derivative)]
-#[derivative(Debug)]
+#[derive(Debug)]
 pub struct RenderObject {
     pub index_count: u32,
     pub first_index: u32,
+    // added to gl_VertexIndex, the vertices of every mesh share the geometry buffer
+    pub vertex_offset: i32,
+    // picks the index buffer first_index points into
+    pub index_type: vk::IndexType,
     
-    pub index_buffer: vk::Buffer,
-    pub vertex_buffer_address: vk::DeviceAddress,
-    
-    #[derivative(Debug="ignore")]
-    pub material: Option<MaterialInstance>,
+    pub material: Option<MaterialHandle>,
     
     pub transform: na::Matrix4<f32>,
+    // multiplies the vertex color, lets instances of the same surface look different
+    pub tint: na::Vector4<f32>,
+    pub bounds: Bounds,
 }
 
 
@@ -40,7 +48,7 @@ struct Node {
 }
 
 struct MeshNode {
-    mesh: Rc<VkMeshAsset>,
+    mesh: MeshHandle,
     world_transform: na::Matrix4<f32>,
     sons: Vec<Rc<RenderableNode>>,
 }
@@ -49,33 +57,43 @@ struct MeshNode {
 pub struct MeshAssetMetadata {
     pub name: ArrayString<64>,
     pub surfaces: Vec<VkGeoSurface>,
-    pub meshes: Vec<VkMeshBuffers>,
+    pub geometry: GeometryAllocation,
 }
 
 
 
 // base class for a renderable dynamic object
+// the assets resolve the handles nodes keep instead of the meshes themselves
 pub trait IRenderable {
-    fn draw(&self, top_matrix:&na::Matrix4<f32>, ctx:&mut DrawContext);
+    fn draw(&self, top_matrix:&na::Matrix4<f32>, assets:&AssetManager, ctx:&mut DrawContext);
 }
 
 impl IRenderable for VkMeshAsset {
-    fn draw(&self, top_matrix:&na::Matrix4<f32>, ctx:&mut DrawContext) {
+    fn draw(&self, top_matrix:&na::Matrix4<f32>, _assets:&AssetManager, ctx:&mut DrawContext) {
+        self.draw_instance(top_matrix, &na::Vector4::new(1.0, 1.0, 1.0, 1.0), None, ctx);
+    }
+}
+
+impl VkMeshAsset {
+    // every call adds an instance, the draw context merges the ones sharing a surface
+    // a material override replaces the one of every surface
+    pub fn draw_instance(&self, top_matrix:&na::Matrix4<f32>, tint:&na::Vector4<f32>, material_override:Option<MaterialHandle>, ctx:&mut DrawContext) {
         
-        for (index, geo_surface) in self.surfaces.iter().enumerate() {
-            let first_index = geo_surface.start_index;
+        for geo_surface in self.surfaces.iter() {
+            let first_index = self.geometry.first_index + geo_surface.start_index;
             let index_count = geo_surface.count;
-            let index_buffer = self.meshes[index].index_buffer.underlying();
-            let vertex_buffer_address = self.meshes[index].vertex_buffer_address;
-            let material = geo_surface.material.clone();
+            let vertex_offset = i32::try_from(self.geometry.first_vertex).expect(MODEL_DENSITY);
+            let material = material_override.or(geo_surface.material);
             
             let render_object_holder = RenderObject{
                 first_index,
                 index_count,
-                index_buffer,
-                vertex_buffer_address,
+                vertex_offset,
+                index_type: self.geometry.index_type,
                 material,
                 transform: top_matrix.clone(),
+                tint: *tint,
+                bounds: self.bounds,
             };
             ctx.push(render_object_holder);
         }
@@ -83,21 +101,33 @@ impl IRenderable for VkMeshAsset {
 }
 
 impl IRenderable for Node {
-    fn draw(&self, top_matrix:&na::Matrix4<f32>, ctx:&mut DrawContext) {
+    fn draw(&self, top_matrix:&na::Matrix4<f32>, assets:&AssetManager, ctx:&mut DrawContext) {
         let node_matrix = top_matrix * self.world_transform;
         for son in &self.sons {
-            son.draw(&node_matrix, ctx);
+            son.draw(&node_matrix, assets, ctx);
         }
     }
 }
 
 
 impl IRenderable for MeshNode {
-    fn draw(&self, top_matrix:&na::Matrix4<f32>, ctx:&mut DrawContext) {
+    fn draw(&self, top_matrix:&na::Matrix4<f32>, assets:&AssetManager, ctx:&mut DrawContext) {
         let node_matrix = top_matrix * self.world_transform;
-        self.mesh.draw(&node_matrix, ctx);
+        // an unloaded mesh leaves its sons drawable
+        if let Some(mesh) = assets.meshes.get(self.mesh) {
+            mesh.draw(&node_matrix, assets, ctx);
+        }
         for son in &self.sons {
-            son.draw(&node_matrix, ctx);
+            son.draw(&node_matrix, assets, ctx);
+        }
+    }
+}
+
+// entities whose mesh is not streamed in yet or was unloaded draw nothing
+impl IRenderable for MeshRenderer {
+    fn draw(&self, top_matrix:&na::Matrix4<f32>, assets:&AssetManager, ctx:&mut DrawContext) {
+        if let Some(mesh) = assets.meshes.get(self.mesh) {
+            mesh.draw_instance(top_matrix, &self.tint, self.material, ctx);
         }
     }
 }
@@ -112,8 +142,8 @@ impl RenderableNode {
 }
 
 impl IRenderable for RenderableNode {
-    fn draw(&self, top_matrix:&na::Matrix4<f32>, ctx:&mut DrawContext) {
-        self.unwrap().draw(&top_matrix, ctx);
+    fn draw(&self, top_matrix:&na::Matrix4<f32>, assets:&AssetManager, ctx:&mut DrawContext) {
+        self.unwrap().draw(top_matrix, assets, ctx);
     }
 }
 
@@ -0,0 +1,758 @@
+use crate::AAError;
+use crate::logger;
+
+pub use crate::graphics::StreamProgress;
+pub use crate::graphics::StreamState;
+
+use super::mesh::CpuMesh;
+use super::mesh::CpuMaterial;
+use super::mesh::MeshView;
+use super::mesh::GltfFile;
+use super::mesh::open_gltf;
+use super::mesh::read_gltf_meshes;
+use super::mesh::read_gltf_materials;
+use super::obj::ObjModel;
+use super::obj::read_obj;
+use super::optimize::optimize_mesh;
+use super::bake::BakedMesh;
+use super::bake::BakedModel;
+use super::bake::BakeSource;
+use super::bake::read_baked;
+use super::bake::write_baked;
+use super::VkMeshAsset;
+use super::AssetManager;
+use super::SceneHandle;
+use super::MeshHandle;
+use super::MaterialHandle;
+use super::TextureHandle;
+use super::TextureKey;
+
+use super::VkDestructor;
+use super::VkDestructorArguments;
+use super::VInit;
+use super::Image;
+use super::super::UploadTicket;
+use super::super::Device;
+use super::super::Sampler;
+
+use super::super::materials::Materials;
+use super::super::materials::BindlessTables;
+use super::super::materials::MaterialInstance;
+use super::super::materials::MaterialPass;
+use super::super::materials::MaterialResources;
+use super::super::materials::MaterialConstants;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::fs;
+
+use ash::vk;
+
+// events handled per frame, keeps a big scene from stalling a single frame with uploads
+const EVENTS_PER_FRAME:usize = 8;
+
+#[derive(Debug)]
+pub struct CpuTexture {
+    pub extent: vk::Extent3D,
+    pub pixels: Vec<u32>,
+}
+
+enum StreamEvent {
+    Started{job:usize, meshes:usize, materials:Vec<CpuMaterial>, textures:usize},
+    Mesh{job:usize, mesh:StreamedMesh},
+    Texture{job:usize, texture:usize, key:TextureKey, image:CpuTexture},
+    TextureFailed{job:usize, texture:usize, error:String},
+    Finished{job:usize},
+    Failed{job:usize, error:String},
+}
+
+// parsed meshes own their arrays, baked ones read them from the mapped cache
+enum StreamedMesh {
+    Parsed(CpuMesh),
+    Baked(BakedMesh),
+}
+
+// what a source file parses into, a gltf stays open to decode its images
+struct ParsedModel {
+    meshes: Vec<CpuMesh>,
+    materials: Vec<CpuMaterial>,
+    textures: Vec<PathBuf>,
+    dependencies: Vec<PathBuf>,
+    gltf: Option<GltfFile>,
+}
+
+// the images of a gltf or the files an obj points to
+struct TextureSource<'a> {
+    path: &'a PathBuf,
+    gltf: Option<&'a GltfFile>,
+    files: &'a [PathBuf],
+}
+
+// formats the streamer can read, picked from the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModelFormat {
+    Gltf,
+    Obj,
+}
+
+// mesh whose buffers are still being copied, it is not drawable until the ticket is reached
+struct PendingMesh {
+    job: usize,
+    ticket: UploadTicket,
+    asset: VkMeshAsset,
+    materials: Vec<Option<usize>>,
+}
+
+// texture whose copy is still running, the materials using it are only written once the ticket is reached
+struct PendingTexture {
+    job: usize,
+    texture: usize,
+    ticket: UploadTicket,
+    handle: TextureHandle,
+}
+
+struct JobData {
+    // every asset the job creates belongs to the scene
+    scene: SceneHandle,
+    // surfaces waiting for their material, (model material, mesh, surface)
+    waiting: Vec<(usize, MeshHandle, usize)>,
+    cpu_materials: Vec<CpuMaterial>,
+    // by model material, textured ones only appear once their texture is resolved
+    materials: HashMap<usize, MaterialHandle>,
+    // the workers are done but some meshes or textures may still be uploading
+    finished: bool,
+}
+
+pub struct AssetStreamer {
+    sender: mpsc::Sender<StreamEvent>,
+    receiver: mpsc::Receiver<StreamEvent>,
+    progress: Vec<StreamProgress>,
+    jobs: Vec<JobData>,
+    pending: Vec<PendingMesh>,
+    pending_textures: Vec<PendingTexture>,
+    optimize_meshes: bool,
+    // read the baked caches and bake the models that have none
+    asset_cache: bool,
+}
+
+
+impl AssetStreamer {
+    pub fn new(optimize_meshes:bool, asset_cache:bool) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self{
+            sender,
+            receiver,
+            progress: Vec::new(),
+            jobs: Vec::new(),
+            pending: Vec::new(),
+            pending_textures: Vec::new(),
+            optimize_meshes,
+            asset_cache,
+        }
+    }
+    
+//----
+    // a file that is already loaded only gets one more user, anything else starts a job filling a new scene
+    pub fn load<P: AsRef<Path>>(&mut self, assets:&mut AssetManager, path:P) -> Result<SceneHandle, AAError> {
+        let path = path.as_ref();
+        ModelFormat::from_path(path).ok_or(AAError::UnsuportedFormat)?;
+        // the same file reached through another relative path is still the same scene
+        let path = fs::canonicalize(path).unwrap_or_else(|_|path.to_path_buf());
+        if let Some(scene) = assets.find_scene(&path) {
+            logger::various_log!("streaming",
+                (logger::Debug, "{:?} already loaded, {} users", path, assets.scenes.users(scene))
+            );
+            return Ok(scene);
+        }
+        let scene = assets.add_scene(path.clone());
+        self.request(path, scene);
+        Ok(scene)
+    }
+    
+    // parsing and image decoding happen on worker threads, the result is picked by poll_streaming
+    fn request(&mut self, path:PathBuf, scene:SceneHandle) -> usize {
+        let job = self.progress.len();
+        self.progress.push(StreamProgress::new(&path.to_string_lossy()));
+        self.jobs.push(JobData{
+            scene,
+            waiting: Vec::new(),
+            cpu_materials: Vec::new(),
+            materials: HashMap::new(),
+            finished: false,
+        });
+        
+        logger::various_log!("streaming",
+            (logger::Debug, "requested {:?} as job {}", path, job)
+        );
+        
+        let sender = self.sender.clone();
+        let settings = (self.optimize_meshes, self.asset_cache);
+        thread::spawn(move ||{
+            let result = stream_model(job, &path, settings, &sender);
+            let event = match result {
+                Ok(()) => StreamEvent::Finished{job},
+                Err(error) => StreamEvent::Failed{job, error:error.to_string()},
+            };
+            // the receiver is gone when the renderer shuts down before the job ends
+            let _ = sender.send(event);
+        });
+        job
+    }
+    
+    pub fn progress(&self) -> &[StreamProgress] {
+        &self.progress
+    }
+    
+    fn next_event(&self) -> Option<StreamEvent> {
+        self.receiver.try_recv().ok()
+    }
+    
+    // a failed job can still have copies running for what it sent before failing
+    fn is_uploading(&self, job:usize) -> bool {
+        self.pending.iter().any(|pending|pending.job == job) || self.pending_textures.iter().any(|pending|pending.job == job)
+    }
+}
+
+// what the jobs created is owned by the asset manager
+impl VkDestructor for AssetStreamer {
+    fn destruct(self, mut args:VkDestructorArguments) {
+        logger::destruct!("asset_streamer");
+        args.unwrap_none();
+    }
+}
+
+
+impl StreamedMesh {
+    fn view(&self) -> MeshView<'_> {
+        match self {
+            StreamedMesh::Parsed(mesh) => mesh.view(),
+            StreamedMesh::Baked(mesh) => mesh.view(),
+        }
+    }
+}
+
+impl ParsedModel {
+    fn bake_source(&self) -> BakeSource<'_> {
+        BakeSource{
+            meshes: &self.meshes,
+            materials: &self.materials,
+            textures: &self.textures,
+            dependencies: &self.dependencies,
+        }
+    }
+}
+
+impl TextureSource<'_> {
+    fn decode(&self, texture:usize) -> Result<CpuTexture, AAError> {
+        match self.gltf {
+            Some(gltf) => decode_bytes(&gltf.image_bytes(texture)?),
+            None => decode_bytes(&fs::read(self.files.get(texture).ok_or(AAError::DecodeError)?)?),
+        }
+    }
+    
+    // embedded images can only be shared by loads of the same file, image files by anything pointing at them
+    fn key(&self, texture:usize) -> TextureKey {
+        match (self.gltf, self.files.get(texture)) {
+            (None, Some(file)) => TextureKey{file:fs::canonicalize(file).unwrap_or_else(|_|file.clone()), image:0},
+            _ => TextureKey{file:self.path.clone(), image:texture},
+        }
+    }
+}
+
+
+impl ModelFormat {
+    fn from_path(path:&Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "glb" | "gltf" => Some(ModelFormat::Gltf),
+            "obj" => Some(ModelFormat::Obj),
+            _ => None,
+        }
+    }
+}
+
+
+//----
+// parses the models and writes their caches without streaming them, the offline side of the cache,
+// failures are logged and counted
+pub fn bake_models(models:&[PathBuf], optimize_meshes:bool) -> usize {
+    models.iter().filter(|&model|{
+        let result = ModelFormat::from_path(model).ok_or(AAError::UnsuportedFormat)
+            .and_then(|format|parse_model(model, format, optimize_meshes))
+            .and_then(|parsed|write_baked(model, optimize_meshes, &parsed.bake_source()));
+        match result {
+            Ok(baked) => {
+                logger::various_log!("streaming",
+                    (logger::Info, "{:?} baked into {:?}", model, baked)
+                );
+                false
+            }
+            Err(error) => {
+                logger::various_log!("streaming",
+                    (logger::Error, "{:?} could not be baked {}", model, error)
+                );
+                true
+            }
+        }
+    }).count()
+}
+
+// a usable cache skips the parsing and the optimization, without one the parsed model is baked for the next time
+fn stream_model(job:usize, path:&PathBuf, (optimize_meshes, asset_cache):(bool, bool), sender:&mpsc::Sender<StreamEvent>) -> Result<(), AAError> {
+    let format = ModelFormat::from_path(path).ok_or(AAError::UnsuportedFormat)?;
+    if asset_cache {
+        match read_baked(path, optimize_meshes) {
+            Ok(BakedModel{meshes, materials, textures}) => {
+                logger::various_log!("streaming",
+                    (logger::Debug, "{:?} read from its cache", path)
+                );
+                // gltf images are not baked, the file is only opened when a material needs one
+                let gltf = match format {
+                    ModelFormat::Gltf if materials.iter().any(|material|material.base_color.is_some()) => Some(open_gltf(path)?),
+                    _ => None,
+                };
+                let source = TextureSource{path, gltf:gltf.as_ref(), files:&textures};
+                return stream_meshes(job, meshes.into_iter().map(StreamedMesh::Baked).collect(), materials, sender, &source);
+            }
+            Err(error) => {
+                logger::various_log!("streaming",
+                    (logger::Debug, "{:?} not read from its cache {}", path, error)
+                );
+            }
+        }
+    }
+    
+    let parsed = parse_model(path, format, optimize_meshes)?;
+    if asset_cache {
+        match write_baked(path, optimize_meshes, &parsed.bake_source()) {
+            Ok(baked) => {
+                logger::various_log!("streaming",
+                    (logger::Debug, "{:?} baked into {:?}", path, baked)
+                );
+            }
+            Err(error) => {
+                logger::various_log!("streaming",
+                    (logger::Warn, "{:?} could not be baked {}", path, error)
+                );
+            }
+        }
+    }
+    let ParsedModel{meshes, materials, textures, gltf, ..} = parsed;
+    let source = TextureSource{path, gltf:gltf.as_ref(), files:&textures};
+    stream_meshes(job, meshes.into_iter().map(StreamedMesh::Parsed).collect(), materials, sender, &source)
+}
+
+fn parse_model(path:&Path, format:ModelFormat, optimize_meshes:bool) -> Result<ParsedModel, AAError> {
+    let mut holder = match format {
+        ModelFormat::Gltf => {
+            let gltf = open_gltf(path)?;
+            ParsedModel{
+                meshes: read_gltf_meshes(&gltf)?,
+                materials: read_gltf_materials(&gltf),
+                textures: Vec::new(),
+                dependencies: gltf.buffer_files(),
+                gltf: Some(gltf),
+            }
+        }
+        ModelFormat::Obj => {
+            let ObjModel{meshes, materials, textures, libraries} = read_obj(path)?;
+            ParsedModel{
+                meshes,
+                materials,
+                textures,
+                dependencies: libraries,
+                gltf: None,
+            }
+        }
+    };
+    if optimize_meshes {
+        holder.meshes.iter_mut().for_each(optimize_mesh);
+    }
+    Ok(holder)
+}
+
+// meshes are sent right away, the textures used by the materials are decoded on scoped workers
+fn stream_meshes(
+    job: usize,
+    meshes: Vec<StreamedMesh>,
+    materials: Vec<CpuMaterial>,
+    sender: &mpsc::Sender<StreamEvent>,
+    source: &TextureSource,
+) -> Result<(), AAError> {
+    let mut textures:Vec<usize> = materials.iter()
+        .filter_map(|material|material.base_color)
+        .collect();
+    textures.sort_unstable();
+    textures.dedup();
+    
+    let _ = sender.send(StreamEvent::Started{job, meshes:meshes.len(), materials, textures:textures.len()});
+    for mesh in meshes.into_iter() {
+        let _ = sender.send(StreamEvent::Mesh{job, mesh});
+    }
+    
+    let next = AtomicUsize::new(0);
+    let workers = thread::available_parallelism().map_or(1, |count|count.get()).min(textures.len());
+    thread::scope(|scope|{
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (textures, next) = (&textures, &next);
+            scope.spawn(move ||{
+                while let Some(&texture) = textures.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let event = match source.decode(texture) {
+                        Ok(image) => StreamEvent::Texture{job, texture, key:source.key(texture), image},
+                        Err(error) => StreamEvent::TextureFailed{job, texture, error:error.to_string()},
+                    };
+                    let _ = sender.send(event);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn decode_bytes(bytes:&[u8]) -> Result<CpuTexture, AAError> {
+    let rgba = image::load_from_memory(bytes)?.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    Ok(CpuTexture{
+        extent: vk::Extent3D{width, height, depth:1},
+        pixels: rgba.pixels().map(|pixel|u32::from_le_bytes(pixel.0)).collect(),
+    })
+}
+
+// the factors come from the file, the color image is the decoded texture or white when there is none
+fn write_streamed_material(
+    (device, bindless, materials):(&mut Device, &mut BindlessTables, &mut Materials),
+    material: &CpuMaterial,
+    color_image: &Image,
+    (white_texture, sampler):(&Image, &Sampler),
+) -> Result<MaterialInstance, AAError> {
+    let resources = MaterialResources{
+        constants: MaterialConstants{
+            color_factors: material.color_factors,
+            metal_rough_factors: material.metal_rough_factors,
+            ..MaterialConstants::opaque()
+        },
+        color_image,
+        color_sampler: sampler,
+        metal_image: white_texture,
+        metal_sampler: sampler,
+    };
+    materials.metalic.write_material(device, bindless, MaterialPass::MainColor, &resources)
+}
+
+// every material of the job using the texture, None for the untextured ones, the failed ones are only logged
+fn write_job_materials(
+    (device, bindless, materials):(&mut Device, &mut BindlessTables, &mut Materials),
+    (job, cpu_materials):(usize, &[CpuMaterial]),
+    texture: Option<usize>,
+    color_image: &Image,
+    (white_texture, sampler):(&Image, &Sampler),
+) -> Vec<(usize, MaterialInstance)> {
+    cpu_materials.iter().enumerate()
+        .filter(|(_, material)|material.base_color == texture)
+        .filter_map(|(index, material)|{
+            match write_streamed_material((device, bindless, materials), material, color_image, (white_texture, sampler)) {
+                Ok(instance) => Some((index, instance)),
+                Err(error) => {
+                    logger::various_log!("streaming",
+                        (logger::Warn, "material {} of job {} failed {:?}", index, job, error)
+                    );
+                    None
+                }
+            }
+        }).collect()
+}
+
+// the instances become assets of the job scene, then the surfaces that were waiting for them get them
+fn apply_materials(data:&mut JobData, assets:&mut AssetManager, written:Vec<(usize, MaterialInstance)>) {
+    let JobData{scene, waiting, materials, ..} = data;
+    for (index, instance) in written.into_iter() {
+        materials.insert(index, assets.add_material(*scene, instance));
+    }
+    waiting.retain(|&(material, mesh, surface_index)|{
+        let Some(&handle) = materials.get(&material) else {return true};
+        if let Some(asset) = assets.meshes.get_mut(mesh) {
+            asset.surfaces[surface_index].material = Some(handle);
+        }
+        false
+    });
+}
+
+
+impl VInit {
+    
+//----
+    // uploads what the workers finished, surfaces keep the default material until their own is resolved,
+    // failed uploads only fail their asset, the error is for a device that can't report progress anymore
+    pub fn poll_streaming(&mut self) -> Result<(), AAError> {
+        let VInit{
+            device,
+            allocator,
+            upload_manager,
+            geometry,
+            asset_streamer,
+            assets,
+            materials,
+            bindless,
+            white_texture,
+            fuzzy_sampler,
+            ..
+        } = self;
+        let (white_texture, fuzzy_sampler):(&Image, &Sampler) = (white_texture, fuzzy_sampler);
+        
+        let mut uploaded = Vec::new();
+        let mut uploaded_textures = Vec::new();
+        for _ in 0..EVENTS_PER_FRAME {
+            let Some(event) = asset_streamer.next_event() else {break};
+            match event {
+                StreamEvent::Started{job, meshes, materials:cpu_materials, textures} => {
+                    let progress = &mut asset_streamer.progress[job];
+                    progress.state = StreamState::Loading;
+                    progress.meshes_total = meshes;
+                    progress.textures_total = textures;
+                    
+                    // untextured materials only need their factors, they are ready before any mesh
+                    let data = &mut asset_streamer.jobs[job];
+                    let written = write_job_materials((device, bindless, materials), (job, &cpu_materials), None, white_texture, (white_texture, fuzzy_sampler));
+                    data.cpu_materials = cpu_materials;
+                    apply_materials(data, assets, written);
+                }
+                StreamEvent::Mesh{job, mesh} => {
+                    let mesh = mesh.view();
+                    match VkMeshAsset::upload(device, geometry, upload_manager, &mesh) {
+                        Ok(asset) => {
+                            let surface_materials = mesh.surfaces.iter().map(|surface|surface.material).collect();
+                            uploaded.push((job, asset, surface_materials));
+                        }
+                        Err(error) => {
+                            logger::various_log!("streaming",
+                                (logger::Warn, "mesh {} of job {} failed to upload {:?}", mesh.name, job, error)
+                            );
+                        }
+                    }
+                    asset_streamer.progress[job].meshes_loaded += 1;
+                }
+                StreamEvent::Texture{job, texture, key, image} => {
+                    let scene = asset_streamer.jobs[job].scene;
+                    // another scene may have uploaded the same image already, or still be uploading it
+                    let (handle, created) = match assets.share_texture(scene, &key) {
+                        Some(handle) => (Some(handle), false),
+                        None => {
+                            let name = format!("streamed texture {}", texture);
+                            match Image::create_texture(device, allocator, upload_manager, image.extent, Some(&name), &image.pixels) {
+                                Ok(holder) => (Some(assets.add_texture(scene, key, holder)), true),
+                                Err(error) => {
+                                    logger::various_log!("streaming",
+                                        (logger::Warn, "texture {} of job {} failed to upload {:?}", texture, job, error)
+                                    );
+                                    (None, false)
+                                }
+                            }
+                        }
+                    };
+                    
+                    let uploading = created || handle.is_some_and(|handle|uploaded_textures.iter().any(|&(_, _, uploaded)|uploaded == handle));
+                    let in_flight = handle.and_then(|handle|{
+                        asset_streamer.pending_textures.iter().find(|pending|pending.handle == handle).map(|pending|pending.ticket)
+                    });
+                    match (handle, in_flight) {
+                        (Some(handle), _) if uploading => {
+                            uploaded_textures.push((job, texture, handle));
+                        }
+                        (Some(handle), Some(ticket)) => {
+                            asset_streamer.pending_textures.push(PendingTexture{job, texture, ticket, handle});
+                        }
+                        // already sampled by another scene, or failed and left white
+                        (handle, _) => {
+                            let color_image = handle.and_then(|handle|assets.textures.get(handle)).unwrap_or(white_texture);
+                            let data = &mut asset_streamer.jobs[job];
+                            let written = write_job_materials((device, bindless, materials), (job, &data.cpu_materials), Some(texture), color_image, (white_texture, fuzzy_sampler));
+                            apply_materials(data, assets, written);
+                        }
+                    }
+                    asset_streamer.progress[job].textures_loaded += 1;
+                }
+                StreamEvent::TextureFailed{job, texture, error} => {
+                    logger::various_log!("streaming",
+                        (logger::Warn, "texture {} of job {} failed to decode {}", texture, job, error)
+                    );
+                    // the materials still get their factors over a white texture
+                    let data = &mut asset_streamer.jobs[job];
+                    let written = write_job_materials((device, bindless, materials), (job, &data.cpu_materials), Some(texture), white_texture, (white_texture, fuzzy_sampler));
+                    apply_materials(data, assets, written);
+                    asset_streamer.progress[job].textures_loaded += 1;
+                }
+                StreamEvent::Finished{job} => {
+                    logger::various_log!("streaming",
+                        (logger::Debug, "job {} finished", job)
+                    );
+                    asset_streamer.jobs[job].finished = true;
+                }
+                StreamEvent::Failed{job, error} => {
+                    logger::various_log!("streaming",
+                        (logger::Error, "job {} failed {}", job, error)
+                    );
+                    let progress = &mut asset_streamer.progress[job];
+                    progress.state = StreamState::Failed;
+                    progress.error = Some(error);
+                    // loading the path again retries instead of handing out the failed scene
+                    assets.forget_scene_path(asset_streamer.jobs[job].scene);
+                }
+            }
+        }
+        
+        if !uploaded.is_empty() || !uploaded_textures.is_empty() {
+            match upload_manager.flush(device) {
+                Ok(ticket) => {
+                    for (job, asset, materials) in uploaded.into_iter() {
+                        asset_streamer.pending.push(PendingMesh{job, ticket, asset, materials});
+                    }
+                    for (job, texture, handle) in uploaded_textures.into_iter() {
+                        asset_streamer.pending_textures.push(PendingTexture{job, texture, ticket, handle});
+                    }
+                }
+                Err(error) => {
+                    logger::various_log!("streaming",
+                        (logger::Error, "upload flush failed {:?}", error)
+                    );
+                    // nothing was submitted so the ranges are not in use
+                    for (_, asset, _) in uploaded.into_iter() {
+                        geometry.free(asset.geometry);
+                    }
+                    // the images stay with their scene but are never sampled, the materials keep the white texture
+                    for (job, texture, _) in uploaded_textures.into_iter() {
+                        let data = &mut asset_streamer.jobs[job];
+                        let written = write_job_materials((device, bindless, materials), (job, &data.cpu_materials), Some(texture), white_texture, (white_texture, fuzzy_sampler));
+                        apply_materials(data, assets, written);
+                    }
+                }
+            }
+        }
+        
+        let completed = upload_manager.completed(device)?;
+        let (ready, pending):(Vec<_>, Vec<_>) = std::mem::take(&mut asset_streamer.pending_textures).into_iter()
+            .partition(|pending|pending.ticket.is_reached(completed));
+        asset_streamer.pending_textures = pending;
+        
+        // the texture is in its final layout and owned by the graphics queue, the bindless slot can point at it now
+        for PendingTexture{job, texture, handle, ..} in ready.into_iter() {
+            let color_image = assets.textures.get(handle).unwrap_or(white_texture);
+            let data = &mut asset_streamer.jobs[job];
+            let written = write_job_materials((device, bindless, materials), (job, &data.cpu_materials), Some(texture), color_image, (white_texture, fuzzy_sampler));
+            apply_materials(data, assets, written);
+        }
+        
+        let (ready, pending):(Vec<_>, Vec<_>) = std::mem::take(&mut asset_streamer.pending).into_iter()
+            .partition(|pending|pending.ticket.is_reached(completed));
+        asset_streamer.pending = pending;
+        
+        for PendingMesh{job, mut asset, materials:surface_materials, ..} in ready.into_iter() {
+            let JobData{scene, waiting, cpu_materials, materials, ..} = &mut asset_streamer.jobs[job];
+            let mut unresolved = Vec::new();
+            for (surface_index, material) in surface_materials.into_iter().enumerate() {
+                // a material the file does not define keeps the default one
+                let Some(material) = material.filter(|&material|material < cpu_materials.len()) else {continue};
+                match materials.get(&material) {
+                    Some(&handle) => {
+                        asset.surfaces[surface_index].material = Some(handle);
+                    }
+                    None => {
+                        unresolved.push((material, surface_index));
+                    }
+                }
+            }
+            let mesh = assets.add_mesh(*scene, asset);
+            waiting.extend(unresolved.into_iter().map(|(material, surface_index)|(material, mesh, surface_index)));
+        }
+        
+        // a model is only done once its meshes and textures are usable, before that it can't be unloaded
+        let AssetStreamer{jobs, pending, pending_textures, progress, ..} = &mut **asset_streamer;
+        for (job, data) in jobs.iter_mut().enumerate() {
+            let uploading = pending.iter().any(|pending|pending.job == job) || pending_textures.iter().any(|pending|pending.job == job);
+            if data.finished && !uploading {
+                data.finished = false;
+                progress[job].state = StreamState::Done;
+            }
+        }
+        Ok(())
+    }
+
+//----
+    // runtime entry point for models, dropped files and the gui path end here,
+    // loading a file that is already loaded hands out the same scene
+    pub fn load_model<P: AsRef<Path>>(&mut self, path:P) -> Result<SceneHandle, AAError> {
+        let VInit{asset_streamer, assets, ..} = self;
+        asset_streamer.load(assets, path)
+    }
+    
+//----
+    // a bad path is only logged, the gui and dropped files have nowhere else to report it
+    pub fn open_model<P: AsRef<Path>>(&mut self, path:P) {
+        let path = path.as_ref();
+        if let Err(error) = self.load_model(path) {
+            logger::various_log!("streaming",
+                (logger::Warn, "can't open {:?} {:?}", path, error)
+            );
+        }
+    }
+    
+//----
+    // drops one user of the job scene, the assets left without users stop being drawn right away
+    // and are freed through the destruction stack of the last recorded frame, the only one that can still be reading them
+    pub fn unload_model(&mut self, job:usize) {
+        let cf = self.get_frame();
+        let VInit{
+            frames_data,
+            geometry,
+            asset_streamer,
+            assets,
+            mesh_index,
+            bindless,
+            ..
+        } = self;
+        
+        let Some(scene) = asset_streamer.jobs.get(job).map(|data|data.scene) else {return};
+        if asset_streamer.is_uploading(job) {
+            logger::various_log!("streaming",
+                (logger::Warn, "job {} can't be unloaded while its uploads are running", job)
+            );
+            return;
+        }
+        let progress = &mut asset_streamer.progress[job];
+        if !matches!(progress.state, StreamState::Done | StreamState::Failed) {
+            logger::various_log!("streaming",
+                (logger::Warn, "job {} can't be unloaded while {:?}", job, progress.state)
+            );
+            return;
+        }
+        
+        // the selection follows its mesh, or stays in place when that mesh is gone
+        let selected = assets.mesh_list().get(*mesh_index).map(|entry|entry.handle);
+        let before = assets.mesh_list().len();
+        let destruction_stack = frames_data.get_destruction_stack(cf);
+        if !assets.release_scene(scene, geometry, bindless, destruction_stack) {
+            logger::various_log!("streaming",
+                (logger::Debug, "job {} still has {} users", job, assets.scenes.users(scene))
+            );
+            return;
+        }
+        progress.state = StreamState::Unloaded;
+        
+        let mesh_list = assets.mesh_list();
+        *mesh_index = selected.and_then(|selected|mesh_list.iter().position(|entry|entry.handle == selected))
+            .unwrap_or((*mesh_index).min(mesh_list.len().saturating_sub(1)));
+        
+        let data = &mut asset_streamer.jobs[job];
+        data.waiting.clear();
+        data.materials.clear();
+        
+        logger::various_log!("streaming",
+            (logger::Debug, "job {} unloaded {} meshes", job, before - mesh_list.len())
+        );
+    }
+}
@@ -1,17 +1,13 @@
-use super::MaterialInstance;
+use super::MaterialHandle;
 use super::GeoSurface;
 use super::RenderObject;
 
 
-use derivative::Derivative;
-
-#[derive(Derivative, Default)]
-#[derivative(Debug)]
+#[derive(Debug, Default)]
 pub struct VkGeoSurface {
     pub start_index: u32,
     pub count: u32,
-    #[derivative(Debug="ignore")]
-    pub material: Option<MaterialInstance>,
+    pub material: Option<MaterialHandle>,
 }
 
 impl From<VkGeoSurface> for GeoSurface {
@@ -23,13 +19,24 @@ impl From<VkGeoSurface> for GeoSurface {
     }
 }
 
-/*
+// render objects of a frame, merged into instanced draws before recording
 #[derive(Default, Debug)]
 pub struct DrawContext {
-    pub context: Vec<RenderObject>,
+    objects: Vec<RenderObject>,
 }
-*/
 
-pub type DrawContext = Vec<RenderObject>;
+impl DrawContext {
+    pub fn push(&mut self, object:RenderObject) {
+        self.objects.push(object);
+    }
+    
+    pub fn clear(&mut self) {
+        self.objects.clear();
+    }
+    
+    pub fn iter(&self) -> std::slice::Iter<'_, RenderObject> {
+        self.objects.iter()
+    }
+}
 
 
@@ -1,7 +1,6 @@
 use crate::AAError;
 use crate::macros;
 use crate::logger;
-use crate::errors::messages::GPU_FREE;
 
 use super::VkDestructor;
 use super::VkDestructorType;
@@ -49,9 +48,10 @@ impl Buffer{
             None => "",
         };
         
-        let allocation = allocator.allocate(name, memory_requirements, location);
+        let allocation = allocator.allocate(name, memory_requirements, location)?;
         
         unsafe{device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset())}?;
+        device.set_object_name(buffer, name);
         
         Ok(Self{
             buffer,
@@ -88,7 +88,12 @@ impl Buffer{
     fn internal_destroy(mut self, device:&mut Device, allocator:&mut Allocator) {
         logger::destruct!("buffer");
         unsafe{device.destroy_buffer(self.buffer, None)};
-        unsafe{allocator.free(ManuallyDrop::into_inner(self.allocation)).expect(GPU_FREE)};
+        // teardown goes on after a lost device, a failed free only leaks
+        if let Err(error) = allocator.free(ManuallyDrop::into_inner(self.allocation)) {
+            logger::various_log!("buffer",
+                (logger::Error, "allocation not freed {:?}", error)
+            );
+        }
     }
 }
 
@@ -38,7 +38,7 @@ pub struct ComputeEffects {
 
 
 
-pub fn init_pipelines(device:&mut Device, ds_layout:&DescriptorLayout) -> ComputeEffects {
+pub fn init_pipelines(device:&mut Device, ds_layout:&DescriptorLayout) -> Result<ComputeEffects, AAError> {
     
     logger::various_log!("compute_pipeline",
         (logger::Warn, "Instancing simple compute effects pipeline")
@@ -49,7 +49,7 @@ pub fn init_pipelines(device:&mut Device, ds_layout:&DescriptorLayout) -> Comput
     let mut pipelines = Vec::new();
     let mut effect_name = ArrayString::new();
     
-    let gradient = CPipeline::create(device, ds_layout, constants::comp::GRADIENT_SHADER).unwrap();
+    let gradient = CPipeline::create(device, ds_layout, constants::comp::GRADIENT_SHADER)?;
     effect_name.push_str("gradient");
     logger::various_log!("compute_pipeline",
         (logger::Warn, "Instancing {} compute pipeline", effect_name)
@@ -66,7 +66,7 @@ pub fn init_pipelines(device:&mut Device, ds_layout:&DescriptorLayout) -> Comput
     effect_name.clear();
     
     
-    let gradient = CPipeline::create(device, ds_layout, constants::comp::COMP_SHADER).unwrap();
+    let gradient = CPipeline::create(device, ds_layout, constants::comp::COMP_SHADER)?;
     effect_name.push_str("square fade");
     logger::various_log!("compute_pipeline",
         (logger::Warn, "Instancing {} compute pipeline", effect_name)
@@ -87,7 +87,7 @@ pub fn init_pipelines(device:&mut Device, ds_layout:&DescriptorLayout) -> Comput
     logger::various_log!("compute_pipeline",
         (logger::Warn, "Instancing {} compute pipeline", effect_name)
     );
-    let sky = CPipeline::create(device, ds_layout, constants::comp::SKY_SHADER).unwrap();
+    let sky = CPipeline::create(device, ds_layout, constants::comp::SKY_SHADER)?;
     let push_constant_holder = ComputePushConstants([
         Vector4::new(0.0,0.0,0.0,0.0),
         Vector4::new(0.0,0.0,0.0,0.0),
@@ -99,11 +99,11 @@ pub fn init_pipelines(device:&mut Device, ds_layout:&DescriptorLayout) -> Comput
     pipelines.push(sky);
     
     
-    ComputeEffects{
+    Ok(ComputeEffects{
         names,
         push_constants,
         pipelines,
-    }
+    })
 }
 
 
@@ -120,15 +120,22 @@ impl VkDestructor for ComputeEffects {
 
 impl CPipeline {
     pub fn create(device:&mut Device, ds_layout:&DescriptorLayout, file:&str) -> Result<Self, AAError> {
+        let set_layout = ds_layout.underlying();
+        Self::create_raw(device, from_ref(&set_layout), vk_graphics::ComputePushConstants::size_u32(), file)
+    }
+    
+//----
+    // for compute passes that don't follow the effects layout, like the culling one
+    pub fn create_raw(device:&mut Device, set_layouts:&[vk::DescriptorSetLayout], push_size:u32, file:&str) -> Result<Self, AAError> {
         logger::create!("compute_pipeline");
         
         let push_constant_description = vk::PushConstantRange::builder()
-            .size(vk_graphics::ComputePushConstants::size_u32())
+            .size(push_size)
             .stage_flags(vk::ShaderStageFlags::COMPUTE);
         
         
         let layout_create_info = vk::PipelineLayoutCreateInfo::builder()
-            .set_layouts(from_ref(ds_layout))
+            .set_layouts(set_layouts)
             .push_constant_ranges(from_ref(&push_constant_description));
         
         let layout = unsafe{device.create_pipeline_layout(&layout_create_info, None)}?;
@@ -151,6 +158,8 @@ impl CPipeline {
         };
         
         unsafe{device.destroy_shader_module(compute_module, None)};
+        device.set_object_name(pipeline, file);
+        device.set_object_name(layout, &format!("{} layout", file));
         
         Ok(Self{
             layout,
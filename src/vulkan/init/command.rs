@@ -1,5 +1,4 @@
 use crate::AAError;
-use crate::errors::messages::SIMPLE_VK_FN;
 
 
 use crate::logger;
@@ -16,6 +15,7 @@ use ash::vk;
 pub struct CommandControl{
     pub pool: vk::CommandPool,
     s_u_buffer: vk::CommandBuffer,
+    s_u_fence: vk::Fence,
 }
 
 
@@ -37,9 +37,17 @@ impl CommandControl {
         
         let s_u_buffer = unsafe{device.allocate_command_buffers(&sb_create_info)}?;
         
+        let fence_create_info = vk::FenceCreateInfo::builder();
+        let s_u_fence = unsafe{device.create_fence(&fence_create_info, None)}?;
+        
+        device.set_object_name(command_pool, "immediate command pool");
+        device.set_object_name(s_u_buffer[0], "immediate command buffer");
+        device.set_object_name(s_u_fence, "immediate fence");
+        
         Ok(Self{
             pool: command_pool,
             s_u_buffer: s_u_buffer[0],
+            s_u_fence,
         })
     }
     
@@ -72,18 +80,19 @@ impl CommandControl {
     */
     
     
+    #[allow(dead_code)]
     pub fn run_su_buffer(&mut self, device:&mut Device, instant_command:&mut dyn FnMut(&mut Device, vk::CommandBuffer)->Result<(),AAError>) ->Result<(), AAError> {
         
-        unsafe{device.reset_command_buffer(self.s_u_buffer, vk::CommandBufferResetFlags::empty())}.expect(SIMPLE_VK_FN);
+        unsafe{device.reset_command_buffer(self.s_u_buffer, vk::CommandBufferResetFlags::empty())}?;
         
         let begin_info = ash::vk::CommandBufferBeginInfo::builder()
             .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
         
-        unsafe{device.begin_command_buffer(self.s_u_buffer, &begin_info)}.expect(SIMPLE_VK_FN);
+        unsafe{device.begin_command_buffer(self.s_u_buffer, &begin_info)}?;
         
         let holder = instant_command(device, self.s_u_buffer)?;
         
-        unsafe{device.end_command_buffer(self.s_u_buffer)}.expect(SIMPLE_VK_FN);
+        unsafe{device.end_command_buffer(self.s_u_buffer)}?;
         
         let submit_info = [
             vk::SubmitInfo::builder()
@@ -91,8 +100,10 @@ impl CommandControl {
                 .build(),
         ];
         
-        unsafe{device.queue_submit(device.queue_handles.graphics, &submit_info[..], vk::Fence::null())}.expect(SIMPLE_VK_FN);
-        unsafe{device.device_wait_idle()}.expect(SIMPLE_VK_FN);
+        // only waits for this submission instead of the whole device
+        unsafe{device.queue_submit(device.queue_handles.graphics, &submit_info[..], self.s_u_fence)}?;
+        unsafe{device.wait_for_fences(from_ref(&self.s_u_fence), true, u64::MAX)}?;
+        unsafe{device.reset_fences(from_ref(&self.s_u_fence))}?;
         
         Ok(holder)
     }
@@ -103,6 +114,7 @@ impl VkDestructor for CommandControl {
     fn destruct(self, mut args:VkDestructorArguments) {
         logger::destruct!("command_control");
         let device = args.unwrap_dev();
+        unsafe{device.destroy_fence(self.s_u_fence, None)};
         unsafe{device.destroy_command_pool(self.pool, None)};
     }
 }
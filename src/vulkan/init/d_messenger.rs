@@ -7,17 +7,44 @@ use super::instance::Instance;
 
 use std::ffi::CStr;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 
 use ash::vk;
 
+// set to anything and a run that hit a validation error exits with an error code
+pub const FAIL_ON_VALIDATION_ENV:&str = "ADASTRA_FAIL_ON_VALIDATION";
+// a message id is logged this many times, the repeats after that are only counted
+const REPEATS_LOGGED:u32 = 3;
+
+// the callback has no state of its own, the instance creation messages come before any messenger exists
+static VALIDATION_ERRORS:AtomicU64 = AtomicU64::new(0);
+static MESSAGE_LOG:Mutex<MessageLog> = Mutex::new(MessageLog{suppressed: Vec::new(), seen: None});
+
 
 pub struct DMessenger {
     pub debug_utils: ash::extensions::ext::DebugUtils,
     messenger: vk::DebugUtilsMessengerEXT,
 }
 
+struct MessageLog {
+    // message id names or numbers, decimal or 0x hex, that are never logged nor counted
+    suppressed: Vec<String>,
+    seen: Option<HashMap<String, u32>>,
+}
+
 impl DMessenger {
     
+//----
+    // has to run before the instance is created to filter its messages too
+    pub fn configure(suppressed:&[String]) {
+        let mut log = MESSAGE_LOG.lock().unwrap_or_else(|poisoned|poisoned.into_inner());
+        log.suppressed = suppressed.to_vec();
+    }
+    
+//----
     pub fn create(instance:&Instance) -> Result<Self, AAError> {
         logger::create!("debug_messenger");
         
@@ -25,17 +52,19 @@ impl DMessenger {
         let messenger = unsafe{debug_utils.create_debug_utils_messenger(&Self::populate_create_info(), None)?};
         
         Ok(Self{
-            debug_utils:debug_utils,
-            messenger:messenger,
+            debug_utils,
+            messenger,
         })
     }
     
     pub fn populate_create_info() -> vk::DebugUtilsMessengerCreateInfoEXT  {
         *vk::DebugUtilsMessengerCreateInfoEXT::builder()
+            // every severity is reported, the log filter decides what is shown
             .message_severity(
                 vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
                     | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                    //| vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
             )
             .message_type(
                 vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
@@ -69,10 +98,75 @@ impl DMessenger {
             CStr::from_ptr(callback_data.p_message).to_string_lossy()
         };
         
-        println!( "{message_severity:?}:\n{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n");
+        let mut log = MESSAGE_LOG.lock().unwrap_or_else(|poisoned|poisoned.into_inner());
+        if log.is_suppressed(&message_id_name, message_id_number) {
+            return vk::FALSE;
+        }
+        if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+            VALIDATION_ERRORS.fetch_add(1, Ordering::Relaxed);
+        }
+        
+        // messages without an id are told apart by their text
+        let key = match (message_id_number, message_id_name.is_empty()) {
+            (0, true) => message.to_string(),
+            _ => format!("{message_id_name} {message_id_number}"),
+        };
+        let seen = log.seen.get_or_insert_with(HashMap::new).entry(key).or_insert(0);
+        *seen += 1;
+        if *seen > REPEATS_LOGGED {
+            return vk::FALSE;
+        }
+        
+        // info is the layers talking about themselves, it stays below the default verbosity
+        let level = if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+            logger::Error
+        } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+            logger::Warn
+        } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+            logger::Debug
+        } else {
+            logger::Trace
+        };
+        let hidden = if *seen == REPEATS_LOGGED {" (further repeats hidden)"} else {""};
+        logger::various_log!("validation",
+            (level, "{:?} [{} ({:#x})] {}{}", message_type, message_id_name, message_id_number, message, hidden)
+        );
         vk::FALSE
     }
     
+//----
+    // validation errors so far in the process, suppressed ones are not counted
+    pub fn validation_errors() -> u64 {
+        VALIDATION_ERRORS.load(Ordering::Relaxed)
+    }
+    
+//----
+    pub fn fail_on_validation() -> bool {
+        std::env::var_os(FAIL_ON_VALIDATION_ENV).is_some()
+    }
+    
+}
+
+impl MessageLog {
+    fn is_suppressed(&self, id_name:&str, id_number:i32) -> bool {
+        self.suppressed.iter().any(|entry|{
+            let number = match entry.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().map(|value|value as i32),
+                None => entry.parse::<i32>().ok(),
+            };
+            entry == id_name || number == Some(id_number)
+        })
+    }
+    
+    // the repeats that were hidden, once the messenger goes away
+    fn report_repeats(&mut self) {
+        let Some(seen) = self.seen.take() else {return};
+        for (key, count) in seen.into_iter().filter(|(_, count)|*count > REPEATS_LOGGED) {
+            logger::various_log!("validation",
+                (logger::Info, "[{}] repeated {} times", key, count)
+            );
+        }
+    }
 }
 
 impl VkDestructor for DMessenger {
@@ -80,5 +174,6 @@ impl VkDestructor for DMessenger {
         logger::destruct!("debug_messenger");
         args.unwrap_none();
         unsafe{self.debug_utils.destroy_debug_utils_messenger(self.messenger, None)};
+        MESSAGE_LOG.lock().unwrap_or_else(|poisoned|poisoned.into_inner()).report_repeats();
     }
 }
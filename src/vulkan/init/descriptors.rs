@@ -3,7 +3,6 @@ use crate::macros;
 use crate::logger;
 use crate::errors::messages::STANDARD_CONV;
 use crate::errors::messages::GRANTED;
-use crate::errors::messages::SIMPLE_VK_FN;
 
 use super::VkDestructor;
 use super::VkDestructorArguments;
@@ -23,6 +22,7 @@ const DESCRIPTOR_TYPE_COUNT:usize = 17;
 
 pub struct DescriptorLayoutBuilder {
     bindings: Vec<vk::DescriptorSetLayoutBinding>,
+    binding_flags: Vec<vk::DescriptorBindingFlags>,
     type_count: DescriptorPoolCount,
 }
 
@@ -44,30 +44,33 @@ pub struct DescriptorPoolAllocator {
     pool: vk::DescriptorPool,
 }
 
-pub fn init_descriptors(device:&mut Device, render_image:&Image) -> (GDescriptorAllocator, vk::DescriptorSet, DescriptorLayout, DescriptorLayout) {
+pub fn init_descriptors(device:&mut Device, render_image:&Image) -> Result<(GDescriptorAllocator, vk::DescriptorSet, DescriptorLayout, DescriptorLayout), AAError> {
     //logger::init();
     
     let mut ds_layout_builder = DescriptorLayoutBuilder::create();
     ds_layout_builder.add_binding(0, vk::DescriptorType::STORAGE_IMAGE, 1);
-    let (background_image_descriptor_layout, _types_in_layout) = ds_layout_builder.build(device, vk::ShaderStageFlags::COMPUTE).unwrap();
+    let (background_image_descriptor_layout, _types_in_layout) = ds_layout_builder.build(device, vk::ShaderStageFlags::COMPUTE)?;
     
     let mut ds_layout_builder = DescriptorLayoutBuilder::create();
     ds_layout_builder.add_binding(0, vk::DescriptorType::UNIFORM_BUFFER, 1);
     ds_layout_builder.add_binding(1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1);
     ds_layout_builder.add_binding(2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1);
-    let (texture_descriptor_layout, image_types_in_layout) = ds_layout_builder.build(device, vk::ShaderStageFlags::FRAGMENT).unwrap();
+    let (texture_descriptor_layout, image_types_in_layout) = ds_layout_builder.build(device, vk::ShaderStageFlags::FRAGMENT)?;
     
     //types_in_layout += image_types_in_layout;
     
-    let mut gds_pool: GDescriptorAllocator = GDescriptorAllocator::create(device, image_types_in_layout).unwrap();
+    let mut gds_pool: GDescriptorAllocator = GDescriptorAllocator::create(device, image_types_in_layout)?;
     
     //types_in_layout *= 10;//allocate 10 DS
     //let mut ds_pool = DescriptorPoolAllocator::create(device, types_in_layout).unwrap();
     //let background_image_ds = ds_pool.allocate(device, storage_descriptor_layout).unwrap();
     
-    let background_image_ds = gds_pool.allocate(device, &background_image_descriptor_layout).unwrap();
+    let background_image_ds = gds_pool.allocate(device, &background_image_descriptor_layout)?;
+    device.set_object_name(background_image_descriptor_layout.underlying(), "background image layout");
+    device.set_object_name(texture_descriptor_layout.underlying(), "texture layout");
+    device.set_object_name(background_image_ds, "background image set");
     let mut writer = DescriptorWriter::default();
-    writer.write_image(0, render_image.view, vk::Sampler::null(), vk::ImageLayout::GENERAL, vk::DescriptorType::STORAGE_IMAGE);
+    writer.write_tracked_image(0, render_image, vk::Sampler::null(), vk::ImageLayout::GENERAL, vk::DescriptorType::STORAGE_IMAGE);
     writer.update_set(device, background_image_ds);
     
     /*
@@ -77,7 +80,7 @@ pub fn init_descriptors(device:&mut Device, render_image:&Image) -> (GDescriptor
     writer.update_set(device, background_image_ds);
     */
     
-    (gds_pool, background_image_ds, background_image_descriptor_layout, texture_descriptor_layout)
+    Ok((gds_pool, background_image_ds, background_image_descriptor_layout, texture_descriptor_layout))
     //(gds_pool, storage_descriptor_layout, background_image_ds, None)
 }
 
@@ -87,21 +90,29 @@ impl DescriptorLayoutBuilder {
         //logger::dlb::create();
         Self{
             bindings: Vec::new(),
+            binding_flags: Vec::new(),
             type_count: DescriptorPoolCount::default(),
         }
     }
     
     pub fn add_binding(&mut self, binding:u32, d_type:vk::DescriptorType, count:u32) {
+        self.add_flagged_binding(binding, d_type, count, vk::DescriptorBindingFlags::empty());
+    }
+    
+    // descriptor indexing bindings, an UPDATE_AFTER_BIND flag makes the whole layout update after bind
+    pub fn add_flagged_binding(&mut self, binding:u32, d_type:vk::DescriptorType, count:u32, flags:vk::DescriptorBindingFlags) {
         let mut holder = vk::DescriptorSetLayoutBinding::default();
         holder.binding = binding;
         holder.descriptor_count = count;
         holder.descriptor_type = d_type;
         self.bindings.push(holder);
+        self.binding_flags.push(flags);
         self.type_count.add_type_count(d_type, count);
     }
     
     pub fn reset(&mut self) {
         self.bindings.clear();
+        self.binding_flags.clear();
     }
     
     pub fn build(&mut self, device:&mut Device, shader_stage:vk::ShaderStageFlags) -> Result<(DescriptorLayout, DescriptorPoolCount), AAError> {
@@ -110,15 +121,26 @@ impl DescriptorLayoutBuilder {
             binding.stage_flags |= shader_stage;
         }
         
-        let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+        let mut flags_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder()
+            .binding_flags(&self.binding_flags[..]);
+        
+        let mut create_info = vk::DescriptorSetLayoutCreateInfo::builder()
             .bindings(&self.bindings[..]);
         
+        if self.binding_flags.iter().any(|flags|!flags.is_empty()) {
+            if self.binding_flags.iter().any(|flags|flags.contains(vk::DescriptorBindingFlags::UPDATE_AFTER_BIND)) {
+                create_info = create_info.flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL);
+            }
+            create_info = create_info.push_next(&mut flags_info);
+        }
+        
         let holder_layout = DescriptorLayout{
             set_layout: unsafe{device.create_descriptor_set_layout(&create_info, None)}?,
         };
         let holder_count = self.type_count.clone();
         
         self.bindings.clear();
+        self.binding_flags.clear();
         self.type_count = DescriptorPoolCount::default();
         
         Ok((holder_layout, holder_count))
@@ -392,18 +414,21 @@ impl<const UPPER_LIMIT_PER_POOL:u32, const INITIAL_GROUPS:u32> GDescriptorAlloca
             .pool_sizes(&ratios[..]);
             
         
-        unsafe{device.create_descriptor_pool(&descriptor_pool_ci, None)}.map_err(|error|error.into())
+        let pool = unsafe{device.create_descriptor_pool(&descriptor_pool_ci, None)}?;
+        device.set_object_name(pool, "growable descriptor pool");
+        Ok(pool)
     } 
     
-    pub fn clear_pools(&mut self, device:&Device) {
+    pub fn clear_pools(&mut self, device:&Device) -> Result<(), AAError> {
         for pool in &self.ready_pools {
-            unsafe{device.reset_descriptor_pool(*pool, vk::DescriptorPoolResetFlags::empty())}.expect(SIMPLE_VK_FN);
+            unsafe{device.reset_descriptor_pool(*pool, vk::DescriptorPoolResetFlags::empty())}?;
         }
         for pool in self.full_pools.iter() {
-            unsafe{device.reset_descriptor_pool(*pool, vk::DescriptorPoolResetFlags::empty())}.expect(SIMPLE_VK_FN);
+            unsafe{device.reset_descriptor_pool(*pool, vk::DescriptorPoolResetFlags::empty())}?;
             self.ready_pools.push(*pool);
         }
         self.full_pools.clear();
+        Ok(())
     }
     
     pub fn allocate(&mut self, device:&mut Device, layout:&vk::DescriptorSetLayout) -> Result<vk::DescriptorSet, AAError> {
@@ -493,7 +518,13 @@ impl DescriptorWriter {
     }
     
     
+    #[allow(dead_code)]
     pub fn write_image(&mut self, binding:u32, image:vk::ImageView, sampler:vk::Sampler, layout:vk::ImageLayout, descriptor_type:vk::DescriptorType) {
+        self.write_image_element(binding, 0, image, sampler, layout, descriptor_type);
+    }
+    
+    // writes a single element of an array binding
+    pub fn write_image_element(&mut self, binding:u32, element:u32, image:vk::ImageView, sampler:vk::Sampler, layout:vk::ImageLayout, descriptor_type:vk::DescriptorType) {
         
         let descriptor_image_info = vk::DescriptorImageInfo::builder()
             .sampler(sampler)
@@ -504,6 +535,7 @@ impl DescriptorWriter {
         
         let write_ds = vk::WriteDescriptorSet::builder()
             .dst_binding(binding)
+            .dst_array_element(element)
             .dst_set(vk::DescriptorSet::null())
             .descriptor_type(descriptor_type)
             .image_info(from_ref(&self.image_infos.last().expect(GRANTED))); //was inserted above so should be valid
@@ -512,6 +544,37 @@ impl DescriptorWriter {
         
     }
     
+    // same as write_image but in debug builds checks the layout against the one tracked by the image
+    pub fn write_tracked_image(&mut self, binding:u32, image:&Image, sampler:vk::Sampler, layout:vk::ImageLayout, descriptor_type:vk::DescriptorType) {
+        self.write_tracked_image_element(binding, 0, image, sampler, layout, descriptor_type);
+    }
+    
+    pub fn write_tracked_image_element(&mut self, binding:u32, element:u32, image:&Image, sampler:vk::Sampler, layout:vk::ImageLayout, descriptor_type:vk::DescriptorType) {
+        if cfg!(debug_assertions) {
+            Self::check_layout(binding, image, layout, descriptor_type);
+        }
+        self.write_image_element(binding, element, image.view, sampler, layout, descriptor_type);
+    }
+    
+    fn check_layout(binding:u32, image:&Image, layout:vk::ImageLayout, descriptor_type:vk::DescriptorType) {
+        let expected = Self::expected_layout(image.layout(), descriptor_type);
+        if layout != expected {
+            logger::various_log!("descriptors",
+                (logger::Error, "binding {} written as {:?} but the image is expected in {:?}", binding, layout, expected)
+            );
+        }
+    }
+    
+    // storage images get moved to GENERAL by the render graph right before a pass uses them,
+    // until then they keep whatever layout they were tracked in
+    fn expected_layout(tracked:vk::ImageLayout, descriptor_type:vk::DescriptorType) -> vk::ImageLayout {
+        match descriptor_type {
+            vk::DescriptorType::STORAGE_IMAGE => vk::ImageLayout::GENERAL,
+            _ => tracked,
+        }
+    }
+    
+    #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.image_infos.clear();
         self.buffer_infos.clear();
@@ -527,4 +590,20 @@ impl DescriptorWriter {
 }
 
 
-
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn storage_write_of_a_new_image_is_valid() {
+        // what init_descriptors writes for the render image, which is still tracked as UNDEFINED
+        assert_eq!(DescriptorWriter::expected_layout(vk::ImageLayout::UNDEFINED, vk::DescriptorType::STORAGE_IMAGE), vk::ImageLayout::GENERAL);
+        assert_eq!(DescriptorWriter::expected_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL, vk::DescriptorType::STORAGE_IMAGE), vk::ImageLayout::GENERAL);
+    }
+    
+    #[test]
+    fn sampled_writes_follow_the_tracked_layout() {
+        assert_eq!(DescriptorWriter::expected_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::DescriptorType::COMBINED_IMAGE_SAMPLER), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(DescriptorWriter::expected_layout(vk::ImageLayout::UNDEFINED, vk::DescriptorType::SAMPLED_IMAGE), vk::ImageLayout::UNDEFINED);
+    }
+}
@@ -13,12 +13,16 @@ use super::p_device::QueueFamilyIndices;
 
 use std::collections::HashSet;
 use std::ffi::CStr;
+use std::ffi::CString;
 use std::ffi::c_char;
 
 use ash::vk;
+use ash::extensions::ext::DebugUtils;
 
 pub struct Device {
     device: ash::Device,
+    // only there with validation, names and labels are skipped without it
+    debug_utils: Option<DebugUtils>,
     pub queue_handles: QueueHandles,
 }
 macros::impl_deref!(Device, ash::Device, device);
@@ -27,6 +31,8 @@ macros::impl_underlying!(Device, ash::Device, device);
 pub struct QueueHandles {
     pub graphics: vk::Queue,
     pub presentation: vk::Queue,
+    // same as graphics when there is no dedicated transfer family
+    pub transfer: vk::Queue,
 }
 
 
@@ -41,6 +47,9 @@ impl Device {
         let mut queue_set:HashSet<u32> = HashSet::new();
         queue_set.insert(p_device.queues.graphics_family);
         queue_set.insert(p_device.queues.present_family);
+        if let Some(transfer_family) = p_device.queues.transfer_family {
+            queue_set.insert(transfer_family);
+        }
         
         for elem in queue_set {
             let holder = vk::DeviceQueueCreateInfo::builder()
@@ -61,13 +70,21 @@ impl Device {
         let mut synchronization2 = vk::PhysicalDeviceSynchronization2Features::builder()
             .synchronization2(true);
         
-        let mut buffer_device_address = vk::PhysicalDeviceBufferDeviceAddressFeatures::builder()
+        // the 1.2 core struct can't be chained next to the per extension ones, everything from 1.2 goes here
+        let mut vulkan12_features = vk::PhysicalDeviceVulkan12Features::builder()
             .buffer_device_address(true)
             .buffer_device_address_capture_replay(true)
-            .buffer_device_address_multi_device(true);
-        
-        
-        let mut descriptor_indexing = vk::PhysicalDeviceDescriptorIndexingFeatures::builder();
+            .buffer_device_address_multi_device(true)
+            .timeline_semaphore(true)
+            // what the bindless tables need
+            .descriptor_indexing(true)
+            .runtime_descriptor_array(true)
+            .descriptor_binding_partially_bound(true)
+            .descriptor_binding_variable_descriptor_count(true)
+            .descriptor_binding_sampled_image_update_after_bind(true)
+            .shader_sampled_image_array_non_uniform_indexing(true)
+            // the draw count of the gpu driven path is written by the cull shader
+            .draw_indirect_count(true);
         
         
         let device_create_info = vk::DeviceCreateInfo::builder()
@@ -76,8 +93,7 @@ impl Device {
             .enabled_extension_names(&extensions)
             .push_next(&mut dynamic_rendering)
             .push_next(&mut synchronization2)
-            .push_next(&mut buffer_device_address)
-            .push_next(&mut descriptor_indexing);
+            .push_next(&mut vulkan12_features);
         
         
         /*
@@ -86,20 +102,63 @@ impl Device {
         
         let device = unsafe{instance.create_device(p_device.underlying(), &device_create_info, None)}?;
         let queue_handles = Self::get_queue_handles(&device, &p_device.queues);
+        let debug_utils = instance.debug_utils.then(||DebugUtils::new(&instance.entry, instance));
         
         
-        Ok(Self{
+        let holder = Self{
             device: device,
+            debug_utils,
             queue_handles: queue_handles
-        })
+        };
+        // the queues can be the same one, graphics goes last so a shared queue keeps that name
+        holder.set_object_name(holder.queue_handles.transfer, "transfer queue");
+        holder.set_object_name(holder.queue_handles.presentation, "presentation queue");
+        holder.set_object_name(holder.queue_handles.graphics, "graphics queue");
+        Ok(holder)
+    }
+    
+//----
+    // the name shows up in validation messages and captures
+    pub fn set_object_name<H: vk::Handle>(&self, handle:H, name:&str) {
+        let Some(debug_utils) = self.debug_utils.as_ref().filter(|_|!name.is_empty()) else {return};
+        let Ok(name) = CString::new(name) else {return};
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(H::TYPE)
+            .object_handle(handle.as_raw())
+            .object_name(&name);
+        if let Err(error) = unsafe{debug_utils.set_debug_utils_object_name(self.device.handle(), &name_info)} {
+            logger::various_log!("device",
+                (logger::Debug, "can't name {:?} {} {:?}", H::TYPE, name.to_string_lossy(), error)
+            );
+        }
+    }
+    
+//----
+    // every begin needs its end on the same command buffer
+    pub fn begin_label(&self, cmd:vk::CommandBuffer, name:&str) {
+        let Some(debug_utils) = self.debug_utils.as_ref() else {return};
+        let name = CString::new(name).unwrap_or_default();
+        let label = vk::DebugUtilsLabelEXT::builder()
+            .label_name(&name);
+        unsafe{debug_utils.cmd_begin_debug_utils_label(cmd, &label)};
+    }
+    
+    pub fn end_label(&self, cmd:vk::CommandBuffer) {
+        let Some(debug_utils) = self.debug_utils.as_ref() else {return};
+        unsafe{debug_utils.cmd_end_debug_utils_label(cmd)};
     }
     
     fn get_queue_handles(device:&ash::Device, queue_indices:&QueueFamilyIndices) -> QueueHandles {
-        let graphics = unsafe{device.get_device_queue(0, queue_indices.graphics_family)};
-        let presentation = unsafe{device.get_device_queue(0, queue_indices.present_family)};
+        let graphics = unsafe{device.get_device_queue(queue_indices.graphics_family, 0)};
+        let presentation = unsafe{device.get_device_queue(queue_indices.present_family, 0)};
+        let transfer = match queue_indices.transfer_family {
+            Some(family) => unsafe{device.get_device_queue(family, 0)},
+            None => graphics,
+        };
         QueueHandles{
             graphics: graphics,
             presentation: presentation,
+            transfer,
         }
     }
     
@@ -1,8 +1,6 @@
 use crate::AAError;
 use crate::macros;
 use crate::logger;
-use crate::errors::messages::GPU_FREE;
-use crate::errors::messages::CPU_ACCESIBLE;
 
 use super::VkDestructor;
 use super::VkDestructorType;
@@ -11,9 +9,11 @@ use super::VkDynamicDestructor;
 use super::VkDestructorArguments;
 use super::Device;
 use super::Allocator;
-use super::CommandControl;
+use super::UploadManager;
 use super::memory;
-use super::Buffer;
+
+use super::super::render_graph::Access;
+use super::super::render_graph::ResourceState;
 
 use std::slice::from_ref;
 use std::mem::ManuallyDrop;
@@ -28,11 +28,16 @@ pub struct Image {
     pub extent: vk::Extent3D,
     pub extent_2d: vk::Extent2D,
     pub format: vk::Format,
+    pub aspect: vk::ImageAspectFlags,
+    mip_levels: u32,
+    array_layers: u32,
+    // indexed by layer * mip_levels + mip, moved out before the bitwise copy in defered_destruct
+    states: Vec<ResourceState>,
 }
 
 macros::impl_underlying!(Image, vk::Image, image);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ImageMetadata {
     d_name: Option<&'static str>,
     pub format: vk::Format,
@@ -77,12 +82,35 @@ pub const TEXTURE:ImageMetadata = {
     }
 };
 
+pub const CAPTURE:ImageMetadata = {
+    use vk::ImageUsageFlags as IUF;
+    use vk::ImageAspectFlags as IAF;
+    ImageMetadata{
+        d_name: Some("CAPTURE IMAGE"),
+        format: vk::Format::R16G16B16A16_SFLOAT,
+        usage: IUF::from_raw(0x03),
+        //IUF::TRANSFER_SRC | IUF::TRANSFER_DST
+        aspect_flags: IAF::COLOR,
+    }
+};
+
 impl ImageMetadata {
     pub fn texture(name:&'static str) -> Self {
         let mut holder = TEXTURE.clone();
         holder.d_name = Some(name);
         holder
     }
+    
+    // scaled copy of an image being captured, it keeps the format of the source
+    pub fn capture(format:vk::Format) -> Self {
+        let mut holder = CAPTURE.clone();
+        holder.format = format;
+        holder
+    }
+    
+    pub fn aspect_flags(&self) -> vk::ImageAspectFlags {
+        self.aspect_flags
+    }
 }
 
 impl Image {
@@ -121,11 +149,16 @@ impl Image {
         let memory_requirements = unsafe{device.get_image_memory_requirements(image)};
         
         
-        let allocation = allocator.allocate(name, memory_requirements, memory::GpuOnly);
+        let allocation = allocator.allocate(name, memory_requirements, memory::GpuOnly)?;
         
         unsafe{device.bind_image_memory(image, allocation.memory(), allocation.offset())}?;
         
         let view = Self::create_view(device, image, format, metadata.aspect_flags)?;
+        device.set_object_name(image, name);
+        device.set_object_name(view, &format!("{} view", name));
+        
+        let subresource_count = (create_info.mip_levels * create_info.array_layers) as usize;
+        let states = std::iter::repeat_n(ResourceState::UNDEFINED, subresource_count).collect();
         
         Ok(Self{
             image, 
@@ -133,61 +166,29 @@ impl Image {
             allocation: ManuallyDrop::new(allocation), 
             extent, 
             extent_2d, 
-            format
+            format,
+            aspect: metadata.aspect_flags,
+            mip_levels: create_info.mip_levels,
+            array_layers: create_info.array_layers,
+            states,
         })
     }
     
 //----
+    // the copy goes through the staging ring, the texture can only be sampled once the ticket of the next flush is reached
     pub fn create_texture(
         device: &mut Device,
         allocator: &mut Allocator,
-        cmd_ctrl: &mut CommandControl,
+        uploader: &mut UploadManager,
         extent: vk::Extent3D,
         overwrite_name: Option<&str>,
         data: &[u32],
     ) -> Result<Self, AAError> {
-        
-        let upload_buffer_size = u64::from(extent.depth * extent.width * extent.height * 4);
-        
-        let mut upload_buffer = Buffer::create(device, allocator, Some("upload buffer"), upload_buffer_size, vk::BufferUsageFlags::TRANSFER_SRC, memory::CpuToGpu)?;
-        {
-            let mut align = upload_buffer.get_align::<u32>(0, upload_buffer_size).expect(CPU_ACCESIBLE);
-            align.copy_from_slice(data);
+        let mut holder = Self::create(device, allocator, extent, TEXTURE, overwrite_name)?;
+        if let Err(error) = uploader.upload_image(device, data, &mut holder) {
+            holder.destruct(VkDestructorArguments::DevAll(device, allocator));
+            return Err(error);
         }
-        
-        let holder = Self::create(device, allocator, extent, TEXTURE, overwrite_name)?;
-        
-        let _copy_state = cmd_ctrl.run_su_buffer(device, &mut |device, cmd|{
-            let image_handle = holder.underlying();
-            Self::transition_image(device, cmd, image_handle, vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
-            
-            let subresource = vk::ImageSubresourceLayers::builder()
-                .aspect_mask(vk::ImageAspectFlags::COLOR)
-                .mip_level(0)
-                .base_array_layer(0)
-                .layer_count(1);
-            
-            let image_copy = vk::BufferImageCopy::builder()
-                .buffer_offset(0)
-                .buffer_row_length(0)
-                .buffer_image_height(0)
-                .image_subresource(*subresource)
-                .image_offset(vk::Offset3D::default())
-                .image_extent(extent);
-            
-            unsafe{device.cmd_copy_buffer_to_image(
-                cmd,
-                upload_buffer.underlying(),
-                image_handle,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                from_ref(&image_copy)
-                
-            )};
-            Self::transition_image(device, cmd, image_handle, vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
-            Ok(())
-        })?;
-        
-        upload_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
         Ok(holder)
     }
     
@@ -298,41 +299,95 @@ impl Image {
 
     
 //----
-    pub fn transition_image(
-        device: &Device,
-        command_buffer: vk::CommandBuffer,
-        image: vk::Image,
-        old_layout: vk::ImageLayout,
-        new_layout: vk::ImageLayout,
-    ) {
-        //logger::transitioning_image(old_layout, new_layout);
-        
-        let image_aspect = match new_layout {
-            vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL => {
-                vk::ImageAspectFlags::DEPTH
-            }
-            _ => {
-                vk::ImageAspectFlags::COLOR
-            }
-        };
+    // state of the whole image, subresources are expected to share it
+    pub fn state(&self) -> ResourceState {
+        let first = self.states[0];
+        debug_assert!(self.states.iter().all(|state|state.layout == first.layout), "image subresources are in different layouts");
+        first
+    }
         
-        let subresource = Image::subresource_range(image_aspect);
+//----
+    pub fn layout(&self) -> vk::ImageLayout {
+        self.state().layout
+    }
+    
+//----
+    pub fn subresource_state(&self, mip_level:u32, array_layer:u32) -> ResourceState {
+        self.states[self.subresource_index(mip_level, array_layer)]
+    }
+    
+//----
+    // used when the image was transitioned outside of the tracked API, ie by the render graph
+    pub fn assume_state(&mut self, state:ResourceState) {
+        for holder in self.states.iter_mut() {
+            *holder = state;
+        }
+    }
+    
+//----
+    #[allow(dead_code)]
+    pub fn transition(&mut self, device:&Device, cmd:vk::CommandBuffer, access:Access) {
+        self.transition_range(device, cmd, Self::subresource_range(self.aspect), access);
+    }
+    
+//----
+    // the source state of every subresource is taken from the tracked state, so only the target access is needed
+    pub fn transition_range(&mut self, device:&Device, cmd:vk::CommandBuffer, range:vk::ImageSubresourceRange, access:Access) {
+        let level_count = Self::resolve_count(range.level_count, range.base_mip_level, self.mip_levels);
+        let layer_count = Self::resolve_count(range.layer_count, range.base_array_layer, self.array_layers);
         
-        let image_barrier = vk::ImageMemoryBarrier2::builder()
-            .image(image)
-            .old_layout(old_layout)
-            .new_layout(new_layout)
-            .src_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
-            .src_access_mask(vk::AccessFlags2::MEMORY_WRITE)
-            .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
-            .dst_access_mask(vk::AccessFlags2::MEMORY_WRITE|vk::AccessFlags2::MEMORY_READ)
-            .subresource_range(subresource);
+        let mut barriers = Vec::with_capacity((level_count * layer_count) as usize);
+        for array_layer in range.base_array_layer..range.base_array_layer+layer_count {
+            for mip_level in range.base_mip_level..range.base_mip_level+level_count {
+                let index = self.subresource_index(mip_level, array_layer);
+                let (src, dst) = match self.states[index].transition_to(access, true) {
+                    Some(holder) => holder,
+                    None => {continue;}
+                };
+                
+                let subresource = vk::ImageSubresourceRange{
+                    aspect_mask: range.aspect_mask,
+                    base_mip_level: mip_level,
+                    level_count: 1,
+                    base_array_layer: array_layer,
+                    layer_count: 1,
+                };
+                
+                let barrier = vk::ImageMemoryBarrier2::builder()
+                    .image(self.image)
+                    .old_layout(src.layout)
+                    .new_layout(dst.layout)
+                    .src_stage_mask(src.stage)
+                    .src_access_mask(src.access)
+                    .dst_stage_mask(dst.stage)
+                    .dst_access_mask(dst.access)
+                    .subresource_range(subresource);
+                barriers.push(*barrier);
+            }
+        }
         
-        let dependency = ash::vk::DependencyInfo::builder()
-            .image_memory_barriers(from_ref(&image_barrier));
+        if barriers.is_empty() {
+            return;
+        }
         
-        let _ = unsafe{device.cmd_pipeline_barrier2(command_buffer, &dependency)};
+        let dependency = vk::DependencyInfo::builder()
+            .image_memory_barriers(&barriers);
         
+        unsafe{device.cmd_pipeline_barrier2(cmd, &dependency)};
+    }
+    
+//----
+    fn subresource_index(&self, mip_level:u32, array_layer:u32) -> usize {
+        debug_assert!(mip_level < self.mip_levels && array_layer < self.array_layers);
+        (array_layer * self.mip_levels + mip_level) as usize
+    }
+    
+//----
+    fn resolve_count(count:u32, base:u32, total:u32) -> u32 {
+        match count {
+            vk::REMAINING_MIP_LEVELS => {total - base}
+            count => {count}
+        }
     }
     
 //----
@@ -350,7 +405,12 @@ impl Image {
         logger::destruct!("image");
         unsafe{device.destroy_image_view(self.view, None)};
         unsafe{device.destroy_image(self.image, None)};
-        allocator.free(ManuallyDrop::into_inner(self.allocation)).expect(GPU_FREE);
+        // teardown goes on after a lost device, a failed free only leaks
+        if let Err(error) = allocator.free(ManuallyDrop::into_inner(self.allocation)) {
+            logger::various_log!("image",
+                (logger::Error, "allocation not freed {:?}", error)
+            );
+        }
     }
 
 }
@@ -367,7 +427,10 @@ impl VkDestructor for Image {
 
 impl VkDeferedDestructor for Image {
     fn defered_destruct(&mut self) -> VkDynamicDestructor {
-        let target = unsafe{self.unsafe_clone()};
+        // the copy takes the only heap owned field, self is left with an empty one so neither frees it twice
+        let states = std::mem::take(&mut self.states);
+        let mut target = unsafe{self.unsafe_clone()};
+        target.states = states;
         let callback = Box::new(move |mut args:VkDestructorArguments|{
             let target = target;
             let (device, allocator) = args.unwrap_dev_all();
@@ -377,18 +440,18 @@ impl VkDeferedDestructor for Image {
     }
 }
 
-pub fn init_textures(device:&mut Device, allocator:&mut Allocator, cmd_ctrl:&mut CommandControl) -> (Image, Image, Image, Image){
+pub fn init_textures(device:&mut Device, allocator:&mut Allocator, uploader:&mut UploadManager) -> Result<(Image, Image, Image, Image), AAError> {
     
     let texture_extent = vk::Extent3D{width:1, height:1, depth:1};
     
     let white_pixel:u32 = 0x00_ffffff;
-    let white_texture = Image::create_texture(device, allocator, cmd_ctrl, texture_extent, Some("white texture"), from_ref(&white_pixel)).unwrap();
+    let white_texture = Image::create_texture(device, allocator, uploader, texture_extent, Some("white texture"), from_ref(&white_pixel))?;
     
     let grey_pixel:u32 = 0x00_aaaaaa;
-    let grey_texture = Image::create_texture(device, allocator, cmd_ctrl, texture_extent, Some("grey texture"), from_ref(&grey_pixel)).unwrap();
+    let grey_texture = Image::create_texture(device, allocator, uploader, texture_extent, Some("grey texture"), from_ref(&grey_pixel))?;
     
     let black_pixel:u32 = 0x11_00_00_00;
-    let black_texture = Image::create_texture(device, allocator, cmd_ctrl, texture_extent, Some("black texture"), from_ref(&black_pixel)).unwrap();
+    let black_texture = Image::create_texture(device, allocator, uploader, texture_extent, Some("black texture"), from_ref(&black_pixel))?;
     
     let magenta_pixel:u32 = 0x11_FF_00_FF;
     
@@ -405,8 +468,12 @@ pub fn init_textures(device:&mut Device, allocator:&mut Allocator, cmd_ctrl:&mut
         }
     });
     
-    let error_texture = Image::create_texture(device, allocator, cmd_ctrl, texture_extent, Some("error texture"), &error_data).unwrap();
+    let error_texture = Image::create_texture(device, allocator, uploader, texture_extent, Some("error texture"), &error_data)?;
+    
+    // the defaults are bound right away, so they have to be there before the first frame
+    let ticket = uploader.flush(device)?;
+    uploader.wait(device, ticket)?;
     
-    (white_texture, grey_texture, black_texture, error_texture)
+    Ok((white_texture, grey_texture, black_texture, error_texture))
 }
 
@@ -19,16 +19,18 @@ use ash::vk;
 pub struct Instance {
     pub entry: ash::Entry,
     instance: ash::Instance,
+    // the debug utils extension is enabled together with validation
+    pub debug_utils: bool,
 }
 macros::impl_deref!(Instance, ash::Instance, instance);
 macros::impl_underlying!(Instance, ash::Instance, instance);
 
 impl Instance {
     
-    pub fn create(window:&Window) -> Result<Instance, AAError> {
+    pub fn create(window:&Window, validation:bool) -> Result<Instance, AAError> {
         
         logger::create!("instance");
-        let entry = unsafe {ash::Entry::load().expect(SIMPLE_VK_FN)};
+        let entry = unsafe {ash::Entry::load()}?;
         
         match entry.try_enumerate_instance_version()? {
             // Vulkan 1.1+
@@ -57,13 +59,13 @@ impl Instance {
         
         let av_extensions = Extensions::get(&entry);
         av_extensions.log();
-        let extensions_ptr = av_extensions.handle_logic(window);
+        let extensions_ptr = av_extensions.handle_logic(window, validation);
         
         
         
         let av_layers = Layers::get(&entry);
         av_layers.log();
-        let layers_ptr = av_layers.handle_logic();
+        let layers_ptr = av_layers.handle_logic(validation);
         
         
         let mut create_info = vk::InstanceCreateInfo::builder()
@@ -73,7 +75,7 @@ impl Instance {
         
         
         let mut debug_messenger;
-        if constants::VALIDATION {
+        if validation {
             debug_messenger = DMessenger::populate_create_info();
             create_info = create_info.push_next(&mut debug_messenger);
         }
@@ -81,7 +83,7 @@ impl Instance {
         
         let instance_holder = unsafe{entry.create_instance(&create_info, None)?};
         
-        Ok(Self{entry:entry, instance:instance_holder})
+        Ok(Self{entry, instance:instance_holder, debug_utils:validation})
     }
     
     
@@ -131,9 +133,12 @@ impl Extensions {
         
     }
     
-    fn validate(&self, window:&Window) -> Result<Vec<*const c_char>, AAError> {
+    fn validate(&self, window:&Window, validation:bool) -> Result<Vec<*const c_char>, AAError> {
         let window_extensions = window.get_required_instance_extentions();
-        let mut set:HashSet<&'static str> = HashSet::from(constants::EXTENSIONS);//(extensions);
+        let mut set:HashSet<&'static str> = HashSet::from(constants::BASE_EXTENSIONS);//(extensions);
+        if validation {
+            set.extend(constants::DEBUG_EXTENSIONS);
+        }
         set.extend(&window_extensions[..]);
         let mut holder = Vec::<*const c_char>::with_capacity(set.len());
         
@@ -151,8 +156,8 @@ impl Extensions {
         }
     }
     
-    fn handle_logic(&self, window:&Window) -> Vec<*const c_char> {
-        match self.validate(window) {
+    fn handle_logic(&self, window:&Window, validation:bool) -> Vec<*const c_char> {
+        match self.validate(window, validation) {
             Ok(holder) => {
                 logger::various_log!("instance", 
                     (logger::Trace, "All extensions available"),
@@ -203,8 +208,8 @@ impl Layers {
         }
     }
     
-    fn handle_logic(&self) -> Vec<*const c_char> {
-        if constants::VALIDATION {
+    fn handle_logic(&self, validation:bool) -> Vec<*const c_char> {
+        if validation {
             match self.validate() {
                 Ok(holder) => {
                     logger::various_log!("instance", 
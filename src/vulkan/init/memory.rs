@@ -1,19 +1,14 @@
 use crate::AAError;
 use crate::macros;
 use crate::logger;
-use crate::errors::messages::GPU_ALLOCATION;
-use crate::errors::messages::SU_COMMAND_FAIL;
 
 use super::VkDestructor;
 use super::VkDestructorArguments;
 use super::Instance;
 use super::PDevice;
 use super::Device;
-use super::CommandControl;
-use super::Buffer;
 
 use std::mem::ManuallyDrop;
-use std::slice::from_ref;
 
 use ash::vk;
 use gpu_allocator::vulkan as gpu_vk;
@@ -57,7 +52,7 @@ impl Allocator {
         name: &str,
         requirements: vk::MemoryRequirements,
         location: gpu_all::MemoryLocation,
-    ) -> gpu_vk::Allocation {
+    ) -> Result<gpu_vk::Allocation, AAError> {
         
         logger::various_log!("allocator", 
             (logger::Trace, "Allocation name: {:?}", name),
@@ -71,7 +66,7 @@ impl Allocator {
             allocation_scheme: gpu_vk::AllocationScheme::GpuAllocatorManaged,
         };
         
-        self.allocator.allocate(&alloc_info).expect(GPU_ALLOCATION)
+        Ok(self.allocator.allocate(&alloc_info)?)
     }
     
     pub fn into_inner(self) -> gpu_vk::Allocator {
@@ -90,34 +85,6 @@ impl VkDestructor for Allocator {
 
 
 
-pub fn copy_buffer_2_buffer(
-    device: &mut Device, 
-    cmd_ctrl: &mut CommandControl,
-    src_buf: &Buffer, 
-    src_offset: u64,
-    dst_buf: &mut Buffer, 
-    dst_offset: u64,
-    size: vk::DeviceSize,
-) {
-    
-    logger::various_log!("memory",
-        (logger::Trace, "copying from 1 buffer to another")
-    );
-    
-    
-    let _holder = cmd_ctrl.run_su_buffer(device, &mut |device, cmd|{
-        
-        let buffer_copy = vk::BufferCopy::builder()
-            .size(size)
-            .src_offset(src_offset)
-            .dst_offset(dst_offset);
-        unsafe{device.cmd_copy_buffer(cmd, src_buf.buffer, dst_buf.buffer, from_ref(&buffer_copy))};
-        //Err(AAError::TODOError)
-        Ok(())
-    }).expect(SU_COMMAND_FAIL);
-    
-}
-
 /*
 pub fn find_memory_type_index(
     p_device:&PDevice, 
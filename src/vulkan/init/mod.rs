@@ -37,6 +37,13 @@ pub use descriptors::*;
 mod sampler;
 pub use sampler::*;
 
+mod query;
+pub use query::FrameQueries;
+
+mod upload;
+pub use upload::UploadManager;
+pub use upload::UploadTicket;
+
 pub mod c_pipeline;
 pub use c_pipeline::CPipeline;
 pub use c_pipeline::ComputeEffects;
@@ -2,6 +2,7 @@ use crate::AAError;
 use crate::macros;
 use crate::constants;
 use crate::logger;
+use crate::config::GpuSelector;
 
 use super::instance::Instance;
 use super::surface::Surface;
@@ -27,20 +28,24 @@ macros::impl_underlying!(PDevice, vk::PhysicalDevice, p_device);
 pub struct QueueFamilyOptionalIndices {
     pub graphics_family: Option<u32>,
     pub present_family: Option<u32>,
+    pub transfer_family: Option<u32>,
 }
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct QueueFamilyIndices {
     pub graphics_family: u32,
     pub present_family: u32,
+    // only set when the device exposes a transfer family without graphics support
+    pub transfer_family: Option<u32>,
 }
 
 impl PDevice {
     
-    pub fn chose(instance:&Instance, surface:&Surface) -> Result<Self, AAError> {
+    // a suitable device matching the selector wins over the best rated one
+    pub fn chose(instance:&Instance, surface:&Surface, selector:Option<&GpuSelector>) -> Result<Self, AAError> {
         logger::chossing!("p device");
         
-        let p_devices = unsafe{instance.enumerate_physical_devices().unwrap()};
+        let p_devices = unsafe{instance.enumerate_physical_devices()}?;
         if p_devices.len() == 0 {
             return Err(AAError::NoGPU);
         }
@@ -50,11 +55,17 @@ impl PDevice {
         let mut best_properties = vk::PhysicalDeviceProperties::default();
         let mut best_features = vk::PhysicalDeviceFeatures::default();
         let mut best_score = 0;
+        let mut selected = false;
         
         
-        for p_device in p_devices.into_iter() {
+        for (index, p_device) in p_devices.into_iter().enumerate() {
             
-            if let Ok((current_score, current_queue, sc_support_details, properties, features)) = Self::rate(instance, surface, p_device) {
+            if let Ok((mut current_score, current_queue, sc_support_details, properties, features)) = Self::rate(instance, surface, p_device) {
+                let name = unsafe{CStr::from_ptr(properties.device_name.as_ptr())}.to_string_lossy();
+                if selector.is_some_and(|selector|selector.matches(index, &name)) {
+                    current_score = i64::MAX;
+                    selected = true;
+                }
                 if current_score > best_score {
                     best_score = current_score;
                     best_queue = current_queue;
@@ -67,6 +78,12 @@ impl PDevice {
         }
         
         
+        if selector.is_some() && !selected {
+            logger::various_log!("p_device", 
+                (logger::Warn, "no suitable gpu matches {:?}, using the best rated one", selector),
+            );
+        }
+        
         if best != vk::PhysicalDevice::null() {
             
             logger::various_log!("p_device", 
@@ -172,7 +189,8 @@ impl PDevice {
         for (index, queue) in properties.iter().enumerate() {
             let index_u32 = u32::try_from(index).expect("no gpu has that much queues");
             
-            let present_suport = unsafe{surface.get_physical_device_surface_support(p_device, index_u32, surface.surface).unwrap()};
+            // a lost surface can't be presented to, the device is then rated as unsuitable
+            let present_suport = unsafe{surface.get_physical_device_surface_support(p_device, index_u32, surface.surface)}.unwrap_or(false);
             
             match (present_suport, holder.present_family) {
                 (true, None) => {
@@ -183,6 +201,17 @@ impl PDevice {
             
             if queue.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
                 holder.graphics_family = Some(index_u32);
+            } else if queue.queue_flags.contains(vk::QueueFlags::TRANSFER) {
+                // families without compute are usually the dedicated copy engines
+                let replace = match holder.transfer_family {
+                    None => true,
+                    Some(current) => {
+                        properties[current as usize].queue_flags.contains(vk::QueueFlags::COMPUTE) && !queue.queue_flags.contains(vk::QueueFlags::COMPUTE)
+                    }
+                };
+                if replace {
+                    holder.transfer_family = Some(index_u32);
+                }
             }
         }
         
@@ -192,7 +221,7 @@ impl PDevice {
     
     
     fn check_device_support(instance:&Instance, p_device:vk::PhysicalDevice) -> bool {
-        let device_extensions = unsafe{instance.enumerate_device_extension_properties(p_device)}.unwrap();
+        let device_extensions = unsafe{instance.enumerate_device_extension_properties(p_device)}.unwrap_or_default();
         
         let mut set = HashSet::from(constants::DEVICE_EXTENSIONS.clone());
         
@@ -214,14 +243,26 @@ impl PDevice {
     ) -> Result<vk::PhysicalDeviceFeatures, ()> {
         if features.geometry_shader == vk::TRUE && 
             features.fill_mode_non_solid == vk::TRUE &&
+            features.multi_draw_indirect == vk::TRUE &&
+            features.draw_indirect_first_instance == vk::TRUE &&
             vk_features12.buffer_device_address == vk::TRUE && 
             vk_features12.descriptor_indexing == vk::TRUE &&
+            vk_features12.runtime_descriptor_array == vk::TRUE &&
+            vk_features12.descriptor_binding_partially_bound == vk::TRUE &&
+            vk_features12.descriptor_binding_variable_descriptor_count == vk::TRUE &&
+            vk_features12.descriptor_binding_sampled_image_update_after_bind == vk::TRUE &&
+            vk_features12.shader_sampled_image_array_non_uniform_indexing == vk::TRUE &&
+            vk_features12.timeline_semaphore == vk::TRUE &&
+            vk_features12.draw_indirect_count == vk::TRUE &&
             vk_features13.dynamic_rendering == vk::TRUE && 
             vk_features13.synchronization2 == vk::TRUE {
             
             let holder = vk::PhysicalDeviceFeatures::builder()
                 .sampler_anisotropy(true)
                 .fill_mode_non_solid(true)
+                .multi_draw_indirect(true)
+                .draw_indirect_first_instance(true)
+                .pipeline_statistics_query(features.pipeline_statistics_query == vk::TRUE)
                 .build();
             Ok(holder)
         } else {
@@ -254,6 +295,7 @@ impl From<QueueFamilyOptionalIndices> for QueueFamilyIndices {
         Self{
             graphics_family:base.graphics_family.unwrap(),
             present_family:base.present_family.unwrap(),
+            transfer_family:base.transfer_family,
         }
     }
 }
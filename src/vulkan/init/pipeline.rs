@@ -1,6 +1,7 @@
-use crate::errors::messages::RESOURCE_FILE;
 use crate::AAError;
+use crate::logger;
 use crate::constants;
+use crate::config;
 
 use super::Device;
 
@@ -10,8 +11,13 @@ use ash::vk;
 
 
 pub fn create_shader_module(device:&mut Device, file:&str) -> Result<vk::ShaderModule, AAError> {
-    let mut spv_file = File::open(file).unwrap();
-    let spv = ash::util::read_spv(&mut spv_file).expect(RESOURCE_FILE);
+    let path = config::asset_path(file);
+    let spv = File::open(&path).and_then(|mut spv_file|ash::util::read_spv(&mut spv_file)).map_err(|err|{
+        logger::various_log!("pipeline",
+            (logger::Error, "can't read shader {}: {}", path.display(), err)
+        );
+        err
+    })?;
     let create_info = vk::ShaderModuleCreateInfo::builder()
         .code(&spv);
     unsafe{device.create_shader_module(&create_info, None)}.map_err(|err|err.into())
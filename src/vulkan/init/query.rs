@@ -0,0 +1,214 @@
+use crate::AAError;
+use crate::logger;
+use crate::graphics::PassTiming;
+use crate::graphics::PipelineStatistics;
+use crate::errors::messages::GRANTED;
+
+use super::VkDestructor;
+use super::VkDestructorArguments;
+use super::Device;
+use super::PDevice;
+
+use ash::vk;
+use arrayvec::ArrayVec;
+use arrayvec::ArrayString;
+
+const MAX_SCOPES:usize = 16;
+const TIMESTAMP_COUNT:u32 = MAX_SCOPES as u32 * 2;
+
+#[derive(Debug, Clone, Copy)]
+struct Scope {
+    name: ArrayString<32>,
+    start: u32,
+    closed: bool,
+}
+
+pub struct FrameQueries {
+    timestamp_pool: Option<vk::QueryPool>,
+    statistics_pool: Option<vk::QueryPool>,
+    timestamp_period: f32,
+    scopes: ArrayVec<Scope, MAX_SCOPES>,
+    statistics_recorded: bool,
+    submitted: bool,
+}
+
+impl FrameQueries {
+    pub fn create(p_device:&PDevice, device:&mut Device) -> Result<Self, AAError> {
+        logger::create!("frame_queries");
+
+        let limits = &p_device.properties.limits;
+
+        let timestamp_pool = if limits.timestamp_compute_and_graphics == vk::TRUE {
+            let create_info = vk::QueryPoolCreateInfo::builder()
+                .query_type(vk::QueryType::TIMESTAMP)
+                .query_count(TIMESTAMP_COUNT);
+            let pool = unsafe{device.create_query_pool(&create_info, None)}?;
+            device.set_object_name(pool, "timestamp queries");
+            Some(pool)
+        } else {
+            logger::various_log!("frame_queries",
+                (logger::Warn, "timestamps are not supported on graphics queues")
+            );
+            None
+        };
+
+        let statistics_pool = if p_device.features.pipeline_statistics_query == vk::TRUE {
+            let create_info = vk::QueryPoolCreateInfo::builder()
+                .query_type(vk::QueryType::PIPELINE_STATISTICS)
+                .pipeline_statistics(Self::statistic_flags())
+                .query_count(1);
+            let pool = unsafe{device.create_query_pool(&create_info, None)}?;
+            device.set_object_name(pool, "statistics queries");
+            Some(pool)
+        } else {
+            logger::various_log!("frame_queries",
+                (logger::Warn, "pipeline statistics queries are not supported")
+            );
+            None
+        };
+
+        Ok(Self{
+            timestamp_pool,
+            statistics_pool,
+            timestamp_period: limits.timestamp_period,
+            scopes: ArrayVec::new(),
+            statistics_recorded: false,
+            submitted: false,
+        })
+    }
+
+    fn statistic_flags() -> vk::QueryPipelineStatisticFlags {
+        vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS | vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS
+    }
+
+//----
+    pub fn reset(&mut self, device:&Device, cmd:vk::CommandBuffer) {
+        self.scopes.clear();
+        self.statistics_recorded = false;
+        self.submitted = true;
+        if let Some(pool) = self.timestamp_pool {
+            unsafe{device.cmd_reset_query_pool(cmd, pool, 0, TIMESTAMP_COUNT)};
+        }
+        if let Some(pool) = self.statistics_pool {
+            unsafe{device.cmd_reset_query_pool(cmd, pool, 0, 1)};
+        }
+    }
+
+//----
+    // the commands of the frame were thrown away before being submitted, there is nothing to read back
+    pub fn discard(&mut self) {
+        self.scopes.clear();
+        self.statistics_recorded = false;
+        self.submitted = false;
+    }
+
+//----
+    pub fn begin_scope(&mut self, device:&Device, cmd:vk::CommandBuffer, name:&str) {
+        let pool = match self.timestamp_pool {
+            Some(pool) => pool,
+            None => {return;}
+        };
+        if self.scopes.is_full() {
+            logger::various_log!("frame_queries",
+                (logger::Warn, "too many profiling scopes, ignoring {}", name)
+            );
+            return;
+        }
+        let mut scope_name = ArrayString::new();
+        let _ = scope_name.try_push_str(name);
+        let start = u32::try_from(self.scopes.len()).expect(GRANTED) * 2;
+        self.scopes.push(Scope{name:scope_name, start, closed:false});
+        unsafe{device.cmd_write_timestamp2(cmd, vk::PipelineStageFlags2::TOP_OF_PIPE, pool, start)};
+    }
+
+//----
+    pub fn end_scope(&mut self, device:&Device, cmd:vk::CommandBuffer) {
+        let pool = match self.timestamp_pool {
+            Some(pool) => pool,
+            None => {return;}
+        };
+        if let Some(scope) = self.scopes.iter_mut().rev().find(|scope|!scope.closed) {
+            scope.closed = true;
+            unsafe{device.cmd_write_timestamp2(cmd, vk::PipelineStageFlags2::ALL_COMMANDS, pool, scope.start+1)};
+        }
+    }
+
+//----
+    pub fn begin_statistics(&mut self, device:&Device, cmd:vk::CommandBuffer) {
+        if let Some(pool) = self.statistics_pool {
+            unsafe{device.cmd_begin_query(cmd, pool, 0, vk::QueryControlFlags::empty())};
+            self.statistics_recorded = true;
+        }
+    }
+
+//----
+    pub fn end_statistics(&mut self, device:&Device, cmd:vk::CommandBuffer) {
+        if let (Some(pool), true) = (self.statistics_pool, self.statistics_recorded) {
+            unsafe{device.cmd_end_query(cmd, pool, 0)};
+        }
+    }
+
+//----
+    /// Must only be called once the fence of the frame that recorded the queries has signaled.
+    pub fn read_results(&mut self, device:&Device) -> Option<(ArrayVec<PassTiming, MAX_SCOPES>, Option<PipelineStatistics>)> {
+        if !self.submitted {
+            return None;
+        }
+        self.submitted = false;
+
+        let mut timings = ArrayVec::new();
+        if let (Some(pool), false) = (self.timestamp_pool, self.scopes.is_empty()) {
+            let mut raw = [0u64; TIMESTAMP_COUNT as usize];
+            let query_count = u32::try_from(self.scopes.len()).expect(GRANTED) * 2;
+            match unsafe{device.get_query_pool_results(pool, 0, query_count, &mut raw[..], vk::QueryResultFlags::TYPE_64)} {
+                Ok(()) => {
+                    for scope in self.scopes.iter().filter(|scope|scope.closed) {
+                        let start = raw[scope.start as usize];
+                        let end = raw[scope.start as usize + 1];
+                        let nanoseconds = end.saturating_sub(start) as f64 * f64::from(self.timestamp_period);
+                        timings.push(PassTiming{
+                            name: scope.name,
+                            milliseconds: (nanoseconds / 1_000_000.0) as f32,
+                        });
+                    }
+                }
+                Err(err) => {
+                    logger::various_log!("frame_queries",
+                        (logger::Debug, "timestamps not available {:?}", err)
+                    );
+                }
+            }
+        }
+
+        let statistics = match (self.statistics_pool, self.statistics_recorded) {
+            (Some(pool), true) => {
+                let mut raw = [[0u64; 2]; 1];
+                match unsafe{device.get_query_pool_results(pool, 0, 1, &mut raw[..], vk::QueryResultFlags::TYPE_64)} {
+                    Ok(()) => {
+                        Some(PipelineStatistics{
+                            vertex_invocations: raw[0][0],
+                            fragment_invocations: raw[0][1],
+                        })
+                    }
+                    Err(_) => None,
+                }
+            }
+            _ => None,
+        };
+
+        Some((timings, statistics))
+    }
+}
+
+impl VkDestructor for FrameQueries {
+    fn destruct(self, mut args:VkDestructorArguments) {
+        logger::destruct!("frame_queries");
+        let device = args.unwrap_dev();
+        if let Some(pool) = self.timestamp_pool {
+            unsafe{device.destroy_query_pool(pool, None)};
+        }
+        if let Some(pool) = self.statistics_pool {
+            unsafe{device.destroy_query_pool(pool, None)};
+        }
+    }
+}
@@ -23,6 +23,7 @@ impl Sampler {
             .mag_filter(filter)
             .min_filter(filter);
         let holder = unsafe{device.create_sampler(&sampler_ci, None)}?;
+        device.set_object_name(holder, &format!("{:?} sampler", filter).to_lowercase());
         Ok(Self{
             sampler: holder,
         })
@@ -22,7 +22,7 @@ impl Surface {
     pub fn create(window:&Window, instance:&mut Instance) -> Result<Self, AAError> {
         logger::create!("surface");
         
-        let surface = unsafe{window.create_surface(instance)}.unwrap();
+        let surface = unsafe{window.create_surface(instance)}?;
         let surface_loader = ash::extensions::khr::Surface::new(&instance.entry, instance);
         
         Ok(Self{
@@ -3,7 +3,6 @@ use crate::macros;
 use crate::logger;
 
 use crate::errors::messages::U32_TO_USIZE;
-use crate::errors::messages::SIMPLE_VK_FN;
 use crate::constants::sc_max_images;
 
 use super::VkDestructor;
@@ -33,6 +32,7 @@ pub struct Swapchain {
     pub extent: vk::Extent2D,
     pub surface_format: vk::SurfaceFormatKHR,
     pub swapchain: vk::SwapchainKHR,
+    pub capturable: bool,
     swapchain_loader: ash::extensions::khr::Swapchain,
 }
 
@@ -41,13 +41,13 @@ macros::impl_deref!(Swapchain, ash::extensions::khr::Swapchain, swapchain_loader
 
 impl Swapchain {
     
-    pub fn create(instance:&mut Instance, surface:&Surface, p_device:&PDevice, device:&mut Device) -> Result<Self, AAError> {
+    pub fn create(instance:&mut Instance, surface:&Surface, p_device:&PDevice, device:&mut Device, desired_present_mode:vk::PresentModeKHR) -> Result<Self, AAError> {
         logger::create!("swapchain");
         
         let surface_format = p_device.swapchain_details.choose_surface_format();
-        let present_mode = p_device.swapchain_details.choose_present_mode();
+        let present_mode = p_device.swapchain_details.choose_present_mode(desired_present_mode);
         
-        let surface_capabilities = unsafe{surface.get_physical_device_surface_capabilities(p_device.underlying(), surface.surface).expect(SIMPLE_VK_FN)};
+        let surface_capabilities = unsafe{surface.get_physical_device_surface_capabilities(p_device.underlying(), surface.surface)}?;
         
         let swap_extent = SwapchainSupportDetails::choose_swap_extent(&surface_capabilities);
         
@@ -74,6 +74,13 @@ impl Swapchain {
         
         let image_count_usize = usize::try_from(image_count).expect(U32_TO_USIZE);
         
+        // screenshots with the gui overlay copy straight from the swapchain image
+        let capturable = surface_capabilities.supported_usage_flags.contains(vk::ImageUsageFlags::TRANSFER_SRC);
+        let mut image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST;
+        if capturable {
+            image_usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+        }
+        
         
         let mut create_info = vk::SwapchainCreateInfoKHR::builder()
             .surface(surface.surface)
@@ -82,7 +89,7 @@ impl Swapchain {
             .image_color_space(surface_format.color_space)
             .image_extent(swap_extent)
             .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
+            .image_usage(image_usage)
             .pre_transform(surface_capabilities.current_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(present_mode)
@@ -110,12 +117,18 @@ impl Swapchain {
         }
         
         let image_views = Self::create_image_views(&device, &images[..], surface_format.format)?;
+        device.set_object_name(swapchain, "swapchain");
+        for (index, (image, view)) in images.iter().zip(image_views.iter()).enumerate() {
+            device.set_object_name(*image, &format!("swapchain image {}", index));
+            device.set_object_name(*view, &format!("swapchain image {} view", index));
+        }
         
         Ok(Self{
             image_count: image_count_usize,
             image_views:image_views,
             images:images,
             swapchain:swapchain,
+            capturable,
             swapchain_loader:swapchain_loader,
             extent:swap_extent,
             surface_format:surface_format,
@@ -142,7 +155,8 @@ impl Swapchain {
     }
     
     
-    pub fn get_next_image(&mut self, semaphore:vk::Semaphore) -> Result<(vk::Image, vk::ImageView, u32), ()> {
+    // out of date and lost surfaces come back as the raw result, the caller rebuilds the swapchain for those
+    pub fn get_next_image(&mut self, semaphore:vk::Semaphore) -> Result<(vk::Image, vk::ImageView, u32), vk::Result> {
         
         let holder = unsafe{
             self.acquire_next_image(
@@ -155,7 +169,7 @@ impl Swapchain {
         
         
         
-        let image_index = holder.map(|(image_index, _)|image_index).map_err(|_err|())?;
+        let image_index = holder.map(|(image_index, _)|image_index)?;
         
         Ok((self.images[image_index as usize], self.image_views[image_index as usize], image_index))
     }
@@ -219,8 +233,9 @@ impl SwapchainSupportDetails {
     
     pub fn query_swapchain_support(surface:&Surface, p_device:vk::PhysicalDevice) -> SwapchainSupportDetails {
         
-        let surface_formats = unsafe{surface.get_physical_device_surface_formats(p_device, surface.surface).expect(SIMPLE_VK_FN)};
-        let present_modes = unsafe{surface.get_physical_device_surface_present_modes(p_device, surface.surface).expect(SIMPLE_VK_FN)};
+        // empty lists fail min_requirements, so a lost surface only makes the device unsuitable
+        let surface_formats = unsafe{surface.get_physical_device_surface_formats(p_device, surface.surface)}.unwrap_or_default();
+        let present_modes = unsafe{surface.get_physical_device_surface_present_modes(p_device, surface.surface)}.unwrap_or_default();
         SwapchainSupportDetails{
             surface_formats,
             present_modes
@@ -257,7 +272,8 @@ impl SwapchainSupportDetails {
         self.surface_formats[0]
     }
     
-    fn choose_present_mode(&self) -> vk::PresentModeKHR {
+    // fifo is the fallback, it is the only one always supported
+    fn choose_present_mode(&self, desired:vk::PresentModeKHR) -> vk::PresentModeKHR {
         
         logger::various_log!("swapchain",
             (logger::Trace, "Present modes \n{:#?}", &self.present_modes),
@@ -265,11 +281,11 @@ impl SwapchainSupportDetails {
         //logger::present_chossing(&self.present_modes);
         
         for mode in &self.present_modes {
-            if mode == &vk::PresentModeKHR::MAILBOX {
+            if mode == &desired {
                 logger::various_log!("swapchain",
-                    (logger::Debug, "Found desired present mode \n{:#?}", vk::PresentModeKHR::MAILBOX)
+                    (logger::Debug, "Found desired present mode \n{:#?}", desired)
                 );
-                return vk::PresentModeKHR::MAILBOX;
+                return desired;
             }
             
         }
@@ -0,0 +1,517 @@
+use crate::AAError;
+use crate::logger;
+use crate::errors::messages::CPU_ACCESIBLE;
+use crate::errors::messages::VK_CAST;
+use crate::errors::messages::GRANTED;
+
+use super::VkDestructor;
+use super::VkDestructorArguments;
+use super::Device;
+use super::PDevice;
+use super::Allocator;
+use super::Buffer;
+use super::Image;
+use super::memory;
+
+use super::super::render_graph::Access;
+
+use std::slice::from_ref;
+use std::collections::VecDeque;
+use std::mem::size_of_val;
+
+use ash::vk;
+
+const STAGING_SIZE:u64 = 64 * 1024 * 1024;
+const STAGING_ALIGNMENT:u64 = 16;
+
+// timeline value the uploads submitted before it are complete at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UploadTicket(u64);
+
+struct StagingRegion {
+    start: u64,
+    end: u64,
+    // None until the copy reading from the region is submitted
+    value: Option<u64>,
+}
+
+struct PendingCopy {
+    dst: vk::Buffer,
+    region: vk::BufferCopy,
+}
+
+struct PendingImageCopy {
+    dst: vk::Image,
+    region: vk::BufferImageCopy,
+}
+
+struct InFlightBatch {
+    value: u64,
+    transfer_cmd: vk::CommandBuffer,
+    acquire_cmd: Option<vk::CommandBuffer>,
+}
+
+pub struct UploadManager {
+    staging: Buffer,
+    regions: VecDeque<StagingRegion>,
+    pending: Vec<PendingCopy>,
+    pending_images: Vec<PendingImageCopy>,
+    in_flight: VecDeque<InFlightBatch>,
+    
+    transfer_pool: vk::CommandPool,
+    acquire_pool: Option<vk::CommandPool>,
+    free_transfer_cmds: Vec<vk::CommandBuffer>,
+    free_acquire_cmds: Vec<vk::CommandBuffer>,
+    
+    timeline: vk::Semaphore,
+    last_value: u64,
+    
+    graphics_family: u32,
+    transfer_family: Option<u32>,
+}
+
+impl UploadManager {
+    pub fn create(p_device:&PDevice, device:&mut Device, allocator:&mut Allocator) -> Result<Self, AAError> {
+        logger::create!("upload_manager");
+        
+        let queues = &p_device.queues;
+        
+        let transfer_pool = Self::create_pool(device, queues.transfer_family.unwrap_or(queues.graphics_family))?;
+        let acquire_pool = match queues.transfer_family {
+            Some(_) => Some(Self::create_pool(device, queues.graphics_family)?),
+            None => None,
+        };
+        
+        logger::various_log!("upload_manager",
+            (logger::Debug, "uploading through family {:?}, graphics family {}", queues.transfer_family, queues.graphics_family)
+        );
+        
+        let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let create_info = vk::SemaphoreCreateInfo::builder()
+            .push_next(&mut type_info);
+        let timeline = unsafe{device.create_semaphore(&create_info, None)}?;
+        device.set_object_name(timeline, "upload timeline");
+        
+        let staging = Buffer::create(device, allocator, Some("upload staging ring"), STAGING_SIZE, vk::BufferUsageFlags::TRANSFER_SRC, memory::CpuToGpu)?;
+        
+        Ok(Self{
+            staging,
+            regions: VecDeque::new(),
+            pending: Vec::new(),
+            pending_images: Vec::new(),
+            in_flight: VecDeque::new(),
+            transfer_pool,
+            acquire_pool,
+            free_transfer_cmds: Vec::new(),
+            free_acquire_cmds: Vec::new(),
+            timeline,
+            last_value: 0,
+            graphics_family: queues.graphics_family,
+            transfer_family: queues.transfer_family,
+        })
+    }
+    
+    fn create_pool(device:&Device, family:u32) -> Result<vk::CommandPool, AAError> {
+        let create_info = vk::CommandPoolCreateInfo::builder()
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER | vk::CommandPoolCreateFlags::TRANSIENT)
+            .queue_family_index(family);
+        let pool = unsafe{device.create_command_pool(&create_info, None)}?;
+        device.set_object_name(pool, &format!("upload command pool family {}", family));
+        Ok(pool)
+    }
+    
+//----
+    // copies data into the staging ring, the copy into dst is recorded on the next flush
+    pub fn upload_buffer<T:Copy>(&mut self, device:&Device, data:&[T], dst:&Buffer, dst_offset:u64) -> Result<(), AAError> {
+        let size = u64::try_from(size_of_val(data)).expect(VK_CAST);
+        if size == 0 {
+            return Ok(());
+        }
+        
+        let offset = self.stage(device, data)?;
+        
+        let region = vk::BufferCopy::builder()
+            .src_offset(offset)
+            .dst_offset(dst_offset)
+            .size(size);
+        
+        self.pending.push(PendingCopy{
+            dst: dst.buffer,
+            region: *region,
+        });
+        Ok(())
+    }
+    
+//----
+    // fills the first mip of a new image, nothing else may use it until the ticket of the next flush is reached,
+    // by then it is in SHADER_READ_ONLY_OPTIMAL and owned by the graphics family
+    pub fn upload_image<T:Copy>(&mut self, device:&Device, data:&[T], dst:&mut Image) -> Result<(), AAError> {
+        let offset = self.stage(device, data)?;
+        
+        let subresource = vk::ImageSubresourceLayers{
+            aspect_mask: dst.aspect,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        
+        let region = vk::BufferImageCopy{
+            buffer_offset: offset,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: subresource,
+            image_offset: vk::Offset3D::default(),
+            image_extent: dst.extent,
+        };
+        
+        self.pending_images.push(PendingImageCopy{
+            dst: dst.image,
+            region,
+        });
+        dst.assume_state(Access::FragmentSampled.state());
+        Ok(())
+    }
+    
+//----
+    // submits every pending copy, the destinations are owned by the graphics family once the ticket is reached
+    pub fn flush(&mut self, device:&Device) -> Result<UploadTicket, AAError> {
+        if self.pending.is_empty() && self.pending_images.is_empty() {
+            return Ok(UploadTicket(self.last_value));
+        }
+        
+        logger::various_log!("upload_manager",
+            (logger::Trace, "flushing {} copies {} image copies", self.pending.len(), self.pending_images.len())
+        );
+        
+        let transfer_cmd = Self::get_cmd(device, self.transfer_pool, &mut self.free_transfer_cmds)?;
+        Self::begin(device, transfer_cmd)?;
+        
+        // the images are new, their content is discarded
+        let transfer_dst_barriers:Vec<vk::ImageMemoryBarrier2> = self.pending_images.iter().map(|copy|{
+            Self::image_barrier(copy)
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .dst_stage_mask(vk::PipelineStageFlags2::COPY)
+                .dst_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+                .build()
+        }).collect();
+        Self::barriers(device, transfer_cmd, &[], &transfer_dst_barriers);
+        
+        for copy in self.pending.iter() {
+            unsafe{device.cmd_copy_buffer(transfer_cmd, self.staging.buffer, copy.dst, from_ref(&copy.region))};
+        }
+        for copy in self.pending_images.iter() {
+            unsafe{device.cmd_copy_buffer_to_image(transfer_cmd, self.staging.buffer, copy.dst, vk::ImageLayout::TRANSFER_DST_OPTIMAL, from_ref(&copy.region))};
+        }
+        
+        let release_barriers:Vec<vk::BufferMemoryBarrier2> = self.pending.iter().map(|copy|{
+            let holder = Self::copy_barrier(copy)
+                .src_stage_mask(vk::PipelineStageFlags2::COPY)
+                .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE);
+            match self.transfer_family {
+                // release half of the ownership transfer, the destination scope is ignored
+                Some(transfer_family) => {
+                    holder
+                        .src_queue_family_index(transfer_family)
+                        .dst_queue_family_index(self.graphics_family)
+                        .build()
+                }
+                None => {
+                    holder
+                        .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                        .dst_access_mask(vk::AccessFlags2::MEMORY_READ)
+                        .build()
+                }
+            }
+        }).collect();
+        let release_image_barriers:Vec<vk::ImageMemoryBarrier2> = self.pending_images.iter().map(|copy|{
+            let holder = Self::sampled_barrier(copy)
+                .src_stage_mask(vk::PipelineStageFlags2::COPY)
+                .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE);
+            match self.transfer_family {
+                Some(transfer_family) => {
+                    holder
+                        .src_queue_family_index(transfer_family)
+                        .dst_queue_family_index(self.graphics_family)
+                        .build()
+                }
+                None => {
+                    holder
+                        .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                        .dst_access_mask(vk::AccessFlags2::MEMORY_READ)
+                        .build()
+                }
+            }
+        }).collect();
+        Self::barriers(device, transfer_cmd, &release_barriers, &release_image_barriers);
+        
+        unsafe{device.end_command_buffer(transfer_cmd)}?;
+        
+        let transfer_value = self.last_value + 1;
+        self.submit(device, device.queue_handles.transfer, transfer_cmd, None, transfer_value)?;
+        
+        let (value, acquire_cmd) = match (self.transfer_family, self.acquire_pool) {
+            (Some(transfer_family), Some(acquire_pool)) => {
+                let acquire_cmd = Self::get_cmd(device, acquire_pool, &mut self.free_acquire_cmds)?;
+                Self::begin(device, acquire_cmd)?;
+                
+                let acquire_barriers:Vec<vk::BufferMemoryBarrier2> = self.pending.iter().map(|copy|{
+                    Self::copy_barrier(copy)
+                        .src_queue_family_index(transfer_family)
+                        .dst_queue_family_index(self.graphics_family)
+                        .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                        .dst_access_mask(vk::AccessFlags2::MEMORY_READ)
+                        .build()
+                }).collect();
+                // the layout transition is repeated in the acquire half, it only happens once
+                let acquire_image_barriers:Vec<vk::ImageMemoryBarrier2> = self.pending_images.iter().map(|copy|{
+                    Self::sampled_barrier(copy)
+                        .src_queue_family_index(transfer_family)
+                        .dst_queue_family_index(self.graphics_family)
+                        .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                        .dst_access_mask(vk::AccessFlags2::MEMORY_READ)
+                        .build()
+                }).collect();
+                Self::barriers(device, acquire_cmd, &acquire_barriers, &acquire_image_barriers);
+                
+                unsafe{device.end_command_buffer(acquire_cmd)}?;
+                
+                let acquire_value = transfer_value + 1;
+                self.submit(device, device.queue_handles.graphics, acquire_cmd, Some(transfer_value), acquire_value)?;
+                (acquire_value, Some(acquire_cmd))
+            }
+            _ => (transfer_value, None),
+        };
+        
+        for region in self.regions.iter_mut().filter(|region|region.value.is_none()) {
+            region.value = Some(value);
+        }
+        self.in_flight.push_back(InFlightBatch{value, transfer_cmd, acquire_cmd});
+        self.pending.clear();
+        self.pending_images.clear();
+        self.last_value = value;
+        
+        Ok(UploadTicket(value))
+    }
+    
+//----
+    // the last finished batch, read once and checked against many tickets
+    pub fn completed(&self, device:&Device) -> Result<u64, AAError> {
+        Ok(unsafe{device.get_semaphore_counter_value(self.timeline)}?)
+    }
+    
+//----
+    pub fn wait(&mut self, device:&Device, ticket:UploadTicket) -> Result<(), AAError> {
+        let wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(from_ref(&self.timeline))
+            .values(from_ref(&ticket.0));
+        unsafe{device.wait_semaphores(&wait_info, u64::MAX)}?;
+        self.collect(device)
+    }
+    
+//----
+    // recycles the command buffers and staging space of the finished batches
+    pub fn collect(&mut self, device:&Device) -> Result<(), AAError> {
+        let completed = self.completed(device)?;
+        
+        while let Some(batch) = self.in_flight.front() {
+            if batch.value > completed {
+                break;
+            }
+            let batch = self.in_flight.pop_front().expect(GRANTED);
+            self.free_transfer_cmds.push(batch.transfer_cmd);
+            if let Some(acquire_cmd) = batch.acquire_cmd {
+                self.free_acquire_cmds.push(acquire_cmd);
+            }
+        }
+        
+        while let Some(StagingRegion{value:Some(value), ..}) = self.regions.front() {
+            if *value > completed {
+                break;
+            }
+            self.regions.pop_front();
+        }
+        Ok(())
+    }
+    
+//----
+    fn reserve(&mut self, device:&Device, size:u64) -> Result<u64, AAError> {
+        if size > STAGING_SIZE {
+            return Err(AAError::UploadTooLarge(size));
+        }
+        loop {
+            self.collect(device)?;
+            if let Some(start) = self.find_space(size) {
+                self.regions.push_back(StagingRegion{start, end:start+size, value:None});
+                return Ok(start);
+            }
+            // the ring is full, submit what is pending and wait for the oldest batch to free its space
+            if !self.pending.is_empty() || !self.pending_images.is_empty() {
+                self.flush(device)?;
+            }
+            let oldest = self.in_flight.front().expect(GRANTED).value;
+            self.wait(device, UploadTicket(oldest))?;
+        }
+    }
+    
+//----
+    // copies data into a new region of the staging ring, returning its offset
+    fn stage<T:Copy>(&mut self, device:&Device, data:&[T]) -> Result<u64, AAError> {
+        let size = u64::try_from(size_of_val(data)).expect(VK_CAST);
+        let offset = self.reserve(device, size)?;
+        let offset_usize = usize::try_from(offset).expect(VK_CAST);
+        let mut align = self.staging.get_align::<T>(offset_usize, size).expect(CPU_ACCESIBLE);
+        align.copy_from_slice(data);
+        Ok(offset)
+    }
+    
+//----
+    fn find_space(&self, size:u64) -> Option<u64> {
+        let (front, back) = match (self.regions.front(), self.regions.back()) {
+            (Some(front), Some(back)) => (front.start, back.end),
+            _ => {return Some(0);}
+        };
+        let head = back.div_ceil(STAGING_ALIGNMENT) * STAGING_ALIGNMENT;
+        if back > front {
+            if head + size <= STAGING_SIZE {
+                Some(head)
+            } else if size <= front {
+                Some(0)
+            } else {
+                None
+            }
+        } else if head + size <= front {
+            Some(head)
+        } else {
+            None
+        }
+    }
+    
+//----
+    fn get_cmd(device:&Device, pool:vk::CommandPool, free:&mut Vec<vk::CommandBuffer>) -> Result<vk::CommandBuffer, AAError> {
+        if let Some(cmd) = free.pop() {
+            unsafe{device.reset_command_buffer(cmd, vk::CommandBufferResetFlags::empty())}?;
+            return Ok(cmd);
+        }
+        let allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_pool(pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+        let cmd = unsafe{device.allocate_command_buffers(&allocate_info)}?[0];
+        device.set_object_name(cmd, "upload command buffer");
+        Ok(cmd)
+    }
+    
+//----
+    fn begin(device:&Device, cmd:vk::CommandBuffer) -> Result<(), AAError> {
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        Ok(unsafe{device.begin_command_buffer(cmd, &begin_info)}?)
+    }
+    
+//----
+    fn copy_barrier(copy:&PendingCopy) -> vk::BufferMemoryBarrier2Builder<'static> {
+        vk::BufferMemoryBarrier2::builder()
+            .buffer(copy.dst)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .offset(copy.region.dst_offset)
+            .size(copy.region.size)
+    }
+    
+//----
+    fn image_barrier(copy:&PendingImageCopy) -> vk::ImageMemoryBarrier2Builder<'static> {
+        let subresource = copy.region.image_subresource;
+        let range = vk::ImageSubresourceRange{
+            aspect_mask: subresource.aspect_mask,
+            base_mip_level: subresource.mip_level,
+            level_count: 1,
+            base_array_layer: subresource.base_array_layer,
+            layer_count: subresource.layer_count,
+        };
+        vk::ImageMemoryBarrier2::builder()
+            .image(copy.dst)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .subresource_range(range)
+    }
+    
+//----
+    fn sampled_barrier(copy:&PendingImageCopy) -> vk::ImageMemoryBarrier2Builder<'static> {
+        Self::image_barrier(copy)
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+    }
+    
+//----
+    fn barriers(device:&Device, cmd:vk::CommandBuffer, buffer_barriers:&[vk::BufferMemoryBarrier2], image_barriers:&[vk::ImageMemoryBarrier2]) {
+        if buffer_barriers.is_empty() && image_barriers.is_empty() {
+            return;
+        }
+        let dependency = vk::DependencyInfo::builder()
+            .buffer_memory_barriers(buffer_barriers)
+            .image_memory_barriers(image_barriers);
+        unsafe{device.cmd_pipeline_barrier2(cmd, &dependency)};
+    }
+    
+//----
+    fn submit(&self, device:&Device, queue:vk::Queue, cmd:vk::CommandBuffer, wait:Option<u64>, signal:u64) -> Result<(), AAError> {
+        let command_submit_info = vk::CommandBufferSubmitInfo::builder()
+            .command_buffer(cmd);
+        
+        let wait_semaphore_submit_info = vk::SemaphoreSubmitInfo::builder()
+            .semaphore(self.timeline)
+            .value(wait.unwrap_or(0))
+            .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS);
+        
+        let signal_semaphore_submit_info = vk::SemaphoreSubmitInfo::builder()
+            .semaphore(self.timeline)
+            .value(signal)
+            .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS);
+        
+        let wait_semaphore_infos:&[vk::SemaphoreSubmitInfo] = match wait {
+            Some(_) => from_ref(&wait_semaphore_submit_info),
+            None => &[],
+        };
+        
+        let submit_info = vk::SubmitInfo2::builder()
+            .command_buffer_infos(from_ref(&command_submit_info))
+            .wait_semaphore_infos(wait_semaphore_infos)
+            .signal_semaphore_infos(from_ref(&signal_semaphore_submit_info));
+        
+        unsafe{device.queue_submit2(queue, from_ref(&submit_info), vk::Fence::null())}?;
+        Ok(())
+    }
+}
+
+impl UploadTicket {
+    pub fn is_reached(self, completed:u64) -> bool {
+        completed >= self.0
+    }
+}
+
+impl VkDestructor for UploadManager {
+    fn destruct(self, mut args:VkDestructorArguments) {
+        logger::destruct!("upload_manager");
+        let (device, allocator) = args.unwrap_dev_all();
+        
+        let wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(from_ref(&self.timeline))
+            .values(from_ref(&self.last_value));
+        // a lost device finishes nothing, there is nothing left to wait for either
+        if let Err(error) = unsafe{device.wait_semaphores(&wait_info, u64::MAX)} {
+            logger::various_log!("upload_manager",
+                (logger::Warn, "pending uploads not awaited {:?}", error)
+            );
+        }
+        
+        unsafe{device.destroy_command_pool(self.transfer_pool, None)};
+        if let Some(pool) = self.acquire_pool {
+            unsafe{device.destroy_command_pool(pool, None)};
+        }
+        unsafe{device.destroy_semaphore(self.timeline, None)};
+        self.staging.destruct(VkDestructorArguments::DevAll(device, allocator));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::AAError;
+use crate::logger;
+use crate::errors::messages::VK_CAST;
+use crate::errors::messages::GRANTED;
+use crate::errors::messages::CPU_ACCESIBLE;
+
+use super::MaterialConstants;
+
+use super::super::init::*;
+use super::super::objects::*;
+
+use std::collections::HashMap;
+use std::slice::from_ref;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use ash::vk;
+use gpu_allocator as gpu_all;
+
+pub const MAX_TEXTURES:u32 = 4096;
+pub const MAX_SAMPLERS:u32 = 16;
+pub const MAX_MATERIALS:u32 = 1024;
+
+const MATERIAL_BINDING:u32 = 0;
+const SAMPLER_BINDING:u32 = 1;
+const TEXTURE_BINDING:u32 = 2;
+
+// single descriptor set shared by every material, draws select their material with an index in the push constant
+pub struct BindlessTables {
+    pool: vk::DescriptorPool,
+    layout: DescriptorLayout,
+    set: vk::DescriptorSet,
+    material_buffer: Buffer,
+    textures: HashMap<vk::ImageView, u32>,
+    samplers: HashMap<vk::Sampler, u32>,
+    texture_count: u32,
+    material_count: u32,
+    free_textures: Vec<u32>,
+    free_materials: Vec<u32>,
+    // slots handed back by the destruction stack, they are reused on the next write
+    released: Rc<RefCell<ReleasedSlots>>,
+}
+
+#[derive(Default)]
+struct ReleasedSlots {
+    textures: Vec<u32>,
+    materials: Vec<u32>,
+}
+
+
+impl BindlessTables {
+    pub fn create(device:&mut Device, allocator:&mut Allocator) -> Result<Self, AAError> {
+        logger::create!("bindless_tables");
+        
+        use vk::DescriptorBindingFlags as flags;
+        let mut layout_builder = DescriptorLayoutBuilder::create();
+        layout_builder.add_binding(MATERIAL_BINDING, vk::DescriptorType::STORAGE_BUFFER, 1);
+        layout_builder.add_flagged_binding(SAMPLER_BINDING, vk::DescriptorType::SAMPLER, MAX_SAMPLERS, flags::PARTIALLY_BOUND|flags::UPDATE_AFTER_BIND);
+        // the variable count binding has to be the last one
+        layout_builder.add_flagged_binding(TEXTURE_BINDING, vk::DescriptorType::SAMPLED_IMAGE, MAX_TEXTURES, flags::PARTIALLY_BOUND|flags::UPDATE_AFTER_BIND|flags::VARIABLE_DESCRIPTOR_COUNT);
+        let (layout, _types_in_layout) = layout_builder.build(device, vk::ShaderStageFlags::VERTEX|vk::ShaderStageFlags::FRAGMENT)?;
+        
+        let pool_sizes = [
+            vk::DescriptorPoolSize{ty:vk::DescriptorType::STORAGE_BUFFER, descriptor_count:1},
+            vk::DescriptorPoolSize{ty:vk::DescriptorType::SAMPLER, descriptor_count:MAX_SAMPLERS},
+            vk::DescriptorPoolSize{ty:vk::DescriptorType::SAMPLED_IMAGE, descriptor_count:MAX_TEXTURES},
+        ];
+        let pool_ci = vk::DescriptorPoolCreateInfo::builder()
+            .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+            .max_sets(1)
+            .pool_sizes(&pool_sizes);
+        let pool = unsafe{device.create_descriptor_pool(&pool_ci, None)}?;
+        
+        let set_layout = layout.underlying();
+        let mut variable_count = vk::DescriptorSetVariableDescriptorCountAllocateInfo::builder()
+            .descriptor_counts(from_ref(&MAX_TEXTURES));
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(from_ref(&set_layout))
+            .push_next(&mut variable_count);
+        let set = unsafe{device.allocate_descriptor_sets(&allocate_info)}?.pop().expect(GRANTED);
+        device.set_object_name(set_layout, "bindless layout");
+        device.set_object_name(pool, "bindless pool");
+        device.set_object_name(set, "bindless set");
+        
+        let material_buffer_size = u64::from(MAX_MATERIALS) * MaterialConstants::size_u64();
+        let material_buffer = Buffer::create(device, allocator, Some("bindless material table"), material_buffer_size, vk::BufferUsageFlags::STORAGE_BUFFER, gpu_all::MemoryLocation::CpuToGpu)?;
+        
+        let mut writer = DescriptorWriter::default();
+        writer.write_buffer(MATERIAL_BINDING, material_buffer.underlying(), material_buffer_size, 0, vk::DescriptorType::STORAGE_BUFFER);
+        writer.update_set(device, set);
+        
+        Ok(Self{
+            pool,
+            layout,
+            set,
+            material_buffer,
+            textures: HashMap::new(),
+            samplers: HashMap::new(),
+            texture_count: 0,
+            material_count: 0,
+            free_textures: Vec::new(),
+            free_materials: Vec::new(),
+            released: Rc::new(RefCell::new(ReleasedSlots::default())),
+        })
+    }
+    
+    pub fn layout(&self) -> &DescriptorLayout {
+        &self.layout
+    }
+    
+    pub fn set(&self) -> vk::DescriptorSet {
+        self.set
+    }
+    
+//----
+    // the same image view is only written once, the image has to be in SHADER_READ_ONLY_OPTIMAL
+    pub fn texture_index(&mut self, device:&Device, image:&Image) -> Result<u32, AAError> {
+        if let Some(index) = self.textures.get(&image.view) {
+            return Ok(*index);
+        }
+        self.reclaim();
+        let index = match self.free_textures.pop() {
+            Some(index) => index,
+            None if self.texture_count < MAX_TEXTURES => {
+                self.texture_count += 1;
+                self.texture_count - 1
+            }
+            None => return Err(AAError::BindlessTableFull("texture")),
+        };
+        let mut writer = DescriptorWriter::default();
+        writer.write_tracked_image_element(TEXTURE_BINDING, index, image, vk::Sampler::null(), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::DescriptorType::SAMPLED_IMAGE);
+        writer.update_set(device, self.set);
+        self.textures.insert(image.view, index);
+        Ok(index)
+    }
+    
+//----
+    pub fn sampler_index(&mut self, device:&Device, sampler:&Sampler) -> Result<u32, AAError> {
+        if let Some(index) = self.samplers.get(&sampler.underlying()) {
+            return Ok(*index);
+        }
+        let index = u32::try_from(self.samplers.len()).expect(VK_CAST);
+        if index >= MAX_SAMPLERS {
+            return Err(AAError::BindlessTableFull("sampler"));
+        }
+        let mut writer = DescriptorWriter::default();
+        writer.write_image_element(SAMPLER_BINDING, index, vk::ImageView::null(), sampler.underlying(), vk::ImageLayout::UNDEFINED, vk::DescriptorType::SAMPLER);
+        writer.update_set(device, self.set);
+        self.samplers.insert(sampler.underlying(), index);
+        Ok(index)
+    }
+    
+//----
+    // new slots are never read by the frames in flight, so the table is written in place
+    pub fn add_material(&mut self, constants:&MaterialConstants) -> Result<u32, AAError> {
+        self.reclaim();
+        let index = match self.free_materials.pop() {
+            Some(index) => index,
+            None if self.material_count < MAX_MATERIALS => {
+                self.material_count += 1;
+                self.material_count - 1
+            }
+            None => return Err(AAError::BindlessTableFull("material")),
+        };
+        let offset = usize::try_from(u64::from(index) * MaterialConstants::size_u64()).expect(VK_CAST);
+        {
+            let mut align = self.material_buffer.get_align::<MaterialConstants>(offset, MaterialConstants::size_u64()).expect(CPU_ACCESIBLE);
+            align.copy_from_slice(from_ref(constants));
+        }
+        Ok(index)
+    }
+    
+//----
+    // the view is forgotten right away so a new image reusing the handle gets its own slot,
+    // the slot itself is only reused once the destructor runs
+    pub fn defered_release_texture(&mut self, image:&Image) -> Option<VkDynamicDestructor> {
+        let index = self.textures.remove(&image.view)?;
+        let released = self.released.clone();
+        let callback = Box::new(move |mut args:VkDestructorArguments|{
+            args.unwrap_none();
+            released.borrow_mut().textures.push(index);
+        });
+        Some((callback, VkDestructorType::None))
+    }
+    
+//----
+    pub fn defered_release_material(&mut self, index:u32) -> VkDynamicDestructor {
+        let released = self.released.clone();
+        let callback = Box::new(move |mut args:VkDestructorArguments|{
+            args.unwrap_none();
+            released.borrow_mut().materials.push(index);
+        });
+        (callback, VkDestructorType::None)
+    }
+    
+    fn reclaim(&mut self) {
+        let mut released = self.released.borrow_mut();
+        self.free_textures.append(&mut released.textures);
+        self.free_materials.append(&mut released.materials);
+    }
+}
+
+impl VkDestructor for BindlessTables {
+    fn destruct(self, mut args:VkDestructorArguments) {
+        logger::destruct!("bindless_tables");
+        let (device, allocator) = args.unwrap_dev_all();
+        unsafe{device.destroy_descriptor_pool(self.pool, None)};
+        self.layout.destruct(VkDestructorArguments::Dev(device));
+        self.material_buffer.destruct(VkDestructorArguments::DevAll(device, allocator));
+    }
+}
@@ -12,8 +12,6 @@ pub struct MetalicMaterial {
     opaque_pipeline: vk::Pipeline,
     transparent_pipeline: vk::Pipeline,
     pipeline_layout: vk::PipelineLayout,
-    descriptor_writer: DescriptorWriter,
-    descriptor_layout: DescriptorLayout,
     
     dispatchable_opaque: Rc<DispatchableGPipeline>,
     dispatchable_transparent: Rc<DispatchableGPipeline>,
@@ -25,7 +23,7 @@ impl MetalicMaterial {
     pub fn write_material(
         &mut self, 
         device: &mut Device, 
-        descriptor_allocator: &mut GDescriptorAllocator, 
+        bindless: &mut BindlessTables, 
         material_pass: MaterialPass, 
         material_resources: &MaterialResources
     ) -> Result<MaterialInstance, AAError> {
@@ -43,31 +41,24 @@ impl MetalicMaterial {
         };
         
         
-        let descriptor_holder = descriptor_allocator.allocate(device, &self.descriptor_layout)?;
-        
-        
-        let Self{
-            descriptor_writer: writer,
-            ..
-        } = self;
-        
-        writer.clear();
-        writer.write_buffer(0, material_resources.buffer.underlying(), std::mem::size_of::<MaterialConstants>() as u64, material_resources.buffer_offset, vk::DescriptorType::UNIFORM_BUFFER);
-        writer.write_image(1, material_resources.color_image.view, material_resources.color_sampler.underlying(), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::DescriptorType::COMBINED_IMAGE_SAMPLER);
-        writer.write_image(2, material_resources.metal_image.view, material_resources.metal_sampler.underlying(), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, vk::DescriptorType::COMBINED_IMAGE_SAMPLER);
-        
-        
-        writer.update_set(device, descriptor_holder);
+        let constants = MaterialConstants{
+            color_texture: bindless.texture_index(device, material_resources.color_image)?,
+            color_sampler: bindless.sampler_index(device, material_resources.color_sampler)?,
+            metal_texture: bindless.texture_index(device, material_resources.metal_image)?,
+            metal_sampler: bindless.sampler_index(device, material_resources.metal_sampler)?,
+            ..material_resources.constants
+        };
+        let material_index = bindless.add_material(&constants)?;
         
         
         Ok(MaterialInstance{
             pipeline: pipeline_holder,
-            descriptor_set: descriptor_holder,
+            material_index,
             pass_type: material_pass,
         })
     }
     
-    pub fn build_pipelines(device:&mut Device, canvas:&Canvas, scene_descriptor:&DescriptorLayout) -> Result<Self, AAError> {
+    pub fn build_pipelines(device:&mut Device, canvas:&Canvas, scene_descriptor:&DescriptorLayout, bindless_descriptor:&DescriptorLayout) -> Result<Self, AAError> {
         logger::create!("metalic_material");
         let vert_module = pipeline::create_shader_module(device, constants::graph::MESH_VERT)?;
         let frag_module = pipeline::create_shader_module(device, constants::graph::MESH_FRAG)?;
@@ -76,28 +67,22 @@ impl MetalicMaterial {
             .size(GPUDrawPushConstants::size_u32())
             .stage_flags(vk::ShaderStageFlags::VERTEX);
         
-        let mut layout_builder = DescriptorLayoutBuilder::create();
-        layout_builder.add_binding(0, vk::DescriptorType::UNIFORM_BUFFER, 1);
-        layout_builder.add_binding(1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1);
-        layout_builder.add_binding(2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1);
-        
-        let (material_descriptor_layout, material_types_in_layout) = layout_builder.build(device, vk::ShaderStageFlags::FRAGMENT|vk::ShaderStageFlags::VERTEX)?;
-        
-        let descriptor_layouts = [scene_descriptor.underlying(), material_descriptor_layout.underlying()];
+        let descriptor_layouts = [scene_descriptor.underlying(), bindless_descriptor.underlying()];
         
         let layout_ci = vk::PipelineLayoutCreateInfo::builder()
             .push_constant_ranges(from_ref(&push_constant_description))
             .set_layouts(&descriptor_layouts[..]);
         
-        let pipeline_layout = unsafe{device.create_pipeline_layout(&layout_ci, None)}.unwrap();
+        let pipeline_layout = unsafe{device.create_pipeline_layout(&layout_ci, None)}?;
         
         let opaque_pipeline = Self::create_opaque_pipeline(device, canvas, pipeline_layout, vert_module, frag_module)?;
         let transparent_pipeline = Self::create_transparent_pipeline(device, canvas, pipeline_layout, vert_module, frag_module)?;
         
-        let descriptor_writer = DescriptorWriter::default();
-        
         unsafe{device.destroy_shader_module(vert_module, None)};
         unsafe{device.destroy_shader_module(frag_module, None)};
+        device.set_object_name(pipeline_layout, "metalic layout");
+        device.set_object_name(opaque_pipeline, "metalic opaque pipeline");
+        device.set_object_name(transparent_pipeline, "metalic transparent pipeline");
         
         let dispatchable_opaque = Rc::new(DispatchableGPipeline{
             pipeline: opaque_pipeline,
@@ -113,8 +98,6 @@ impl MetalicMaterial {
             opaque_pipeline,
             transparent_pipeline,
             pipeline_layout,
-            descriptor_writer,
-            descriptor_layout: material_descriptor_layout,
             
             dispatchable_opaque,
             dispatchable_transparent,
@@ -167,7 +150,6 @@ impl MetalicMaterial {
         unsafe{device.destroy_pipeline(self.opaque_pipeline, None)};
         unsafe{device.destroy_pipeline(self.transparent_pipeline, None)};
         unsafe{device.destroy_pipeline_layout(self.pipeline_layout, None)};
-        self.descriptor_layout.destruct(VkDestructorArguments::Dev(device));
         
         let count = Rc::strong_count(&self.dispatchable_opaque);
         if count != 1 {
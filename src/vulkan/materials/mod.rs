@@ -1,12 +1,14 @@
 mod metalic;
 pub use metalic::*;
 
+mod bindless;
+pub use bindless::*;
+
 use crate::logger;
 
 use crate::AAError;
 use crate::constants;
 use crate::errors::messages::COMPILETIME_ASSERT;
-use crate::errors::messages::CPU_ACCESIBLE;
 
 use super::graphics::*;
 use super::init::*;
@@ -17,7 +19,6 @@ use std::slice::from_ref;
 use std::mem::size_of;
 
 use ash::vk;
-use gpu_allocator as gpu_all;
 use nalgebra as na;
 
 pub struct Materials {
@@ -28,7 +29,8 @@ pub struct Materials {
 
 pub struct MaterialInstance {
     pub pipeline: Rc<DispatchableGPipeline>,
-    pub descriptor_set: vk::DescriptorSet,
+    // index in the bindless material table
+    pub material_index: u32,
     pub pass_type: MaterialPass,
 }
 
@@ -36,7 +38,7 @@ impl Clone for MaterialInstance {
     fn clone(&self) -> Self {
         Self{
             pipeline: self.pipeline.clone(),
-            descriptor_set: self.descriptor_set,
+            material_index: self.material_index,
             pass_type: self.pass_type,
         }
     }
@@ -54,16 +56,20 @@ pub struct MaterialResources<'a> {
     pub color_sampler: &'a Sampler,
     pub metal_image: &'a Image,
     pub metal_sampler: &'a Sampler,
-    pub buffer: Buffer,
-    pub buffer_offset: u64,
+    pub constants: MaterialConstants,
 }
 
+// element of the material storage buffer, matches Material in input_structures.glsl
 #[repr(C)]
 #[derive(Default, Clone, Copy)]
 pub struct MaterialConstants {
     pub color_factors: na::Vector4<f32>,
     pub metal_rough_factors: na::Vector4<f32>,
-    pub extra: [na::Vector4<f32>;14],
+    // bindless table indices, filled by write_material
+    pub color_texture: u32,
+    pub color_sampler: u32,
+    pub metal_texture: u32,
+    pub metal_sampler: u32,
 }
 
 const _:u64 = MaterialConstants::size_u64();
@@ -75,16 +81,22 @@ impl MaterialConstants {
         }
         size_of::<Self>() as u64
     }
+    
+    pub fn opaque() -> Self {
+        Self{
+            color_factors: na::Vector4::new(1f32,1f32,1f32,1f32),
+            metal_rough_factors: na::Vector4::new(1f32,0.5f32,0f32,0f32),
+            ..Self::default()
+        }
+    }
 }
 
 
 
 pub fn init_material(
     device: &mut Device, 
-    allocator:&mut Allocator, 
     canvas:&Canvas, 
-    ds_pool:&mut GDescriptorAllocator, 
-    destruction_stack: &mut DestructionStack,
+    bindless: &mut BindlessTables,
     scene_descriptor:&DescriptorLayout, 
     white_texture: &Image,
     linear_sampler: &Sampler,
@@ -93,29 +105,17 @@ pub fn init_material(
     
     logger::create!("materials");
     
-    let mut metalic = MetalicMaterial::build_pipelines(device, canvas, scene_descriptor)?;
-    let mut buffer = Buffer::create(device, allocator, Some("Metalic material"), MaterialConstants::size_u64(), vk::BufferUsageFlags::UNIFORM_BUFFER, gpu_all::MemoryLocation::CpuToGpu)?;
-    let holder = MaterialConstants{
-        color_factors: na::Vector4::new(1f32,1f32,1f32,1f32),
-        metal_rough_factors: na::Vector4::new(1f32,0.5f32,0f32,0f32),
-        ..MaterialConstants::default()
-    };
-    destruction_stack.push(buffer.defered_destruct());
-    {
-        let mut align = buffer.get_align::<MaterialConstants>(0, MaterialConstants::size_u64()).expect(CPU_ACCESIBLE);
-        align.copy_from_slice(from_ref(&holder));
-    }
+    let mut metalic = MetalicMaterial::build_pipelines(device, canvas, scene_descriptor, bindless.layout())?;
     
     let material_resources = MaterialResources{
-        buffer,
-        buffer_offset: 0,
+        constants: MaterialConstants::opaque(),
         metal_image: white_texture,
         metal_sampler: linear_sampler,
         color_image: white_texture,
         color_sampler: linear_sampler,
     };
     
-    let metalic_instance = metalic.write_material(device, ds_pool, MaterialPass::MainColor, &material_resources)?;
+    let metalic_instance = metalic.write_material(device, bindless, MaterialPass::MainColor, &material_resources)?;
     
     
     Ok(Materials{
@@ -3,6 +3,9 @@ pub use init::*;
 
 mod graphics;
 use graphics::*;
+pub use graphics::bake_models;
+pub use graphics::MeshHandle;
+pub use graphics::MaterialHandle;
 
 mod objects;
 
@@ -11,10 +14,17 @@ mod helpers;
 mod materials;
 use materials::*;
 
+mod render_graph;
+use render_graph::TransientImages;
+
 use crate::logger;
 use crate::gui::InputData;
-use crate::errors::messages::SIMPLE_VK_FN;
-use crate::errors::messages::VK_UNRECOVERABLE;
+use crate::graphics::CaptureRequest;
+use crate::graphics::Recording;
+use crate::graphics::RecordingSettings;
+use crate::config;
+use crate::config::Config;
+use crate::AAError;
 
 use super::window::Window;
 use super::constants;
@@ -40,6 +50,7 @@ pub struct VInit {
     frame_control: FrameControl,
     
     resize_required: bool,
+    present_mode: vk::PresentModeKHR,
     
     pub instance: VkWrapper<Instance>,
     messenger: Option<VkWrapper<DMessenger>>,
@@ -50,8 +61,11 @@ pub struct VInit {
     pub swapchain: VkWrapper<Swapchain>,
     
     pub command_control: VkWrapper<CommandControl>,
+    upload_manager: VkWrapper<UploadManager>,
+    asset_streamer: VkWrapper<AssetStreamer>,
     
     canvas: VkWrapper<graphics::Canvas>,
+    transient_images: VkWrapper<TransientImages>,
     
     background_image_descriptor_layout: VkWrapper<DescriptorLayout>,
     texture_descriptor_layout: VkWrapper<DescriptorLayout>,
@@ -64,9 +78,13 @@ pub struct VInit {
     //mesh_assets: VkWrapper<MeshAssets>,
     
     materials: VkWrapper<Materials>,
-    mesh_assets: VkWrapper<VkMeshAssets>,
+    bindless: VkWrapper<BindlessTables>,
+    geometry: VkWrapper<GeometryBuffers>,
+    assets: VkWrapper<AssetManager>,
+    indirect_drawer: VkWrapper<IndirectDrawer>,
     
     main_draw_context: DrawContext,
+    gpu_driven: bool,
     
     compute_effect_index: usize,
     mesh_index: usize,
@@ -75,6 +93,11 @@ pub struct VInit {
     
     frames_data: VkWrapper<graphics::FramesData>,
     scene_data: graphics::GPUSceneData,
+    gpu_timings: GpuTimings,
+    // picked up by the next recorded frame
+    capture_request: Option<CaptureRequest>,
+    recording: Option<Recording>,
+    recording_config: config::RecordingConfig,
     gpu_scene_layout: VkWrapper<DescriptorLayout>,
     
     fuzzy_sampler: VkWrapper<Sampler>,
@@ -92,19 +115,18 @@ pub struct VInit {
 
 
 impl VInit {
-    pub fn init(window:&mut Window) -> VInit {
+    // a failure part way leaks what was already created, the caller can only give up on vulkan at that point
+    pub fn init(window:&mut Window, config:&Config) -> Result<VInit, AAError> {
         
         //panic!("{:?}", ImageMetadata::texture("a"));
         
-        let mut instance = vk_create_interpreter(Instance::create(window), "instance"); 
+        if config.validation {
+            DMessenger::configure(&config.validation_suppress);
+        }
+        let mut instance = Instance::create(window, config.validation)?; 
         
-        let messenger = if constants::VALIDATION {
-            Some(match DMessenger::create(&instance) {
-                Ok(messenger) => {
-                    messenger
-                }
-                Err(err) => {panic!("{:?}", err);}
-            })
+        let messenger = if config.validation {
+            Some(DMessenger::create(&instance)?)
         } else {
             logger::various_log!("debug_messenger",
                 (logger::Debug, "DEBUG_MESSENGER NOT ENABLED"),
@@ -112,36 +134,40 @@ impl VInit {
             None
         };
         
-        let surface =  vk_create_interpreter(Surface::create(&window, &mut instance), "surface"); 
-        let p_device = vk_create_interpreter(PDevice::chose(&instance, &surface), "p_device selected"); 
-        let mut device = vk_create_interpreter(Device::create(&mut instance, &p_device), "device"); 
-        let mut allocator = vk_create_interpreter(Allocator::create(&mut instance, &p_device, &mut device), "allocator");
-        let swapchain = vk_create_interpreter(Swapchain::create(&mut instance, &surface, &p_device, &mut device), "swapchain");
-        let mut command_control = vk_create_interpreter(CommandControl::create(&p_device, &mut device), "command_control");
+        let surface = Surface::create(window, &mut instance)?; 
+        let p_device = PDevice::chose(&instance, &surface, config.gpu.as_ref())?; 
+        let mut device = Device::create(&mut instance, &p_device)?; 
+        let mut allocator = Allocator::create(&mut instance, &p_device, &mut device)?;
+        let present_mode = config.present_mode.underlying();
+        let swapchain = Swapchain::create(&mut instance, &surface, &p_device, &mut device, present_mode)?;
+        let command_control = CommandControl::create(&p_device, &mut device)?;
+        let mut upload_manager = UploadManager::create(&p_device, &mut device, &mut allocator)?;
         
-        let mut destruction_stack = objects::DestructionStack::new();
+        let destruction_stack = objects::DestructionStack::new();
         
-        let mut canvas = Canvas::new(&mut device, &mut allocator, swapchain.extent.into()).unwrap();
+        let canvas = Canvas::new(&mut device, &mut allocator, swapchain.extent.into())?;
         let render_image = canvas.get_color();
         
-        let (mut ds_pool, background_image_ds, background_image_descriptor_layout, texture_descriptor_layout) = init_descriptors(&mut device, &render_image);
-        let compute_effects = c_pipeline::init_pipelines(&mut device, &background_image_descriptor_layout);
+        let (ds_pool, background_image_ds, background_image_descriptor_layout, texture_descriptor_layout) = init_descriptors(&mut device, render_image)?;
+        let compute_effects = c_pipeline::init_pipelines(&mut device, &background_image_descriptor_layout)?;
         
         
-        let frames_data = FramesData::create(&p_device, &mut device).unwrap();
+        let frames_data = FramesData::create(&p_device, &mut device)?;
         
         
         let mut ds_layout_builder = DescriptorLayoutBuilder::create();
         ds_layout_builder.add_binding(0, vk::DescriptorType::UNIFORM_BUFFER, 1);
-        let (gpu_scene_layout, _types_in_layout) = ds_layout_builder.build(&mut device, vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT).unwrap();
+        let (gpu_scene_layout, _types_in_layout) = ds_layout_builder.build(&mut device, vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)?;
+        device.set_object_name(gpu_scene_layout.underlying(), "gpu scene layout");
         
         
-        let (white_texture, grey_texture, black_texture, error_texture) = init_textures(&mut device, &mut allocator, &mut command_control);
+        let (white_texture, grey_texture, black_texture, error_texture) = init_textures(&mut device, &mut allocator, &mut upload_manager)?;
         
-        let pixelated_sampler = Sampler::create(&mut device, vk::Filter::NEAREST).unwrap();
-        let fuzzy_sampler = Sampler::create(&mut device, vk::Filter::LINEAR).unwrap();
+        let pixelated_sampler = Sampler::create(&mut device, vk::Filter::NEAREST)?;
+        let fuzzy_sampler = Sampler::create(&mut device, vk::Filter::LINEAR)?;
         
-        let materials = materials::init_material(&mut device, &mut allocator, &canvas, &mut ds_pool, &mut destruction_stack, &gpu_scene_layout, &white_texture, &fuzzy_sampler).unwrap();
+        let mut bindless = BindlessTables::create(&mut device, &mut allocator)?;
+        let materials = materials::init_material(&mut device, &canvas, &mut bindless, &gpu_scene_layout, &white_texture, &fuzzy_sampler)?;
         
         /*
         let Materials{
@@ -153,17 +179,28 @@ impl VInit {
         */
         
         
-        let (render_image, depth_image) = canvas.get_images();
         //let mesh_assets = load_gltf(&mut device, &mut allocator, &mut command_control, "res/gltf/basicmesh.glb").expect("runtime error");
-        let mesh_assets = load_gltf(&mut device, &mut allocator, &mut command_control, "res/gltf/Fox.glb").expect("runtime error");
+        let geometry = GeometryBuffers::create(&mut device, &mut allocator, VERTEX_CAPACITY, INDEX_CAPACITY)?;
+        let mut asset_streamer = AssetStreamer::new(config.optimize_meshes, config.asset_cache);
+        let mut assets = AssetManager::new();
+        for model in config.models.iter() {
+            let path = config::asset_path(model);
+            if let Err(error) = asset_streamer.load(&mut assets, &path) {
+                logger::various_log!("streaming",
+                    (logger::Warn, "can't open {:?} {:?}", path, error)
+                );
+            }
+        }
+        let indirect_drawer = IndirectDrawer::create(&mut device)?;
         
         let main_draw_context = DrawContext::default();
         
         
         
-        VInit{
+        Ok(VInit{
             frame_control: FrameControl(0),
             resize_required: false,
+            present_mode,
             
             instance: VkWrapper::new(instance),
             
@@ -178,8 +215,11 @@ impl VInit {
             allocator: VkWrapper::new(allocator), 
             swapchain: VkWrapper::new(swapchain),
             command_control: VkWrapper::new(command_control),
+            upload_manager: VkWrapper::new(upload_manager),
+            asset_streamer: VkWrapper::new(asset_streamer),
             
             canvas: VkWrapper::new(canvas),
+            transient_images: VkWrapper::new(TransientImages::new()),
             
             texture_descriptor_layout: VkWrapper::new(texture_descriptor_layout),
             background_image_descriptor_layout: VkWrapper::new(background_image_descriptor_layout),
@@ -190,10 +230,14 @@ impl VInit {
             compute_effect_index:0,
             
             //mesh_pipeline: VkWrapper::new(mesh_pipeline),
-            mesh_assets: VkWrapper::new(mesh_assets),
+            geometry: VkWrapper::new(geometry),
+            assets: VkWrapper::new(assets),
+            indirect_drawer: VkWrapper::new(indirect_drawer),
             main_draw_context,
+            gpu_driven: true,
             
             materials: VkWrapper::new(materials),
+            bindless: VkWrapper::new(bindless),
             
             mesh_index: 0,
             
@@ -203,6 +247,10 @@ impl VInit {
             frames_data: VkWrapper::new(frames_data),
             
             scene_data: GPUSceneData::default(),
+            gpu_timings: GpuTimings::default(),
+            capture_request: None,
+            recording: None,
+            recording_config: config.recording.clone(),
             gpu_scene_layout: VkWrapper::new(gpu_scene_layout),
             
             white_texture: VkWrapper::new(white_texture),
@@ -214,11 +262,60 @@ impl VInit {
             fuzzy_sampler: VkWrapper::new(fuzzy_sampler),
             
             destruction_stack: destruction_stack,
+        })
+        
+    }
+    
+//----
+    // after a failed frame nothing built on the device can be trusted, everything is destroyed and built again,
+    // the loaded scenes are streamed again from their files and what the gui changed is kept
+    pub fn rebuild(mut self, window:&mut Window, config:&Config) -> Result<VInit, AAError> {
+        logger::various_log!("vulkan",
+            (logger::Warn, "rebuilding the renderer")
+        );
+        let scenes = self.assets.scene_loads();
+        let push_constants = self.compute_effects.push_constants.clone();
+        let kept = (self.compute_effect_index, self.mesh_index, self.field_of_view, self.downscale_coheficient, self.gpu_driven);
+        // the frames in flight are lost, the recording goes on with the next ones
+        let recording = self.recording.take();
+        
+        // a lost device has nothing left to run, waiting only reports the loss
+        if let Err(error) = self.wait_idle() {
+            logger::various_log!("vulkan",
+                (logger::Debug, "device not idle before the rebuild {:?}", error)
+            );
         }
+        std::mem::drop(self);
         
+        let mut fresh = VInit::init(window, &Config{models: Vec::new(), ..config.clone()})?;
+        if fresh.compute_effects.push_constants.len() == push_constants.len() {
+            fresh.compute_effects.push_constants = push_constants;
+        }
+        (fresh.compute_effect_index, fresh.mesh_index, fresh.field_of_view, fresh.downscale_coheficient, fresh.gpu_driven) = kept;
+        fresh.recording = recording;
+        for path in scenes.iter() {
+            fresh.open_model(path);
+        }
+        Ok(fresh)
     }
     
-    pub fn gui_tick(&mut self, data:&InputData) {
+    pub fn gui_tick(&mut self, data:&InputData) -> Result<(), AAError> {
+        
+        if data.load_requested {
+            self.open_model(config::asset_path(data.model_path.trim()));
+        }
+        if let Some(job) = data.unload_requested {
+            self.unload_model(job);
+        }
+        if data.capture_requested {
+            self.request_capture(data.capture);
+        }
+        if data.recording_toggled {
+            match self.recording {
+                Some(_) => self.stop_recording()?,
+                None => self.start_recording(data.recording),
+            }
+        }
         
         //self.compute_effects.metadatas[data.background_index].data[index] = data.push_constants[index];
         /*
@@ -227,29 +324,95 @@ impl VInit {
             self.compute_effects.metadatas[data.background_index].data[index] = data.push_constants[index];
         }
         */
+        Ok(())
     }
     
     
+    pub fn request_capture(&mut self, request:CaptureRequest) {
+        self.capture_request = Some(request);
+    }
+    
+    // a recording that can't be started is only logged, the renderer is fine
+    pub fn start_recording(&mut self, settings:RecordingSettings) {
+        let config::RecordingConfig{encoder, directory, ..} = &self.recording_config;
+        match Recording::start(settings, encoder.as_deref(), directory) {
+            Ok(recording) => {
+                self.recording = Some(recording);
+            }
+            Err(error) => {
+                logger::various_log!("vulkan",
+                    (logger::Error, "recording could not be started {}", error)
+                );
+            }
+        }
+    }
+    
+    // the captures in flight are read back first so the recording gets every frame
+    pub fn stop_recording(&mut self) -> Result<(), AAError> {
+        let Some(recording) = self.recording.take() else {
+            return Ok(());
+        };
+        self.wait_idle()?;
+        self.complete_captures();
+        recording.finish();
+        Ok(())
+    }
+    
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+    
+    // simulated seconds per rendered frame while recording, the game steps by it instead of the real time
+    pub fn recording_timestep(&self) -> Option<f32> {
+        self.recording.as_ref().map(Recording::timestep)
+    }
+    
+//----
+    // the mesh picked in the gui, None until the streamer delivers it
+    pub fn selected_mesh(&self) -> Option<MeshHandle> {
+        self.assets.mesh_list().get(self.mesh_index).map(|entry|entry.handle)
+    }
+    
+    // oldest frame first, the recording numbers its frames in arrival order
+    fn complete_captures(&mut self) {
+        let oldest = self.get_frame() + 1;
+        for offset in 0..constants::fif::USIZE {
+            let frame = (oldest + offset) % constants::fif::USIZE;
+            if let Some(capture) = self.frames_data.get_capture(frame).take() {
+                capture.complete(&mut self.device, &mut self.allocator);
+            }
+        }
+    }
+    
     #[inline(always)]
-    pub fn wait_idle(&self) {
-        unsafe{self.device.device_wait_idle()}.expect(SIMPLE_VK_FN);
+    pub fn wait_idle(&self) -> Result<(), AAError> {
+        Ok(unsafe{self.device.device_wait_idle()}?)
     }
     
-    pub fn handle_events(&mut self, window:&Window) {
+    pub fn handle_events(&mut self, window:&mut Window) -> Result<(), AAError> {
+        for path in window.take_dropped_files() {
+            self.open_model(config::asset_path(path));
+        }
         if self.resize_required {
-            self.wait_idle();
-            self.handle_resize(window);
+            self.wait_idle()?;
+            self.handle_resize(window)?;
             self.resize_required = false;
         }
+        if self.recording.as_ref().is_some_and(Recording::is_finished) {
+            self.stop_recording()?;
+        }
+        Ok(())
     }
     
-    pub fn handle_resize(&mut self, window:&Window) {
+    // also what a lost surface goes through, the surface is created again with the swapchain
+    pub fn handle_resize(&mut self, window:&Window) -> Result<(), AAError> {
         let VInit{
             swapchain,
             instance,
             surface,
             p_device,
             device,
+            present_mode,
             ..
         } = self;
         logger::various_log!("vulkan",
@@ -262,11 +425,11 @@ impl VInit {
         old_swapchain_holder.destruct(VkDestructorArguments::Dev(device));
         old_surface_holder.destruct(VkDestructorArguments::None);
         
-        let new_surface_holder = Surface::create(&window, instance).expect(VK_UNRECOVERABLE);
-        let new_swapchaint_holder = Swapchain::create(instance, &new_surface_holder, p_device, device).expect(VK_UNRECOVERABLE);
-        
-        surface.fill(new_surface_holder);
+        // a failure leaves them empty, only a rebuild follows and the teardown skips what is empty
+        surface.fill(Surface::create(window, instance)?);
+        let new_swapchaint_holder = Swapchain::create(instance, surface, p_device, device, *present_mode)?;
         swapchain.fill(new_swapchaint_holder);
+        Ok(())
     }
     
     
@@ -284,24 +447,28 @@ impl VInit {
     ) ->  (
         (
             &[ArrayString<64>],
-            &[std::rc::Rc<VkMeshAsset>],
+            &[MeshEntry],
+            &GpuTimings,
+            &[StreamProgress],
+            Option<(u32, u32)>,
         ), (
             &dyn Fn(&ArrayString<64>)->&str,
-            &dyn Fn(&std::rc::Rc<VkMeshAsset>)->&str,
+            &dyn Fn(&MeshEntry)->&str,
         ),(
             &mut usize,
             &mut ComputePushConstants,
             &mut usize, 
             &mut Vector3<f32>,
             &mut f32,
+            &mut bool,
         )
     ) {
         let ComputeEffects{ref names, ref mut push_constants, ..} = *self.compute_effects;
         let index = self.compute_effect_index;
         (
-            (names, &self.mesh_assets[..]), 
+            (names, self.assets.mesh_list(), &self.gpu_timings, self.asset_streamer.progress(), self.recording.as_ref().map(Recording::progress)), 
             (&|holder|{holder}, &|holder|{&holder.name}),
-            (&mut self.compute_effect_index, &mut push_constants[index], &mut self.mesh_index, &mut self.field_of_view, &mut self.downscale_coheficient, )
+            (&mut self.compute_effect_index, &mut push_constants[index], &mut self.mesh_index, &mut self.field_of_view, &mut self.downscale_coheficient, &mut self.gpu_driven, )
         )
     }
     
@@ -310,16 +477,6 @@ impl VInit {
 
 
 
-#[inline]
-pub fn vk_create_interpreter<T, A:std::fmt::Debug>(result:Result<T, A>, name:&str) -> T {
-    match result {
-        Ok(device) => {
-            device
-        }
-        Err(err) => {panic!("error in {} {:?}", name, err);}
-    }
-}
-
 impl Drop for VInit {
     
     fn drop(&mut self) {
@@ -331,16 +488,22 @@ impl Drop for VInit {
             allocator, 
             swapchain, 
             command_control, 
+            upload_manager,
+            asset_streamer,
             
             canvas,
+            transient_images,
             
             ds_pool, 
             background_image_descriptor_layout, 
             texture_descriptor_layout, 
             compute_effects, 
-            mesh_assets,
+            assets,
+            geometry,
+            indirect_drawer,
             
             materials,
+            bindless,
             
             frames_data,
             gpu_scene_layout,
@@ -372,9 +535,14 @@ impl Drop for VInit {
         frames_data.destruct(VkDestructorArguments::DevAll(dev, all));
         
         
-        mesh_assets.destruct(VkDestructorArguments::DevAll(dev, all));
+        // before the materials, the instances it holds keep their pipelines referenced
+        assets.destruct(VkDestructorArguments::DevAll(dev, all));
+        asset_streamer.destruct(VkDestructorArguments::None);
+        geometry.destruct(VkDestructorArguments::DevAll(dev, all));
+        indirect_drawer.destruct(VkDestructorArguments::Dev(dev));
         
         materials.destruct(VkDestructorArguments::Dev(dev));
+        bindless.destruct(VkDestructorArguments::DevAll(dev, all));
         
         //mesh_assets.destruct(VkDestructorArguments::DevAll(dev, all));
         //mesh_pipeline.destruct(VkDestructorArguments::Dev(dev));
@@ -391,13 +559,19 @@ impl Drop for VInit {
         depth_image.destruct(VkDestructorArguments::DevAll(dev, all));
         */
         command_control.destruct(VkDestructorArguments::Dev(dev));
+        upload_manager.destruct(VkDestructorArguments::DevAll(dev, all));
         
         canvas.destruct(VkDestructorArguments::DevAll(dev, all));
+        transient_images.destruct(VkDestructorArguments::DevAll(dev, all));
         
-        swapchain.destruct(VkDestructorArguments::Dev(dev));
+        if swapchain.is_filled() {
+            swapchain.destruct(VkDestructorArguments::Dev(dev));
+        }
         all.destruct(VkDestructorArguments::Dev(dev));
         dev.destruct(VkDestructorArguments::None);
-        surface.destruct(VkDestructorArguments::None);
+        if surface.is_filled() {
+            surface.destruct(VkDestructorArguments::None);
+        }
         
         match messenger {
             Some(messenger) => {
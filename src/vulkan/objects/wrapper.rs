@@ -30,6 +30,11 @@ impl<T:VkDestructor> VkWrapper<T> {
         self.0.take().expect(ALREADY_DESTROYED)
     }
     
+//----
+    pub fn is_filled(&self) -> bool {
+        self.0.is_some()
+    }
+    
 //----
     pub fn fill(&mut self, new_value:T) {
         match &mut self.0 {
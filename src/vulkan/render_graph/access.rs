@@ -0,0 +1,142 @@
+use ash::vk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceState {
+    pub layout: vk::ImageLayout,
+    pub stage: vk::PipelineStageFlags2,
+    pub access: vk::AccessFlags2,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    ComputeStorageWrite,
+    ComputeStorageRead,
+    ColorAttachmentWrite,
+    DepthAttachmentWrite,
+    FragmentSampled,
+    TransferSrc,
+    TransferDst,
+    BlitSrc,
+    BlitDst,
+    Present,
+    
+    VertexStorageRead,
+    IndexRead,
+    IndirectRead,
+    ComputeBufferWrite,
+    ComputeBufferRead,
+    UniformRead,
+    BufferClear,
+}
+
+impl ResourceState {
+    
+    // state of a resource whose content can be discarded. the memory may still be written by the previous
+    // frame in flight, transient images are shared by all of them, so the source has to make every write
+    // available and not only order the commands
+    pub const UNDEFINED:ResourceState = ResourceState{
+        layout: vk::ImageLayout::UNDEFINED,
+        stage: vk::PipelineStageFlags2::ALL_COMMANDS,
+        access: vk::AccessFlags2::MEMORY_WRITE,
+    };
+    
+    // swapchain images only need to wait for the acquire semaphore stage
+    pub const ACQUIRED:ResourceState = ResourceState{
+        layout: vk::ImageLayout::UNDEFINED,
+        stage: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        access: vk::AccessFlags2::NONE,
+    };
+    
+    pub fn is_write(&self) -> bool {
+        use vk::AccessFlags2 as AF;
+        let write_mask = AF::SHADER_STORAGE_WRITE | AF::SHADER_WRITE | AF::COLOR_ATTACHMENT_WRITE | AF::DEPTH_STENCIL_ATTACHMENT_WRITE | AF::TRANSFER_WRITE | AF::HOST_WRITE | AF::MEMORY_WRITE;
+        self.access.intersects(write_mask)
+    }
+    
+    // moves the state to the one required by access, returning the barrier halves when one is needed.
+    // consecutive reads on the same layout don't need a barrier, their stages get merged so the next
+    // write waits on all of them
+    pub fn transition_to(&mut self, access:Access, track_layout:bool) -> Option<(ResourceState, ResourceState)> {
+        let next = access.state();
+        match derive_barrier(*self, next, track_layout) {
+            Some(barrier) => {
+                *self = next;
+                Some(barrier)
+            }
+            None => {
+                self.stage |= next.stage;
+                self.access |= next.access;
+                None
+            }
+        }
+    }
+}
+
+impl Access {
+    pub fn state(self) -> ResourceState {
+        use vk::ImageLayout as IL;
+        use vk::PipelineStageFlags2 as PS;
+        use vk::AccessFlags2 as AF;
+        let (layout, stage, access) = match self {
+            Access::ComputeStorageWrite => (IL::GENERAL, PS::COMPUTE_SHADER, AF::SHADER_STORAGE_WRITE),
+            Access::ComputeStorageRead => (IL::GENERAL, PS::COMPUTE_SHADER, AF::SHADER_STORAGE_READ),
+            Access::ColorAttachmentWrite => (IL::COLOR_ATTACHMENT_OPTIMAL, PS::COLOR_ATTACHMENT_OUTPUT, AF::COLOR_ATTACHMENT_READ | AF::COLOR_ATTACHMENT_WRITE),
+            Access::DepthAttachmentWrite => (IL::DEPTH_ATTACHMENT_OPTIMAL, PS::EARLY_FRAGMENT_TESTS | PS::LATE_FRAGMENT_TESTS, AF::DEPTH_STENCIL_ATTACHMENT_READ | AF::DEPTH_STENCIL_ATTACHMENT_WRITE),
+            Access::FragmentSampled => (IL::SHADER_READ_ONLY_OPTIMAL, PS::FRAGMENT_SHADER, AF::SHADER_SAMPLED_READ),
+            Access::TransferSrc => (IL::TRANSFER_SRC_OPTIMAL, PS::COPY, AF::TRANSFER_READ),
+            Access::TransferDst => (IL::TRANSFER_DST_OPTIMAL, PS::COPY, AF::TRANSFER_WRITE),
+            Access::BlitSrc => (IL::TRANSFER_SRC_OPTIMAL, PS::BLIT, AF::TRANSFER_READ),
+            Access::BlitDst => (IL::TRANSFER_DST_OPTIMAL, PS::BLIT, AF::TRANSFER_WRITE),
+            Access::Present => (IL::PRESENT_SRC_KHR, PS::NONE, AF::NONE),
+            
+            Access::VertexStorageRead => (IL::UNDEFINED, PS::VERTEX_SHADER, AF::SHADER_STORAGE_READ),
+            Access::IndexRead => (IL::UNDEFINED, PS::INDEX_INPUT, AF::INDEX_READ),
+            Access::IndirectRead => (IL::UNDEFINED, PS::DRAW_INDIRECT, AF::INDIRECT_COMMAND_READ),
+            Access::ComputeBufferWrite => (IL::UNDEFINED, PS::COMPUTE_SHADER, AF::SHADER_STORAGE_WRITE),
+            Access::ComputeBufferRead => (IL::UNDEFINED, PS::COMPUTE_SHADER, AF::SHADER_STORAGE_READ),
+            Access::UniformRead => (IL::UNDEFINED, PS::VERTEX_SHADER | PS::FRAGMENT_SHADER, AF::UNIFORM_READ),
+            Access::BufferClear => (IL::UNDEFINED, PS::CLEAR, AF::TRANSFER_WRITE),
+        };
+        ResourceState{layout, stage, access}
+    }
+}
+
+
+// returns the (src, dst) halves of the barrier needed to go from one state to the other,
+// None when both accesses are reads on the same layout
+pub fn derive_barrier(previous:ResourceState, next:ResourceState, track_layout:bool) -> Option<(ResourceState, ResourceState)> {
+    let layout_change = track_layout && previous.layout != next.layout;
+    if !layout_change && !previous.is_write() && !next.is_write() {
+        return None;
+    }
+    let src = ResourceState{
+        layout: previous.layout,
+        stage: previous.stage,
+        // only writes need to be made available, reads just need the execution dependency
+        access: if previous.is_write() {previous.access} else {vk::AccessFlags2::NONE},
+    };
+    Some((src, next))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn discarded_content_waits_for_previous_writes() {
+        let (src, dst) = derive_barrier(ResourceState::UNDEFINED, Access::DepthAttachmentWrite.state(), true).unwrap();
+        assert!(src.access.contains(vk::AccessFlags2::MEMORY_WRITE));
+        assert_eq!(src.stage, vk::PipelineStageFlags2::ALL_COMMANDS);
+        assert_eq!(dst.layout, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
+    }
+    
+    #[test]
+    fn reads_share_a_layout_without_barrier() {
+        let mut state = Access::FragmentSampled.state();
+        assert!(state.transition_to(Access::FragmentSampled, true).is_none());
+        let (src, _) = state.transition_to(Access::ColorAttachmentWrite, true).unwrap();
+        assert_eq!(src.access, vk::AccessFlags2::NONE);
+    }
+}
@@ -0,0 +1,356 @@
+mod access;
+pub use access::*;
+
+mod transient;
+pub use transient::TransientImages;
+
+use crate::AAError;
+use crate::logger;
+
+use super::Device;
+use super::Allocator;
+use super::Image;
+use super::FrameQueries;
+use super::image::ImageMetadata;
+
+use ash::vk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHandle(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferHandle(usize);
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct GraphImage {
+    pub image: vk::Image,
+    pub view: vk::ImageView,
+    pub extent: vk::Extent3D,
+    pub format: vk::Format,
+    pub aspect: vk::ImageAspectFlags,
+}
+
+enum ImageSource {
+    Imported(GraphImage),
+    Transient(vk::Extent3D, ImageMetadata),
+}
+
+struct ImageResource<'a> {
+    name: &'static str,
+    source: ImageSource,
+    state: ResourceState,
+    final_access: Option<Access>,
+    tracked: Option<&'a mut Image>,
+}
+
+struct BufferResource {
+    name: &'static str,
+    buffer: vk::Buffer,
+    state: ResourceState,
+}
+
+pub type PassExecutor<'a> = Box<dyn FnOnce(&mut Device, vk::CommandBuffer, &PassResources) + 'a>;
+
+struct Pass<'a> {
+    name: &'static str,
+    images: Vec<(ImageHandle, Access)>,
+    buffers: Vec<(BufferHandle, Access)>,
+    statistics: bool,
+    executor: PassExecutor<'a>,
+}
+
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut RenderGraph<'a>,
+    name: &'static str,
+    images: Vec<(ImageHandle, Access)>,
+    buffers: Vec<(BufferHandle, Access)>,
+    statistics: bool,
+}
+
+#[allow(dead_code)]
+pub struct PassResources {
+    images: Vec<GraphImage>,
+    buffers: Vec<vk::Buffer>,
+}
+
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    images: Vec<ImageResource<'a>>,
+    buffers: Vec<BufferResource>,
+    passes: Vec<Pass<'a>>,
+}
+
+
+impl GraphImage {
+    pub fn from_image(image:&Image, aspect:vk::ImageAspectFlags) -> Self {
+        Self{
+            image: image.image,
+            view: image.view,
+            extent: image.extent,
+            format: image.format,
+            aspect,
+        }
+    }
+    
+    pub fn extent_2d(&self) -> vk::Extent2D {
+        vk::Extent2D{width:self.extent.width, height:self.extent.height}
+    }
+}
+
+impl PassResources {
+    pub fn image(&self, handle:ImageHandle) -> &GraphImage {
+        &self.images[handle.0]
+    }
+    
+    #[allow(dead_code)]
+    pub fn buffer(&self, handle:BufferHandle) -> vk::Buffer {
+        self.buffers[handle.0]
+    }
+}
+
+
+impl<'g, 'a> PassBuilder<'g, 'a> {
+    
+    pub fn read_image(mut self, handle:ImageHandle, access:Access) -> Self {
+        debug_assert!(!access.state().is_write(), "{:?} is not a read access", access);
+        self.images.push((handle, access));
+        self
+    }
+    
+    pub fn write_image(mut self, handle:ImageHandle, access:Access) -> Self {
+        debug_assert!(access.state().is_write(), "{:?} is not a write access", access);
+        self.images.push((handle, access));
+        self
+    }
+    
+    pub fn read_buffer(mut self, handle:BufferHandle, access:Access) -> Self {
+        debug_assert!(!access.state().is_write(), "{:?} is not a read access", access);
+        self.buffers.push((handle, access));
+        self
+    }
+    
+    pub fn write_buffer(mut self, handle:BufferHandle, access:Access) -> Self {
+        debug_assert!(access.state().is_write(), "{:?} is not a write access", access);
+        self.buffers.push((handle, access));
+        self
+    }
+    
+    // records pipeline statistics for the pass, only one pass per frame can use it
+    pub fn statistics(mut self) -> Self {
+        self.statistics = true;
+        self
+    }
+    
+    pub fn execute<F:FnOnce(&mut Device, vk::CommandBuffer, &PassResources) + 'a>(self, executor:F) {
+        let PassBuilder{graph, name, images, buffers, statistics} = self;
+        graph.passes.push(Pass{
+            name,
+            images,
+            buffers,
+            statistics,
+            executor: Box::new(executor),
+        });
+    }
+}
+
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+//----
+    // final_access is the state the image is left in after the graph, None leaves it in the last used state
+    pub fn import_image(&mut self, name:&'static str, image:GraphImage, initial:ResourceState, final_access:Option<Access>) -> ImageHandle {
+        self.images.push(ImageResource{
+            name,
+            source: ImageSource::Imported(image),
+            state: initial,
+            final_access,
+            tracked: None,
+        });
+        ImageHandle(self.images.len() - 1)
+    }
+    
+//----
+    // the initial state is read from the image and the state it is left in is written back after execute
+    pub fn import_tracked_image(&mut self, name:&'static str, image:&'a mut Image, final_access:Option<Access>) -> ImageHandle {
+        self.images.push(ImageResource{
+            name,
+            source: ImageSource::Imported(GraphImage::from_image(image, image.aspect)),
+            state: image.state(),
+            final_access,
+            tracked: Some(image),
+        });
+        ImageHandle(self.images.len() - 1)
+    }
+    
+//----
+    // transient images are only valid inside the graph, their content is discarded every frame
+    pub fn create_image(&mut self, name:&'static str, extent:vk::Extent3D, metadata:ImageMetadata) -> ImageHandle {
+        self.images.push(ImageResource{
+            name,
+            source: ImageSource::Transient(extent, metadata),
+            state: ResourceState::UNDEFINED,
+            final_access: None,
+            tracked: None,
+        });
+        ImageHandle(self.images.len() - 1)
+    }
+    
+//----
+    pub fn import_buffer(&mut self, name:&'static str, buffer:vk::Buffer, initial:ResourceState) -> BufferHandle {
+        self.buffers.push(BufferResource{
+            name,
+            buffer,
+            state: initial,
+        });
+        BufferHandle(self.buffers.len() - 1)
+    }
+    
+//----
+    pub fn add_pass<'g>(&'g mut self, name:&'static str) -> PassBuilder<'g, 'a> {
+        PassBuilder{
+            graph: self,
+            name,
+            images: Vec::new(),
+            buffers: Vec::new(),
+            statistics: false,
+        }
+    }
+    
+//----
+    pub fn execute(
+        self,
+        device: &mut Device,
+        allocator: &mut Allocator,
+        transient_images: &mut TransientImages,
+        cmd: vk::CommandBuffer,
+        mut queries: Option<&mut FrameQueries>,
+    ) -> Result<(), AAError> {
+        
+        let RenderGraph{mut images, mut buffers, passes} = self;
+        
+        transient_images.begin_frame(device, allocator);
+        let mut resolved = Vec::with_capacity(images.len());
+        for resource in images.iter() {
+            let image = match &resource.source {
+                ImageSource::Imported(image) => {*image}
+                ImageSource::Transient(extent, metadata) => {
+                    let image = transient_images.acquire(device, allocator, *extent, metadata)?;
+                    GraphImage::from_image(image, metadata.aspect_flags())
+                }
+            };
+            resolved.push(image);
+        }
+        let resources = PassResources{
+            images: resolved,
+            buffers: buffers.iter().map(|resource|resource.buffer).collect(),
+        };
+        
+        for pass in passes.into_iter() {
+            let Pass{name, images:image_accesses, buffers:buffer_accesses, statistics, executor} = pass;
+            // the barriers go inside so captures show them with the pass that needs them
+            device.begin_label(cmd, name);
+            
+            let mut image_barriers = Vec::new();
+            for (handle, access) in image_accesses.iter() {
+                let resource = &mut images[handle.0];
+                let image = &resources.images[handle.0];
+                if let Some(barrier) = resource.state.transition_to(*access, true).map(|(src, dst)|Self::image_barrier(image, src, dst)) {
+                    logger::various_log!("render_graph",
+                        (logger::Trace, "{} {} {:?} -> {:?}", name, resource.name, barrier.old_layout, barrier.new_layout)
+                    );
+                    image_barriers.push(barrier);
+                }
+            }
+            
+            let mut buffer_barriers = Vec::new();
+            for (handle, access) in buffer_accesses.iter() {
+                let resource = &mut buffers[handle.0];
+                if let Some((src, dst)) = resource.state.transition_to(*access, false) {
+                    logger::various_log!("render_graph",
+                        (logger::Trace, "{} {} {:?} -> {:?}", name, resource.name, src.stage, dst.stage)
+                    );
+                    buffer_barriers.push(Self::buffer_barrier(resource.buffer, src, dst));
+                }
+            }
+            
+            Self::flush_barriers(device, cmd, &image_barriers, &buffer_barriers);
+            
+            if let Some(queries) = queries.as_deref_mut() {
+                queries.begin_scope(device, cmd, name);
+                if statistics {
+                    queries.begin_statistics(device, cmd);
+                }
+            }
+            
+            executor(device, cmd, &resources);
+            
+            if let Some(queries) = queries.as_deref_mut() {
+                if statistics {
+                    queries.end_statistics(device, cmd);
+                }
+                queries.end_scope(device, cmd);
+            }
+            device.end_label(cmd);
+        }
+        
+        let mut final_barriers = Vec::new();
+        for (index, resource) in images.iter_mut().enumerate() {
+            if let Some(access) = resource.final_access {
+                if let Some((src, dst)) = resource.state.transition_to(access, true) {
+                    final_barriers.push(Self::image_barrier(&resources.images[index], src, dst));
+                }
+            }
+        }
+        Self::flush_barriers(device, cmd, &final_barriers, &[]);
+        
+        for resource in images.into_iter() {
+            if let Some(image) = resource.tracked {
+                image.assume_state(resource.state);
+            }
+        }
+        
+        Ok(())
+    }
+    
+//----
+    fn image_barrier(image:&GraphImage, src:ResourceState, dst:ResourceState) -> vk::ImageMemoryBarrier2 {
+        vk::ImageMemoryBarrier2::builder()
+            .image(image.image)
+            .old_layout(src.layout)
+            .new_layout(dst.layout)
+            .src_stage_mask(src.stage)
+            .src_access_mask(src.access)
+            .dst_stage_mask(dst.stage)
+            .dst_access_mask(dst.access)
+            .subresource_range(Image::subresource_range(image.aspect))
+            .build()
+    }
+    
+//----
+    fn buffer_barrier(buffer:vk::Buffer, src:ResourceState, dst:ResourceState) -> vk::BufferMemoryBarrier2 {
+        vk::BufferMemoryBarrier2::builder()
+            .buffer(buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE)
+            .src_stage_mask(src.stage)
+            .src_access_mask(src.access)
+            .dst_stage_mask(dst.stage)
+            .dst_access_mask(dst.access)
+            .build()
+    }
+    
+//----
+    fn flush_barriers(device:&Device, cmd:vk::CommandBuffer, image_barriers:&[vk::ImageMemoryBarrier2], buffer_barriers:&[vk::BufferMemoryBarrier2]) {
+        if image_barriers.is_empty() && buffer_barriers.is_empty() {
+            return;
+        }
+        let dependency = vk::DependencyInfo::builder()
+            .image_memory_barriers(image_barriers)
+            .buffer_memory_barriers(buffer_barriers);
+        unsafe{device.cmd_pipeline_barrier2(cmd, &dependency)};
+    }
+}
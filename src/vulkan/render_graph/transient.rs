@@ -0,0 +1,100 @@
+use crate::AAError;
+use crate::logger;
+use crate::constants;
+
+use super::super::VkDestructor;
+use super::super::VkDestructorArguments;
+use super::super::Device;
+use super::super::Allocator;
+use super::super::Image;
+use super::super::image::ImageMetadata;
+
+use ash::vk;
+
+struct TransientImage {
+    metadata: ImageMetadata,
+    image: Image,
+    in_use: bool,
+    last_used: u64,
+}
+
+// one pool for every frame in flight, the graph starts them from ResourceState::UNDEFINED
+// so the writes of a frame still running are waited for
+#[derive(Default)]
+pub struct TransientImages {
+    images: Vec<TransientImage>,
+    frame: u64,
+}
+
+impl TransientImages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+//----
+    // the fence of the frame was waited, so an image no frame in flight used can be freed,
+    // that is what is left of a resize or a format change
+    pub fn begin_frame(&mut self, device:&mut Device, allocator:&mut Allocator) {
+        self.frame += 1;
+        let frame = self.frame;
+        let (kept, stale):(Vec<_>, Vec<_>) = std::mem::take(&mut self.images).into_iter().partition(|transient|{
+            frame - transient.last_used <= constants::fif::USIZE as u64
+        });
+        for transient in stale {
+            logger::various_log!("render_graph",
+                (logger::Debug, "freeing transient image {:?} {:?}", transient.metadata.format, transient.image.extent)
+            );
+            transient.image.destruct(VkDestructorArguments::DevAll(device, allocator));
+        }
+        self.images = kept;
+        for image in self.images.iter_mut() {
+            image.in_use = false;
+        }
+    }
+    
+//----
+    pub fn acquire(
+        &mut self,
+        device: &mut Device,
+        allocator: &mut Allocator,
+        extent: vk::Extent3D,
+        metadata: &ImageMetadata,
+    ) -> Result<&Image, AAError> {
+        
+        let position = self.images.iter().position(|transient|{
+            !transient.in_use && transient.image.extent == extent && transient.metadata == *metadata
+        });
+        
+        let index = match position {
+            Some(index) => index,
+            None => {
+                logger::various_log!("render_graph",
+                    (logger::Debug, "allocating transient image {:?} {:?}", metadata.format, extent)
+                );
+                let image = Image::create(device, allocator, extent, metadata.clone(), None)?;
+                self.images.push(TransientImage{
+                    metadata: metadata.clone(),
+                    image,
+                    in_use: false,
+                    last_used: self.frame,
+                });
+                self.images.len() - 1
+            }
+        };
+        
+        let transient = &mut self.images[index];
+        transient.in_use = true;
+        transient.last_used = self.frame;
+        Ok(&transient.image)
+    }
+}
+
+impl VkDestructor for TransientImages {
+    fn destruct(self, mut args:VkDestructorArguments) {
+        logger::destruct!("transient_images");
+        let (device, allocator) = args.unwrap_dev_all();
+        for transient in self.images.into_iter() {
+            transient.image.destruct(VkDestructorArguments::DevAll(device, allocator));
+        }
+    }
+}
@@ -3,9 +3,13 @@ mod vk_win;
 use crate::logger;
 use crate::errors::messages::SIMPLE_SDL_FN;
 use crate::gui::Gui;
-use crate::player::Movement;
+use crate::config::WindowConfig;
+use crate::input;
+use crate::input::InputMap;
+use crate::input::InputState;
 
 use std::mem::ManuallyDrop;
+use std::path::PathBuf;
 
 
 #[allow(dead_code)]
@@ -14,7 +18,13 @@ pub struct Window {
     video_subsys: sdl2::VideoSubsystem,
     window: sdl2::video::Window,
     event_pump: sdl2::EventPump,
+    // None when sdl has no controller support, the keyboard and mouse still work
+    controller_subsys: Option<sdl2::GameControllerSubsystem>,
+    controllers: Vec<sdl2::controller::GameController>,
+    input: InputState,
     should_quit: bool,
+    dropped_files: Vec<PathBuf>,
+    screenshot_requested: bool,
 }
 
 
@@ -27,21 +37,32 @@ impl Window {
         &self.window
     }
     
-    pub fn init() -> Window {
+    pub fn init(config:&WindowConfig, input_map:&InputMap) -> Window {
         
         logger::create!("window");
         
         let sdl = sdl2::init().expect(SIMPLE_SDL_FN);
         let mut video_subsys = sdl.video().expect(SIMPLE_SDL_FN);
-        let window = Self::create_vulkan_builder(&mut video_subsys).unwrap();
+        let window = Self::create_vulkan_builder(&mut video_subsys, config).unwrap();
         let event_pump = sdl.event_pump().expect(SIMPLE_SDL_FN);
+        // the already connected controllers arrive as added events on the first poll
+        let controller_subsys = sdl.game_controller().map_err(|error|{
+            logger::various_log!("window",
+                (logger::Warn, "game controllers not available {}", error)
+            );
+        }).ok();
         
         Self{
             sdl: ManuallyDrop::new(sdl),
             video_subsys,
             window,
             event_pump,
+            controller_subsys,
+            controllers: Vec::new(),
+            input: InputState::new(input_map.clone()),
             should_quit: false,
+            dropped_files: Vec::new(),
+            screenshot_requested: false,
         }
     }
     
@@ -49,23 +70,62 @@ impl Window {
         self.should_quit
     }
     
-    pub fn poll_events(&mut self, gui:&mut Gui) -> Movement {
+    pub fn take_dropped_files(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.dropped_files)
+    }
+    
+    pub fn take_screenshot_request(&mut self) -> bool {
+        std::mem::take(&mut self.screenshot_requested)
+    }
+    
+    pub fn input(&self) -> &InputState {
+        &self.input
+    }
+    
+    pub fn input_mut(&mut self) -> &mut InputState {
+        &mut self.input
+    }
+    
+    // the gui gets what it asked for through want_capture_*, the game the rest,
+    // releases and mouse motion reach both so neither keeps stale state
+    pub fn poll_events(&mut self, gui:&mut Gui) {
         use sdl2::event::Event;
-        use sdl2::keyboard::Keycode;
         
+        self.input.begin_frame();
+        let io = gui.context.io();
+        let (gui_keyboard, gui_mouse, gui_text) = (io.want_capture_keyboard, io.want_capture_mouse, io.want_text_input);
+        let mut added_controllers = Vec::new();
         
         for event in self.event_pump.poll_iter() {
-            gui.platform.handle_event(&mut gui.context, &event);
+            let (to_gui, to_game) = match event {
+                Event::KeyDown{..} => (gui_keyboard || gui_text, !(gui_keyboard || gui_text)),
+                Event::TextInput{..} | Event::TextEditing{..} => (gui_text, false),
+                Event::MouseButtonDown{..} | Event::MouseWheel{..} => (gui_mouse, !gui_mouse),
+                Event::MouseMotion{..} => (true, !gui_mouse),
+                Event::KeyUp{..} | Event::MouseButtonUp{..} => (true, true),
+                Event::ControllerButtonDown{..} | Event::ControllerButtonUp{..} | Event::ControllerAxisMotion{..} => (false, true),
+                _ => (true, false),
+            };
+            if to_gui {
+                gui.platform.handle_event(&mut gui.context, &event);
+            }
+            if to_game {
+                self.input.handle_event(&event);
+            }
+            
             match event {
-                Event::Quit { .. } | Event::KeyDown{keycode: Some(Keycode::Escape),.. } => {
-                    self.should_quit = true;
-                }//break running,
-                Event::KeyDown{keycode: Some(Keycode::Q),.. } => {
-                    println!("Q");
+                Event::Quit { .. } => {
                     self.should_quit = true;
                 }
-                Event::KeyDown{keycode: Some(Keycode::W),.. } => {
-                    println!("{:?}", self.window.size());
+                Event::DropFile{filename, ..} => {
+                    self.dropped_files.push(PathBuf::from(filename));
+                }
+                Event::ControllerDeviceAdded{which, ..} => {
+                    added_controllers.push(which);
+                }
+                Event::ControllerDeviceRemoved{which, ..} => {
+                    self.controllers.retain(|controller|controller.instance_id() != which);
+                    self.input.release_controllers();
                 }
                 _event @ Event::Window{..} => {
                     //println!("{:?}", event);
@@ -73,7 +133,35 @@ impl Window {
                 _ => {}
             }
         }
-        Movement::Up
+        for joystick_index in added_controllers {
+            self.open_controller(joystick_index);
+        }
+        
+        if self.input.is_pressed(input::QUIT) {
+            self.should_quit = true;
+        }
+        if self.input.is_pressed(input::SCREENSHOT) {
+            self.screenshot_requested = true;
+        }
+    }
+    
+    fn open_controller(&mut self, joystick_index:u32) {
+        let Some(controller_subsys) = &self.controller_subsys else {
+            return;
+        };
+        match controller_subsys.open(joystick_index) {
+            Ok(controller) => {
+                logger::various_log!("window",
+                    (logger::Info, "controller connected {}", controller.name())
+                );
+                self.controllers.push(controller);
+            }
+            Err(error) => {
+                logger::various_log!("window",
+                    (logger::Warn, "controller {} could not be opened {}", joystick_index, error)
+                );
+            }
+        }
     }
     
     pub fn get_required_instance_extentions(&self) -> Vec<&'static str> {
@@ -1,6 +1,7 @@
 use crate::vulkan;
 use crate::AAError;
-use crate::constants;
+use crate::config::WindowConfig;
+use crate::config::WindowMode;
 
 
 
@@ -33,12 +34,19 @@ impl Window {
         */
     }
     
-    pub fn create_vulkan_builder(video:&mut VideoSubsystem) -> Result<sdl2::video::Window, AAError> {
-        video.window("rust-sdl2 demo", constants::WIDTH, constants::HEIGTH)
+    pub fn create_vulkan_builder(video:&mut VideoSubsystem, config:&WindowConfig) -> Result<sdl2::video::Window, AAError> {
+        let mut builder = video.window("rust-sdl2 demo", config.width, config.height);
+        builder
             .resizable()
             .position_centered()
-            .vulkan()
-            .build().map_err(|err|err.into())
+            .vulkan();
+        match config.mode {
+            WindowMode::Windowed => {}
+            WindowMode::Fullscreen => {builder.fullscreen();}
+            // covers the desktop at its resolution, the configured size is ignored
+            WindowMode::Borderless => {builder.fullscreen_desktop();}
+        }
+        builder.build().map_err(|err|err.into())
     }
 }
     